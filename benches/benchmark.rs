@@ -1,29 +1,18 @@
-use std::convert::TryInto;
+use std::sync::Arc;
 
-use astar_rust_wasm::{
-    astar::{astar::find_path, point::Point},
-    utils::{normalize, rgb_to_hsv},
+use astar_rust_wasm::astar::{
+    astar::{find_path, find_path_with_landmarks},
+    batch::find_paths,
+    landmarks::{LandmarkStrategy, Landmarks},
+    point::Point,
 };
+use common::castle_weights;
 use criterion::{criterion_group, criterion_main, Criterion};
 
-fn criterion_benchmark(c: &mut Criterion) {
-    let mut bytes: &[u8] = include_bytes!("../assets/castle.bmp");
-    let image = bmp::from_reader(&mut bytes).unwrap();
-    let height = image.get_height();
-    let width = image.get_width();
-
-    let mut cell_weights = vec![0.0; (width * height).try_into().unwrap()];
+mod common;
 
-    for y in 0..height {
-        for x in 0..width {
-            let pixel = image.get_pixel(x, y);
-            let hsv = rgb_to_hsv(pixel.r, pixel.g, pixel.b);
-            let inverted_brighntess = (hsv.brightness - 1.0).abs();
-            let normalized_brighntess = normalize(0.0, 1.0, 1.0, 10.0, inverted_brighntess);
-
-            cell_weights[Point::new(x, y).to_1d_index(width) as usize] = normalized_brighntess;
-        }
-    }
+fn criterion_benchmark(c: &mut Criterion) {
+    let (cell_weights, width, height) = castle_weights();
 
     find_path(
         Point { x: 0, y: 37 },
@@ -48,6 +37,62 @@ fn criterion_benchmark(c: &mut Criterion) {
             )
         })
     });
+
+    let landmarks = Arc::new(Landmarks::select(
+        8,
+        LandmarkStrategy::Avoid,
+        width,
+        height,
+        &cell_weights,
+    ));
+
+    c.bench_function("castle alt", |b| {
+        b.iter(|| {
+            find_path_with_landmarks(
+                Point { x: 0, y: 37 },
+                Point { x: 99, y: 12 },
+                width,
+                height,
+                1,
+                1.0,
+                &cell_weights,
+                &landmarks,
+            )
+        })
+    });
+
+    // the same queries one by one and as a batch sharing allocations
+    let queries: Vec<(Point, Point)> = (0..20)
+        .map(|n| {
+            (
+                Point::new(n * 5 % width, n * 7 % height),
+                Point::new((n * 13 + 50) % width, (n * 3 + 30) % height),
+            )
+        })
+        .collect();
+
+    c.bench_function("castle 20 queries separately", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .map(|(from, to)| {
+                    find_path(
+                        from.clone(),
+                        to.clone(),
+                        width,
+                        height,
+                        1,
+                        1.0,
+                        &cell_weights,
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+
+    c.bench_function("castle 20 queries batched", |b| {
+        b.iter(|| find_paths(&queries, width, height, 1, 1.0, &cell_weights))
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,27 @@
+use astar_rust_wasm::{
+    astar::point::Point,
+    utils::{normalize, rgb_to_hsv},
+};
+
+/// Weights of the castle map, 1 for white to 10 for black without any walls
+pub fn castle_weights() -> (Vec<f32>, u32, u32) {
+    let mut bytes: &[u8] = include_bytes!("../../assets/castle.bmp");
+    let image = bmp::from_reader(&mut bytes).unwrap();
+    let height = image.get_height();
+    let width = image.get_width();
+
+    let mut cell_weights = vec![0.0; (width * height) as usize];
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = image.get_pixel(x, y);
+            let hsv = rgb_to_hsv(pixel.r, pixel.g, pixel.b);
+            let inverted_brighntess = (hsv.brightness - 1.0).abs();
+            let normalized_brighntess = normalize(0.0, 1.0, 1.0, 10.0, inverted_brighntess);
+
+            cell_weights[Point::new(x, y).to_1d_index(width) as usize] = normalized_brighntess;
+        }
+    }
+
+    (cell_weights, width, height)
+}
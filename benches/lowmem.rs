@@ -0,0 +1,249 @@
+use astar_rust_wasm::astar::{
+    astar::find_path, fringe::find_path_fringe, ida_star::find_path_ida, point::Point,
+};
+use common::castle_weights;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+mod common;
+
+const IDA_TABLE_SIZE: usize = 64;
+
+/// Open map with uniform weights and a wall in the middle, the kind of map IDA* copes with
+fn walled_weights(width: u32, height: u32) -> Vec<f32> {
+    (0..width * height)
+        .map(|i| {
+            let point = Point::from_1d_index(width, i);
+            if point.x == width / 2 && point.y > height / 4 {
+                -1.0
+            } else {
+                1.0
+            }
+        })
+        .collect()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let (castle, width, height) = castle_weights();
+    let (from, to) = (Point { x: 0, y: 37 }, Point { x: 99, y: 12 });
+
+    c.bench_function("castle find_path", |b| {
+        b.iter(|| find_path(from.clone(), to.clone(), width, height, 1, 1.0, &castle))
+    });
+    c.bench_function("castle fringe", |b| {
+        b.iter(|| find_path_fringe(from.clone(), to.clone(), width, height, 1, 1.0, &castle))
+    });
+
+    // IDA* only finishes on small uniform maps, already at 48x48 it runs out of tens of millions of expanded nodes,
+    // so the time below is for a toy map and says little about bigger ones
+    let walled = walled_weights(12, 12);
+    let (from, to) = (Point { x: 0, y: 11 }, Point { x: 11, y: 11 });
+
+    c.bench_function("walled find_path", |b| {
+        b.iter(|| find_path(from.clone(), to.clone(), 12, 12, 1, 1.0, &walled))
+    });
+    c.bench_function("walled fringe", |b| {
+        b.iter(|| find_path_fringe(from.clone(), to.clone(), 12, 12, 1, 1.0, &walled))
+    });
+    c.bench_function("walled ida", |b| {
+        b.iter(|| {
+            find_path_ida(
+                from.clone(),
+                to.clone(),
+                12,
+                12,
+                1,
+                1.0,
+                &walled,
+                IDA_TABLE_SIZE,
+                u32::MAX,
+            )
+        })
+    });
+}
+
+/// Peak heap usage of the searches, reported by criterion like the times but in bytes
+/// Only built without wee_alloc, since counting needs its own global allocator
+#[cfg(not(feature = "wee_alloc"))]
+mod memory {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use criterion::{
+        measurement::{Measurement, ValueFormatter},
+        Criterion, Throughput,
+    };
+
+    use super::*;
+
+    const IDA_CASTLE_BUDGET: u32 = 1_000_000;
+
+    /// Keeps track of the peak heap usage, criterion only measures time by itself
+    struct CountingAllocator;
+
+    static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+    static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::Relaxed);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    /// Peak bytes allocated between start and end, on top of what was allocated before
+    /// The value is a float since criterion asks for huge iteration counts, it takes small byte counts for nanoseconds
+    pub struct PeakMemory;
+
+    impl Measurement for PeakMemory {
+        type Intermediate = usize;
+        type Value = f64;
+
+        fn start(&self) -> usize {
+            let before = ALLOCATED.load(Ordering::Relaxed);
+            PEAK.store(before, Ordering::Relaxed);
+            before
+        }
+
+        fn end(&self, before: usize) -> f64 {
+            (PEAK.load(Ordering::Relaxed) - before) as f64
+        }
+
+        fn add(&self, v1: &f64, v2: &f64) -> f64 {
+            v1 + v2
+        }
+
+        fn zero(&self) -> f64 {
+            0.0
+        }
+
+        fn to_f64(&self, value: &f64) -> f64 {
+            *value
+        }
+
+        fn formatter(&self) -> &dyn ValueFormatter {
+            &BytesFormatter
+        }
+    }
+
+    struct BytesFormatter;
+
+    impl ValueFormatter for BytesFormatter {
+        fn scale_values(&self, typical_value: f64, values: &mut [f64]) -> &'static str {
+            let (factor, unit) = match typical_value {
+                v if v < 1024.0 => (1.0, "B"),
+                v if v < 1024.0 * 1024.0 => (1024.0, "KiB"),
+                _ => (1024.0 * 1024.0, "MiB"),
+            };
+
+            for value in values {
+                *value /= factor;
+            }
+
+            unit
+        }
+
+        // the memory benches dont set a throughput
+        fn scale_throughputs(
+            &self,
+            typical_value: f64,
+            _throughput: &Throughput,
+            values: &mut [f64],
+        ) -> &'static str {
+            self.scale_values(typical_value, values)
+        }
+
+        fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+            "B"
+        }
+    }
+
+    /// Peak memory of a single run, scaled by the iterations since criterion reports the value per iteration
+    fn bench_peak_memory<T>(c: &mut Criterion<PeakMemory>, name: &str, f: impl Fn() -> T) {
+        c.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                let before = PeakMemory.start();
+                let result = f();
+                let peak = PeakMemory.end(before);
+                drop(result);
+                peak * iters as f64
+            })
+        });
+    }
+
+    pub fn memory_benchmark(c: &mut Criterion<PeakMemory>) {
+        let (castle, width, height) = castle_weights();
+        let (from, to) = (Point { x: 0, y: 37 }, Point { x: 99, y: 12 });
+
+        bench_peak_memory(c, "castle find_path memory", || {
+            find_path(from.clone(), to.clone(), width, height, 1, 1.0, &castle)
+        });
+        bench_peak_memory(c, "castle fringe memory", || {
+            find_path_fringe(from.clone(), to.clone(), width, height, 1, 1.0, &castle)
+        });
+
+        // IDA* does not finish on the castle, the varying weights make the threshold grow in tiny steps and every iteration starts over
+        // so this is how little memory it uses before giving up after the budget of expanded nodes
+        bench_peak_memory(c, "castle ida memory", || {
+            find_path_ida(
+                from.clone(),
+                to.clone(),
+                width,
+                height,
+                1,
+                1.0,
+                &castle,
+                IDA_TABLE_SIZE,
+                IDA_CASTLE_BUDGET,
+            )
+        });
+
+        let walled = walled_weights(12, 12);
+        let (from, to) = (Point { x: 0, y: 11 }, Point { x: 11, y: 11 });
+
+        bench_peak_memory(c, "walled find_path memory", || {
+            find_path(from.clone(), to.clone(), 12, 12, 1, 1.0, &walled)
+        });
+        bench_peak_memory(c, "walled fringe memory", || {
+            find_path_fringe(from.clone(), to.clone(), 12, 12, 1, 1.0, &walled)
+        });
+        bench_peak_memory(c, "walled ida memory", || {
+            find_path_ida(
+                from.clone(),
+                to.clone(),
+                12,
+                12,
+                1,
+                1.0,
+                &walled,
+                IDA_TABLE_SIZE,
+                u32::MAX,
+            )
+        });
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+
+// memory is the same on every run, so a few samples are enough
+#[cfg(not(feature = "wee_alloc"))]
+criterion_group!(
+    name = memory_benches;
+    config = Criterion::default().with_measurement(memory::PeakMemory).sample_size(10);
+    targets = memory::memory_benchmark
+);
+
+#[cfg(not(feature = "wee_alloc"))]
+criterion_main!(benches, memory_benches);
+#[cfg(feature = "wee_alloc")]
+criterion_main!(benches);
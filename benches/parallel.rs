@@ -0,0 +1,126 @@
+use astar_rust_wasm::astar::{
+    astar::find_path,
+    batch::find_paths,
+    parallel::{find_path_hda, find_paths_parallel},
+    point::Point,
+};
+use common::castle_weights;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+mod common;
+
+const THREAD_COUNTS: [usize; 4] = [1, 2, 4, 8];
+
+/// Bumpy terrain with long walls every 32 columns, each with a few gaps at different heights
+fn generated_weights(width: u32, height: u32) -> Vec<f32> {
+    (0..width * height)
+        .map(|i| {
+            let point = Point::from_1d_index(width, i);
+            if point.x % 32 == 16 && (point.y + point.x * 7) % 97 > 4 {
+                -1.0
+            } else {
+                1.0 + (i.wrapping_mul(2654435761) >> 28) as f32 / 2.0
+            }
+        })
+        .collect()
+}
+
+/// Pseudo random queries between passable cells, the same every run
+fn queries(count: u32, width: u32, height: u32, weights: &[f32]) -> Vec<(Point, Point)> {
+    let passable = |n: u32| {
+        let start = n.wrapping_mul(2654435761) % (width * height);
+        let index = (start..width * height)
+            .chain(0..start)
+            .find(|&i| weights[i as usize] >= 0.0)
+            .unwrap();
+        Point::from_1d_index(width, index)
+    };
+
+    (0..count)
+        .map(|n| (passable(n * 2 + 1), passable(n * 2 + 2)))
+        .collect()
+}
+
+fn bench_batch(
+    c: &mut Criterion,
+    name: &str,
+    queries: &[(Point, Point)],
+    width: u32,
+    height: u32,
+    weights: &[f32],
+) {
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10);
+
+    group.bench_function("sequential", |b| {
+        b.iter(|| find_paths(queries, width, height, 1, 1.0, weights))
+    });
+
+    for threads in THREAD_COUNTS {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+
+        group.bench_with_input(BenchmarkId::new("parallel", threads), &threads, |b, _| {
+            b.iter(|| pool.install(|| find_paths_parallel(queries, width, height, 1, 1.0, weights)))
+        });
+    }
+
+    group.finish();
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let (castle, width, height) = castle_weights();
+    bench_batch(
+        c,
+        "castle 200 queries",
+        &queries(200, width, height, &castle),
+        width,
+        height,
+        &castle,
+    );
+
+    let generated = generated_weights(512, 512);
+    bench_batch(
+        c,
+        "generated 512x512 50 queries",
+        &queries(50, 512, 512, &generated),
+        512,
+        512,
+        &generated,
+    );
+
+    // one long query across a big map
+    let generated = generated_weights(1024, 1024);
+    let (from, to) = (Point::new(0, 512), Point::new(1023, 100));
+
+    let mut group = c.benchmark_group("generated 1024x1024 single query");
+    group.sample_size(10);
+
+    group.bench_function("find_path", |b| {
+        b.iter(|| find_path(from.clone(), to.clone(), 1024, 1024, 1, 1.0, &generated))
+    });
+
+    for threads in THREAD_COUNTS {
+        group.bench_with_input(BenchmarkId::new("hda", threads), &threads, |b, &threads| {
+            b.iter(|| {
+                find_path_hda(
+                    from.clone(),
+                    to.clone(),
+                    1024,
+                    1024,
+                    1,
+                    1.0,
+                    &generated,
+                    threads,
+                )
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,445 @@
+use std::collections::HashSet;
+
+use super::{astar::FindPath, point::Point};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlternativePath {
+    pub path: Vec<u32>,  // cells in order from start to end, both included
+    pub costs: Vec<f32>, // cumulative cost at each cell of the path
+    pub total_distance: f32,
+}
+
+impl AlternativePath {
+    /// Read the path of a finished search
+    fn from_search(path_finder: &FindPath) -> Option<Self> {
+        let path = path_finder.ordered_path()?;
+        let visited = path_finder.visited_points();
+        let costs: Vec<f32> = path.iter().map(|index| visited[index].score).collect();
+
+        Some(AlternativePath {
+            total_distance: *costs.last()?,
+            path,
+            costs,
+        })
+    }
+
+    /// Path with costs evaluated over the weights the same way the search costs moves, see FindPath::move_cost
+    fn evaluate(path: Vec<u32>, path_finder: &FindPath, weights: &[f32]) -> Self {
+        let mut costs = Vec::with_capacity(path.len());
+        let mut total = 0.0;
+
+        costs.push(total);
+
+        for step in path.windows(2) {
+            total += path_finder.move_cost(step[0], step[1], weights);
+            costs.push(total);
+        }
+
+        AlternativePath {
+            path,
+            costs,
+            total_distance: total,
+        }
+    }
+}
+
+/// k best loopless paths between two points in order of cost, using Yen's algorithm
+/// Each spur path is found with a regular search where the root path cells and already used edges are blocked
+/// Returns fewer than k paths if there arent that many
+#[allow(clippy::too_many_arguments)]
+pub fn k_shortest_paths(
+    from: Point,
+    to: Point,
+    k: usize,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+) -> Vec<AlternativePath> {
+    let mut path_finder = FindPath::new(from, to, width, height, multiplier, min_weight);
+
+    k_shortest_paths_with(&mut path_finder, k, weights)
+}
+
+/// Same as k_shortest_paths, using an existing path finder, eg one with portals
+/// The from and to points of the path finder are used
+pub fn k_shortest_paths_with(
+    path_finder: &mut FindPath,
+    k: usize,
+    weights: &[f32],
+) -> Vec<AlternativePath> {
+    let width = path_finder.width();
+    let from = Point::from_1d_index(width, path_finder.from_index);
+    let to = Point::from_1d_index(width, path_finder.to_index);
+
+    let mut accepted: Vec<AlternativePath> = Vec::with_capacity(k);
+    let mut candidates: Vec<AlternativePath> = Vec::new();
+
+    path_finder.clear_blocked();
+    path_finder.restart(from.clone(), to.clone());
+
+    if k == 0 || path_finder.run(weights).is_none() {
+        return accepted;
+    }
+
+    accepted.extend(AlternativePath::from_search(path_finder));
+
+    while accepted.len() < k {
+        let previous = accepted.last().unwrap().clone();
+
+        for spur_position in 0..previous.path.len() - 1 {
+            let spur_index = previous.path[spur_position];
+            let root = &previous.path[..=spur_position];
+
+            // dont take the same next step as any accepted path sharing this root
+            let blocked_edges: HashSet<(u32, u32)> = accepted
+                .iter()
+                .filter(|p| p.path.len() > spur_position + 1 && p.path[..=spur_position] == *root)
+                .map(|p| (spur_index, p.path[spur_position + 1]))
+                .collect();
+
+            // and dont go back through the root, paths must be loopless
+            let blocked_cells: HashSet<u32> = root[..spur_position].iter().copied().collect();
+
+            path_finder.set_blocked(blocked_cells, blocked_edges);
+            path_finder.restart(Point::from_1d_index(width, spur_index), to.clone());
+
+            if path_finder.run(weights).is_none() {
+                continue;
+            }
+
+            let spur = AlternativePath::from_search(path_finder).unwrap();
+            let root_cost = previous.costs[spur_position];
+
+            let mut path = root[..spur_position].to_vec();
+            path.extend(spur.path);
+
+            let mut costs = previous.costs[..spur_position].to_vec();
+            costs.extend(spur.costs.iter().map(|c| c + root_cost));
+
+            let candidate = AlternativePath {
+                total_distance: root_cost + spur.total_distance,
+                path,
+                costs,
+            };
+
+            if !candidates.iter().any(|c| c.path == candidate.path)
+                && !accepted.iter().any(|a| a.path == candidate.path)
+            {
+                candidates.push(candidate);
+            }
+        }
+
+        path_finder.clear_blocked();
+
+        match (0..candidates.len()).min_by(|&a, &b| {
+            candidates[a]
+                .total_distance
+                .total_cmp(&candidates[b].total_distance)
+        }) {
+            Some(best) => accepted.push(candidates.swap_remove(best)),
+            None => break,
+        }
+    }
+
+    accepted
+}
+
+/// Diverse alternatives with the penalty method
+/// After each search the weights of the path cells are multiplied by the penalty factor on a copy of the weights, and the search is run again
+/// Paths overlapping previously accepted ones by more than max_overlap (0..1, share of cells) are rejected
+/// Costs of the returned paths are evaluated over the original weights
+/// Factors below 1 are treated as 1, cells cheaper than the min weight would make the heuristic overestimate
+#[allow(clippy::too_many_arguments)]
+pub fn penalty_alternatives(
+    from: Point,
+    to: Point,
+    max_routes: usize,
+    penalty_factor: f32,
+    max_overlap: f32,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+) -> Vec<AlternativePath> {
+    let mut path_finder = FindPath::new(from, to, width, height, multiplier, min_weight);
+
+    penalty_alternatives_with(
+        &mut path_finder,
+        max_routes,
+        penalty_factor,
+        max_overlap,
+        weights,
+    )
+}
+
+/// Same as penalty_alternatives, using an existing path finder, eg one with portals
+/// The from and to points of the path finder are used
+pub fn penalty_alternatives_with(
+    path_finder: &mut FindPath,
+    max_routes: usize,
+    penalty_factor: f32,
+    max_overlap: f32,
+    weights: &[f32],
+) -> Vec<AlternativePath> {
+    let width = path_finder.width();
+    let from = Point::from_1d_index(width, path_finder.from_index);
+    let to = Point::from_1d_index(width, path_finder.to_index);
+
+    let penalty_factor = penalty_factor.max(1.0);
+    let mut penalized_weights = weights.to_vec();
+    let mut accepted: Vec<AlternativePath> = Vec::with_capacity(max_routes);
+    let mut used_cells: HashSet<u32> = HashSet::new();
+
+    // penalties pile up on rejected paths as well, so the search eventually moves elsewhere, but give up at some point
+    for _ in 0..max_routes * 3 {
+        if accepted.len() >= max_routes {
+            break;
+        }
+
+        path_finder.restart(from.clone(), to.clone());
+
+        if path_finder.run(&penalized_weights).is_none() {
+            break;
+        }
+
+        let path = path_finder.ordered_path().unwrap();
+
+        let overlap =
+            path.iter().filter(|c| used_cells.contains(c)).count() as f32 / path.len() as f32;
+
+        for &index in &path {
+            let weight = &mut penalized_weights[index as usize];
+            if *weight > 0.0 {
+                *weight *= penalty_factor;
+            }
+        }
+
+        if accepted.is_empty() || overlap <= max_overlap {
+            used_cells.extend(path.iter().copied());
+            accepted.push(AlternativePath::evaluate(path, path_finder, weights));
+        }
+    }
+
+    accepted
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::{astar_utils::calculate_weight_with_elevation, elevation::Elevation};
+
+    use super::*;
+
+    #[test]
+    fn test_k_shortest_paths_corridors() {
+        // three corridors of different lengths between the left and the right side
+        #[rustfmt::skip]
+        let weights: Vec<f32> = vec![
+            1.0,  1.0,  1.0,  1.0,  1.0,
+            1.0, -1.0, -1.0, -1.0,  1.0,
+            1.0,  1.0,  1.0,  1.0,  1.0,
+            1.0, -1.0, -1.0, -1.0,  1.0,
+            1.0, -1.0, -1.0, -1.0,  1.0,
+            1.0,  1.0,  1.0,  1.0,  1.0,
+        ];
+
+        let paths = k_shortest_paths(
+            Point::new(0, 2),
+            Point::new(4, 2),
+            3,
+            5,
+            6,
+            1,
+            1.0,
+            &weights,
+        );
+
+        assert_eq!(3, paths.len());
+        assert_eq!(4.0, paths[0].total_distance);
+        assert_eq!(vec![10, 11, 12, 13, 14], paths[0].path);
+        assert!(paths[0].total_distance <= paths[1].total_distance);
+        assert!(paths[1].total_distance <= paths[2].total_distance);
+
+        for path in &paths {
+            let unique: HashSet<&u32> = path.path.iter().collect();
+            assert_eq!(path.path.len(), unique.len());
+            assert_eq!(Some(&10), path.path.first());
+            assert_eq!(Some(&14), path.path.last());
+            assert_eq!(path.path.len(), path.costs.len());
+        }
+    }
+
+    #[test]
+    fn test_penalty_alternatives_costs_include_slopes() {
+        #[rustfmt::skip]
+        let weights: Vec<f32> = vec![
+            1.0,  1.0,  1.0,  1.0,  1.0,
+            1.0, -1.0, -1.0, -1.0,  1.0,
+            1.0,  1.0,  1.0,  1.0,  1.0,
+            1.0, -1.0, -1.0, -1.0,  1.0,
+            1.0, -1.0, -1.0, -1.0,  1.0,
+            1.0,  1.0,  1.0,  1.0,  1.0,
+        ];
+        // bumps along the outer corridors
+        let heights: Vec<f32> = (0..30)
+            .map(|i| match (i % 5, i / 5) {
+                (1 | 3, 0 | 5) => 2.0,
+                _ => 0.0,
+            })
+            .collect();
+        let elevation = Elevation::new(heights, 1.0, 0.5, 10.0);
+
+        let paths = penalty_alternatives_with(
+            &mut FindPath::new(Point::new(0, 2), Point::new(4, 2), 5, 6, 1, 1.0)
+                .with_elevation(elevation.clone()),
+            3,
+            4.0,
+            1.0,
+            &weights,
+        );
+
+        // some of the paths go over the bumps
+        assert!(paths.iter().any(|path| path.path.contains(&1)));
+
+        for path in &paths {
+            let cost: f32 = path
+                .path
+                .windows(2)
+                .map(|step| {
+                    calculate_weight_with_elevation(
+                        &Point::from_1d_index(5, step[0]),
+                        &Point::from_1d_index(5, step[1]),
+                        &weights,
+                        5,
+                        &elevation,
+                    )
+                })
+                .sum();
+
+            assert!((cost - path.total_distance).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn test_k_shortest_paths_runs_out() {
+        // single corridor, only one loopless path
+        let weights: Vec<f32> = vec![1.0; 5];
+
+        let paths = k_shortest_paths(
+            Point::new(0, 0),
+            Point::new(4, 0),
+            3,
+            5,
+            1,
+            1,
+            1.0,
+            &weights,
+        );
+
+        assert_eq!(1, paths.len());
+        assert_eq!(4.0, paths[0].total_distance);
+    }
+
+    #[test]
+    fn test_k_shortest_paths_unreachable() {
+        let weights: Vec<f32> = vec![1.0, -1.0, 1.0];
+
+        assert!(k_shortest_paths(
+            Point::new(0, 0),
+            Point::new(2, 0),
+            3,
+            3,
+            1,
+            1,
+            1.0,
+            &weights
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_penalty_alternatives_open_field() {
+        let weights: Vec<f32> = vec![1.0; 400];
+
+        let paths = penalty_alternatives(
+            Point::new(0, 10),
+            Point::new(19, 10),
+            3,
+            2.0,
+            0.5,
+            20,
+            20,
+            1,
+            1.0,
+            &weights,
+        );
+
+        assert_eq!(3, paths.len());
+        assert_eq!(19.0, paths[0].total_distance);
+
+        for (n, path) in paths.iter().enumerate() {
+            assert_eq!(Some(&200), path.path.first());
+            assert_eq!(Some(&219), path.path.last());
+
+            // costs are over the original weights, not the penalized ones
+            assert!(path.total_distance >= 19.0);
+            assert!(path.total_distance < 19.0 * 2.0);
+
+            for other in &paths[..n] {
+                let other_cells: HashSet<&u32> = other.path.iter().collect();
+                let overlap = path.path.iter().filter(|c| other_cells.contains(c)).count();
+                assert!(overlap as f32 / path.path.len() as f32 <= 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_penalty_alternatives_keeps_weights() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let paths = penalty_alternatives(
+            Point::new(0, 0),
+            Point::new(9, 9),
+            2,
+            3.0,
+            1.0,
+            10,
+            10,
+            1,
+            1.0,
+            &weights,
+        );
+
+        assert_eq!(2, paths.len());
+        assert!(weights.iter().all(|w| *w == 1.0));
+        assert_eq!(12.727921, paths[0].total_distance);
+    }
+
+    #[test]
+    fn test_penalty_alternatives_factor_below_one() {
+        let weights: Vec<f32> = vec![1.0; 400];
+
+        let search = |penalty_factor| {
+            penalty_alternatives(
+                Point::new(0, 10),
+                Point::new(19, 10),
+                3,
+                penalty_factor,
+                0.5,
+                20,
+                20,
+                1,
+                1.0,
+                &weights,
+            )
+        };
+
+        // no penalty at all, the same path comes back every time and overlaps completely
+        let paths = search(0.5);
+        assert_eq!(1, paths.len());
+        assert_eq!(19.0, paths[0].total_distance);
+        assert_eq!(search(1.0)[0].path, paths[0].path);
+    }
+}
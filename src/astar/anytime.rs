@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use super::{
+    astar::{FindPath, VisitedPoint},
+    point::Point,
+};
+
+/// Anytime Repairing A* (ARA*)
+/// Starts with the heuristic inflated by epsilon for a quick first path, then lowers epsilon step by step and repairs the path, reusing the earlier search effort
+/// Every found path costs at most suboptimality_bound times the optimal cost, and the last one with epsilon 1 is optimal
+/// The rounds run on a FindPath, so portals, slopes, one-way cells, clearance and components work the same as in other searches
+pub struct AnytimeFindPath {
+    path_finder: FindPath,
+    epsilon: f32,
+    epsilon_step: f32,
+    best_path: Option<Vec<u32>>,
+    best_cost: Option<f32>,
+    bound: Option<f32>,
+    finished: bool,
+}
+
+impl AnytimeFindPath {
+    pub fn new(
+        from: Point,
+        to: Point,
+        initial_epsilon: f32,
+        epsilon_step: f32,
+        width: u32,
+        height: u32,
+        min_weight: f32,
+    ) -> Self {
+        AnytimeFindPath::with_path_finder(
+            FindPath::new(from, to, width, height, 1, min_weight),
+            initial_epsilon,
+            epsilon_step,
+        )
+    }
+
+    /// Run the rounds on a configured search, eg one with portals or elevation
+    pub fn with_path_finder(
+        path_finder: FindPath,
+        initial_epsilon: f32,
+        epsilon_step: f32,
+    ) -> Self {
+        let epsilon = initial_epsilon.max(1.0);
+
+        AnytimeFindPath {
+            path_finder: path_finder.with_inflation(epsilon),
+            epsilon,
+            epsilon_step: epsilon_step.max(f32::EPSILON),
+            best_path: None,
+            best_cost: None,
+            bound: None,
+            finished: false,
+        }
+    }
+
+    /// Current inflation of the heuristic
+    pub fn epsilon(&self) -> f32 {
+        self.epsilon
+    }
+
+    /// The best path costs at most this many times the optimal cost, None until a path is found
+    pub fn suboptimality_bound(&self) -> Option<f32> {
+        self.bound
+    }
+
+    pub fn best_cost(&self) -> Option<f32> {
+        self.best_cost
+    }
+
+    /// Best path found so far in order from start to end
+    pub fn ordered_path(&self) -> Option<&Vec<u32>> {
+        self.best_path.as_ref()
+    }
+
+    /// True once the optimal path is found, or the target turned out to be unreachable
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn visited_points(&self) -> &HashMap<u32, VisitedPoint<f32, u32>> {
+        self.path_finder.visited_points()
+    }
+
+    /// Cells expanded during the current epsilon round
+    pub fn closed_points(&self) -> impl Iterator<Item = u32> + '_ {
+        self.path_finder.closed_cells()
+    }
+
+    /// Expand at most ticks cells, returns true when finished
+    /// Whenever the path for the current epsilon is done, it is published and epsilon is lowered for the next round
+    pub fn tick(&mut self, ticks: u32, weights: &[f32]) -> bool {
+        let mut remaining_ticks = ticks;
+
+        while !self.finished && remaining_ticks > 0 {
+            remaining_ticks -= 1;
+
+            // the path is good enough for this epsilon once nothing in the openset could improve it
+            let goal_score = self.path_finder.goal_score().unwrap_or(f32::INFINITY);
+            let round_done = match self.path_finder.peek_f_score() {
+                Some(f_score) => goal_score <= f_score,
+                None => true,
+            };
+
+            if round_done {
+                self.finish_round();
+                continue;
+            }
+
+            self.path_finder.expand_next(weights);
+        }
+
+        self.finished
+    }
+
+    /// Run until the optimal path is found, returns its cost
+    pub fn run(&mut self, weights: &[f32]) -> Option<f32> {
+        while !self.tick(u32::MAX, weights) {}
+        self.best_cost
+    }
+
+    fn finish_round(&mut self) {
+        // nothing left to search and no path
+        let Some(goal_score) = self.path_finder.accept_goal() else {
+            self.finished = true;
+            return;
+        };
+
+        self.best_cost = Some(goal_score);
+        self.best_path = self.path_finder.ordered_path();
+
+        // the optimal cost is at least the smallest unexpanded g + h
+        let lower_bound = self.path_finder.lower_bound().min(goal_score);
+
+        self.bound = Some(
+            (goal_score / lower_bound.max(f32::EPSILON))
+                .min(self.epsilon)
+                .max(1.0),
+        );
+
+        if self.epsilon <= 1.0 {
+            self.bound = Some(1.0);
+            self.finished = true;
+            return;
+        }
+
+        // reopen the inconsistent cells and sort everything by the new epsilon
+        self.epsilon = (self.epsilon - self.epsilon_step).max(1.0);
+        self.path_finder.set_inflation(self.epsilon);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::{astar::find_path, elevation::Elevation, portals::Portals};
+
+    use super::*;
+
+    fn bumpy_weights(width: u32, height: u32) -> Vec<f32> {
+        (0..width * height)
+            .map(|i| {
+                let point = Point::from_1d_index(width, i);
+                if point.x == width / 2 && point.y > 2 {
+                    -1.0
+                } else {
+                    1.0 + ((point.x * 7 + point.y * 13) % 10) as f32
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_converges_to_optimal() {
+        let weights = bumpy_weights(30, 30);
+        let optimal = find_path(
+            Point::new(0, 29),
+            Point::new(29, 29),
+            30,
+            30,
+            1,
+            1.0,
+            &weights,
+        )
+        .unwrap()
+        .total_distance;
+
+        let mut path_finder =
+            AnytimeFindPath::new(Point::new(0, 29), Point::new(29, 29), 3.0, 0.5, 30, 30, 1.0);
+
+        let mut costs: Vec<f32> = Vec::new();
+        let mut bounds: Vec<f32> = Vec::new();
+
+        while !path_finder.tick(50, &weights) {
+            if let (Some(cost), Some(bound)) =
+                (path_finder.best_cost(), path_finder.suboptimality_bound())
+            {
+                if costs.last() != Some(&cost) || bounds.last() != Some(&bound) {
+                    costs.push(cost);
+                    bounds.push(bound);
+                }
+            }
+        }
+
+        assert!((path_finder.best_cost().unwrap() - optimal).abs() < 0.001);
+        assert_eq!(Some(1.0), path_finder.suboptimality_bound());
+
+        for (cost, bound) in costs.iter().zip(&bounds) {
+            assert!(*cost <= optimal * bound + 0.001);
+        }
+
+        for pair in costs.windows(2) {
+            assert!(pair[1] <= pair[0]);
+        }
+        for pair in bounds.windows(2) {
+            assert!(pair[1] <= pair[0] + 0.0001);
+        }
+    }
+
+    #[test]
+    fn test_path_is_connected() {
+        let weights = bumpy_weights(20, 20);
+        let mut path_finder =
+            AnytimeFindPath::new(Point::new(0, 19), Point::new(19, 19), 2.0, 1.0, 20, 20, 1.0);
+
+        path_finder.run(&weights).unwrap();
+
+        let path = path_finder.ordered_path().unwrap();
+        assert_eq!(Some(&Point::new(0, 19).to_1d_index(20)), path.first());
+        assert_eq!(Some(&Point::new(19, 19).to_1d_index(20)), path.last());
+
+        for step in path.windows(2) {
+            let a = Point::from_1d_index(20, step[0]);
+            let b = Point::from_1d_index(20, step[1]);
+            assert!(a.x.abs_diff(b.x) <= 1 && a.y.abs_diff(b.y) <= 1);
+        }
+    }
+
+    #[test]
+    fn test_uses_the_board_settings_of_the_search() {
+        // the wall is closed, so the only way across is the portal, and the hills cost extra
+        let mut weights = bumpy_weights(20, 20);
+        for y in 0..3 {
+            weights[(y * 20 + 10) as usize] = -1.0;
+        }
+        let heights: Vec<f32> = (0..400).map(|i| ((i % 20) % 4) as f32).collect();
+
+        let mut portals = Portals::new(20, 20);
+        portals.add(&Point::new(5, 10), &Point::new(15, 10), 2.0, false);
+
+        let search = || {
+            FindPath::new(Point::new(0, 19), Point::new(19, 19), 20, 20, 1, 1.0)
+                .with_portals(portals.clone())
+                .with_elevation(Elevation::new(heights.clone(), 1.0, 0.5, 5.0))
+        };
+
+        let optimal = search().run(&weights).unwrap();
+
+        let mut path_finder = AnytimeFindPath::with_path_finder(search(), 3.0, 0.5);
+        let cost = path_finder.run(&weights).unwrap();
+
+        assert!((cost - optimal).abs() < 0.001);
+
+        let path = path_finder.ordered_path().unwrap();
+        assert!(path.windows(2).any(|step| step
+            == [
+                Point::new(5, 10).to_1d_index(20),
+                Point::new(15, 10).to_1d_index(20)
+            ]));
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let weights: Vec<f32> = vec![1.0, -1.0, 1.0];
+        let mut path_finder =
+            AnytimeFindPath::new(Point::new(0, 0), Point::new(2, 0), 2.0, 0.5, 3, 1, 1.0);
+
+        assert_eq!(None, path_finder.run(&weights));
+        assert!(path_finder.is_finished());
+    }
+}
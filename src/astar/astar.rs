@@ -1,13 +1,32 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use wasm_bindgen::prelude::wasm_bindgen;
 
 use crate::{astar::point::Point, hybridheap::HybridHeap};
 
-use super::astar_utils::{
-    calculate_heuristical_distance, calculate_weight, get_neighbours, reconstruct_path,
+use super::{
+    astar_utils::{
+        calculate_heuristical_distance, calculate_weight, calculate_weight_with_elevation,
+        get_neighbours, reconstruct_ordered_path, reconstruct_path,
+    },
+    clearance::{has_clearance, SINGLE_CELL_AGENT_RADIUS},
+    components::Components,
+    elevation::Elevation,
+    heading::{heading_between, turn_steps, HeadingState},
+    landmarks::Landmarks,
+    oneway::{allows_exit, ALL_EXITS},
+    portals::Portals,
 };
 
+/// Headings a cell can be entered with, plus one slot for the start cell without a heading
+const HEADING_SLOTS: u32 = 9;
+
+/// Above this many goals the min over goals heuristic costs more than it saves and a plain dijkstra is used instead
+pub const MULTI_TARGET_DIJKSTRA_THRESHOLD: usize = 32;
+
 pub struct PathResult {
     pub from_index: u32,
     pub to_index: u32,
@@ -30,16 +49,34 @@ pub struct VisitedPoint<S, K> {
 }
 
 pub struct FindPath {
-    to: Point,
-    pub to_index: u32,
+    goals: Vec<Point>,
+    goal_indexes: HashSet<u32>,
+    pub to_index: u32, // the first goal until a path is found, then the goal that was reached
+    to_key: u32,       // search state the goal was reached with
     pub from_index: u32,
     width: u32,
     height: u32,
     multiplier: u32,
     min_weight: f32,
-    openset: HybridHeap<u32, f32>, // openset contains seen nodes which havent yet been visited
-    g_score: HashMap<u32, VisitedPoint<f32, u32>>, // g scores contains the currently best scores for visited nodes and from where we ended up here
+    openset: HybridHeap<u32, f32>, // openset contains seen states which havent yet been visited
+    g_score: HashMap<u32, VisitedPoint<f32, u32>>, // g scores contains the currently best scores for visited states and from where we ended up here
     pub path_indexes: Option<HashSet<u32>>, // hohum.. maybe return coordinates instead, since that would better reflect the "public api"
+    portals: Portals,
+    portal_bound: Option<f32>, // lower bound for the remaining distance of any path using a portal
+    blocked_cells: HashSet<u32>,
+    blocked_edges: HashSet<(u32, u32)>,
+    clearance: Vec<f32>, // empty if agent size is not considered
+    agent_radius: f32,
+    elevation: Option<Elevation>,
+    exit_masks: Vec<u8>, // empty if all moves are allowed
+    landmarks: Option<Arc<Landmarks>>,
+    components: Option<Arc<Components>>,
+    turn_penalty: Option<f32>, // states are the cell and the heading it was entered with when set, see with_turn_penalty
+    initial_heading: Option<u8>,
+    inflation: f32,   // heuristic weight, above 1 for the rounds of an anytime search
+    repairable: bool, // expanded states are tracked for lowering the inflation later, see with_inflation
+    closed: HashSet<u32>, // states expanded with the current inflation
+    inconsistent: HashSet<u32>, // improved after being expanded, reopened when the inflation is lowered
 }
 
 impl FindPath {
@@ -51,44 +88,260 @@ impl FindPath {
         multiplier: u32,
         min_weight: f32,
     ) -> Self {
-        let mut openset: HybridHeap<u32, f32> = HybridHeap::with_capacity(1000);
-        let mut g_score: HashMap<u32, VisitedPoint<f32, u32>> = HashMap::with_capacity(1000);
+        FindPath::new_multi_target(from, vec![to], width, height, multiplier, min_weight)
+    }
 
-        let from_index = from.to_1d_index(width);
-        let to_index = to.to_1d_index(width);
+    /// Search for the cheapest reachable of many goals, eg the nearest exit
+    /// The heuristic is the minimum over all goals, or zero (ie dijkstra) if there are more than MULTI_TARGET_DIJKSTRA_THRESHOLD goals
+    pub fn new_multi_target(
+        from: Point,
+        goals: Vec<Point>,
+        width: u32,
+        height: u32,
+        multiplier: u32,
+        min_weight: f32,
+    ) -> Self {
+        let mut path_finder = FindPath {
+            to_index: 0,
+            to_key: 0,
+            goal_indexes: HashSet::new(),
+            goals: Vec::new(),
+            from_index: 0,
+            width,
+            height,
+            multiplier,
+            min_weight,
+            openset: HybridHeap::with_capacity(1000),
+            g_score: HashMap::with_capacity(1000),
+            path_indexes: None,
+            portals: Portals::new(width, height),
+            portal_bound: None,
+            blocked_cells: HashSet::new(),
+            blocked_edges: HashSet::new(),
+            clearance: Vec::new(),
+            agent_radius: SINGLE_CELL_AGENT_RADIUS,
+            elevation: None,
+            exit_masks: Vec::new(),
+            landmarks: None,
+            components: None,
+            turn_penalty: None,
+            initial_heading: None,
+            inflation: 1.0,
+            repairable: false,
+            closed: HashSet::new(),
+            inconsistent: HashSet::new(),
+        };
+
+        path_finder.restart_multi_target(from, goals);
+        path_finder
+    }
+
+    /// Use portals as extra neighbours when searching
+    /// The heuristic is capped by the cheapest possible way of reaching the target through a portal to keep it admissible
+    pub fn with_portals(mut self, portals: Portals) -> Self {
+        self.portals = portals;
+        self.update_portal_bound();
+
+        let from = Point::from_1d_index(self.width, self.from_index);
+        let from_key = self.key(self.from_index, self.initial_heading);
+        if self.openset.contains_key(&from_key) {
+            self.openset.change_value(from_key, self.heuristic(&from));
+        }
+
+        self
+    }
+
+    /// Use the landmark distance tables as a lower bound on top of the euclidean heuristic, shared since they are large and expensive to compute
+    pub fn with_landmarks(mut self, landmarks: Arc<Landmarks>) -> Self {
+        self.landmarks = Some(landmarks);
+        self.update_portal_bound();
+
+        let from = Point::from_1d_index(self.width, self.from_index);
+        let from_key = self.key(self.from_index, self.initial_heading);
+        if self.openset.contains_key(&from_key) {
+            self.openset.change_value(from_key, self.heuristic(&from));
+        }
+
+        self
+    }
+
+    /// Give up right away when no goal is in the same connected component as the start, instead of exploring everything reachable
+    /// Portals may connect components, so add them before this
+    pub fn with_components(mut self, components: Arc<Components>) -> Self {
+        self.components = Some(components);
+
+        if self.is_known_unreachable() {
+            self.openset.clear();
+        }
 
-        g_score.insert(
-            from_index,
+        self
+    }
+
+    /// Only traverse cells with enough clearance for an agent of the radius, see clearance_map
+    pub fn with_clearance(mut self, clearance: Vec<f32>, agent_radius: f32) -> Self {
+        self.clearance = clearance;
+        self.agent_radius = agent_radius;
+        self
+    }
+
+    /// Add slope costs from the elevation to every move between neighbours, see Elevation
+    pub fn with_elevation(mut self, elevation: Elevation) -> Self {
+        self.elevation = Some(elevation);
+        self
+    }
+
+    /// Only leave cells in the headings allowed by their exit mask, see oneway
+    /// Disallowed moves can only make paths longer, so the heuristic stays admissible. Portals are not affected
+    pub fn with_exit_masks(mut self, exit_masks: Vec<u8>) -> Self {
+        self.exit_masks = exit_masks;
+        self
+    }
+
+    /// Make every 45 degree turn cost turn_penalty on top of the terrain weight, so straighter paths are preferred
+    /// The search state becomes the cell and the heading it was entered with, the start has the initial heading and goals can be reached with any heading.
+    /// Penalties are never negative so the heuristic stays admissible, and leaving a portal is free to turn. Restarts the search
+    pub fn with_turn_penalty(mut self, turn_penalty: f32, initial_heading: Option<u8>) -> Self {
+        self.turn_penalty = Some(turn_penalty.max(0.0));
+        self.initial_heading = initial_heading;
+
+        let from = Point::from_1d_index(self.width, self.from_index);
+        let goals = std::mem::take(&mut self.goals);
+        self.restart_multi_target(from, goals);
+
+        self
+    }
+
+    /// Inflate the heuristic by the factor for a quicker first path, which costs at most inflation times the optimal cost
+    /// Expanded states are tracked, so the inflation can be lowered later on without starting over, see AnytimeFindPath
+    pub fn with_inflation(mut self, inflation: f32) -> Self {
+        self.repairable = true;
+        self.set_inflation(inflation);
+        self
+    }
+
+    /// Lower (or raise) the inflation for the next round of a search made with with_inflation
+    /// States improved after being expanded are reopened and the openset is sorted by the new inflation
+    pub fn set_inflation(&mut self, inflation: f32) {
+        self.inflation = inflation.max(1.0);
+
+        let open: Vec<u32> = self
+            .openset
+            .keys()
+            .copied()
+            .chain(self.inconsistent.drain())
+            .collect();
+
+        self.openset.clear();
+        for key in open {
+            if !self.openset.contains_key(&key) {
+                let f_score = self.g_score[&key].score + self.inflation * self.state_heuristic(key);
+                self.openset.push(key, f_score);
+            }
+        }
+
+        self.closed.clear();
+    }
+
+    /// Prevent the search from entering the cells or moving along the edges, eg for finding alternative routes
+    /// Edges are directed from, to index pairs. The blocks are kept when restarting
+    pub fn set_blocked(&mut self, cells: HashSet<u32>, edges: HashSet<(u32, u32)>) {
+        self.blocked_cells = cells;
+        self.blocked_edges = edges;
+    }
+
+    pub fn clear_blocked(&mut self) {
+        self.blocked_cells.clear();
+        self.blocked_edges.clear();
+    }
+
+    /// Start a new search on the same map, reusing the allocations of the previous search
+    pub fn restart(&mut self, from: Point, to: Point) {
+        self.restart_multi_target(from, vec![to]);
+    }
+
+    /// Start a new search towards many goals on the same map, reusing the allocations of the previous search
+    pub fn restart_multi_target(&mut self, from: Point, goals: Vec<Point>) {
+        assert!(!goals.is_empty(), "at least one goal is required");
+
+        self.reset();
+
+        self.from_index = from.to_1d_index(self.width);
+        self.to_index = goals[0].to_1d_index(self.width);
+        self.to_key = self.key(self.to_index, None);
+        self.goal_indexes = goals
+            .iter()
+            .map(|goal| goal.to_1d_index(self.width))
+            .collect();
+        self.goals = goals;
+        self.update_portal_bound();
+
+        let from_key = self.key(self.from_index, self.initial_heading);
+        self.g_score.insert(
+            from_key,
             VisitedPoint {
                 score: 0.0,
-                came_from_key: from_index,
+                came_from_key: from_key,
             },
         );
-        openset.push(
-            from_index,
-            calculate_heuristical_distance(&from, &to, multiplier, min_weight),
-        );
 
-        FindPath {
-            to,
-            to_index,
-            from_index,
-            width,
-            height,
-            multiplier,
-            min_weight,
-            openset,
-            g_score,
-            path_indexes: None,
+        // nothing to search, the openset is left empty
+        if !self.is_known_unreachable() {
+            self.openset.push(from_key, self.heuristic(&from));
+        }
+    }
+
+    /// True if the components show that no goal can be reached
+    pub fn is_known_unreachable(&self) -> bool {
+        match &self.components {
+            Some(components) if self.portals.is_empty() => !self
+                .goal_indexes
+                .iter()
+                .any(|&goal| components.are_connected(self.from_index, goal)),
+            _ => false,
         }
     }
 
+    /// True if every path costs the same in both directions, not the case with portals, one-way cells, slopes or blocks
+    pub fn is_symmetric(&self) -> bool {
+        self.portals.is_empty()
+            && self.exit_masks.is_empty()
+            && self.elevation.is_none()
+            && self.blocked_cells.is_empty()
+            && self.blocked_edges.is_empty()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn goal_indexes(&self) -> &HashSet<u32> {
+        &self.goal_indexes
+    }
+
+    /// The goal the path leads to, None until a path has been found
+    pub fn reached_goal(&self) -> Option<Point> {
+        self.path_indexes
+            .as_ref()
+            .map(|_| Point::from_1d_index(self.width, self.to_index))
+    }
+
+    pub fn portals(&self) -> &Portals {
+        &self.portals
+    }
+
     pub fn reset(&mut self) {
         self.g_score.clear();
         self.openset.clear();
+        self.closed.clear();
+        self.inconsistent.clear();
         self.path_indexes = None;
     }
 
+    /// Scores of the visited search states, these are the cells unless there is a turn penalty, see state
     pub fn visited_points(&self) -> &HashMap<u32, VisitedPoint<f32, u32>> {
         &self.g_score
     }
@@ -97,148 +350,477 @@ impl FindPath {
         &self.openset
     }
 
+    /// Cells expanded with the current inflation, only tracked by searches made with with_inflation
+    pub fn closed_cells(&self) -> impl Iterator<Item = u32> + '_ {
+        self.closed.iter().map(|key| self.cell(*key))
+    }
+
+    /// Expand the state with the lowest f score without checking for the goal, returns false when the openset is empty
+    /// Lets an anytime search decide itself when a round is done, see goal_score
+    pub fn expand_next(&mut self, weights: &[f32]) -> bool {
+        let Some(current_key) = self.openset.pop() else {
+            return false;
+        };
+
+        if self.repairable {
+            self.closed.insert(current_key);
+        }
+
+        self.expand(current_key, weights);
+        true
+    }
+
+    /// Lowest f score in the openset, None when it is empty
+    pub fn peek_f_score(&self) -> Option<f32> {
+        self.openset.peek_value()
+    }
+
+    /// Score of the cheapest goal state found so far, None if no goal has been reached
+    pub fn goal_score(&self) -> Option<f32> {
+        self.best_goal_key().map(|key| self.g_score[&key].score)
+    }
+
+    /// Take the cheapest goal state found so far as the path, returns its cost
+    pub fn accept_goal(&mut self) -> Option<f32> {
+        let key = self.best_goal_key()?;
+        Some(self.reach_goal(key))
+    }
+
+    /// Lower bound for the optimal cost, the smallest g + h of the states which could still improve the path
+    pub fn lower_bound(&self) -> f32 {
+        self.openset
+            .keys()
+            .chain(self.inconsistent.iter())
+            .map(|key| self.g_score[key].score + self.state_heuristic(*key))
+            .fold(f32::INFINITY, f32::min)
+    }
+
     /// Tick ... specify number of max nodes to process
     /// Returns None if the path was not found with specified tick count
-    pub fn tick(&mut self, ticks: u32, weights: &Vec<f32>) -> Option<f32> {
+    pub fn tick(&mut self, ticks: u32, weights: &[f32]) -> Option<f32> {
         let mut remaining_ticks = ticks; // todo wtf, no underflow panic but wrapping? so, apparently wasm is fine with js passing in 0 here and then decreasing it without panicking
-        while let Some(current_index) = self.openset.pop() {
-            if current_index == self.to_index {
-                self.path_indexes = Some(reconstruct_path(&self.g_score, self.to_index));
-                return Some(self.g_score.get(&self.to_index).unwrap().score);
+        while let Some(current_key) = self.openset.pop() {
+            let current_index = self.cell(current_key);
+
+            if self.goal_indexes.contains(&current_index) {
+                return Some(self.reach_goal(current_key));
             }
 
-            tick(
-                &mut self.g_score,
-                &mut self.openset,
-                &self.to,
-                current_index,
-                self.width,
-                self.height,
-                self.multiplier,
-                self.min_weight,
-                weights,
-            );
+            self.expand(current_key, weights);
 
-            remaining_ticks = remaining_ticks - 1;
+            remaining_ticks -= 1;
 
-            if remaining_ticks <= 0 {
+            if remaining_ticks == 0 {
                 return None;
             }
         }
 
         None
     }
-}
 
-/// Find path \o/
-pub fn find_path(
-    from: Point,
-    to: Point,
-    width: u32,
-    height: u32,
-    multiplier: u32,
-    min_weight: f32,
-    weights: &Vec<f32>,
-) -> Option<PathResult> {
-    // openset contains seen nodes which havent yet been visited
-    let mut openset: HybridHeap<u32, f32> = HybridHeap::with_capacity(1000);
-
-    // g scores contains the currently best scores for visited nodes and from where we ended up here
-    let mut g_score: HashMap<u32, VisitedPoint<f32, u32>> = HashMap::with_capacity(1000);
-
-    let from_index = from.to_1d_index(width);
-    let to_index = to.to_1d_index(width);
-
-    g_score.insert(
-        from_index,
-        VisitedPoint {
-            score: 0.0,
-            came_from_key: from_index,
-        },
-    );
-    openset.push(
-        from_index,
-        calculate_heuristical_distance(&from, &to, multiplier, min_weight),
-    );
-
-    while let Some(current_index) = openset.pop() {
-        if current_index == to_index {
-            return Some(PathResult {
-                from_index,
-                to_index,
-                total_distance: g_score.get(&to_index).unwrap().score,
-                path_indexes: reconstruct_path(&g_score, to_index),
-                visited_indexes: g_score,
-            });
-        }
-
-        tick(
-            &mut g_score,
-            &mut openset,
-            &to,
-            current_index,
-            width,
-            height,
-            multiplier,
-            min_weight,
-            weights,
+    /// Run the search until the target is found or the openset runs out
+    pub fn run(&mut self, weights: &[f32]) -> Option<f32> {
+        while let Some(current_key) = self.openset.pop() {
+            let current_index = self.cell(current_key);
+
+            if self.goal_indexes.contains(&current_index) {
+                return Some(self.reach_goal(current_key));
+            }
+
+            self.expand(current_key, weights);
+        }
+
+        None
+    }
+
+    /// Cells of the found path in order, from and to included
+    pub fn ordered_path(&self) -> Option<Vec<u32>> {
+        self.path_indexes.as_ref()?;
+        Some(
+            reconstruct_ordered_path(&self.g_score, self.to_key)
+                .into_iter()
+                .map(|key| self.cell(key))
+                .collect(),
         )
     }
-    None
-}
 
-fn tick(
-    g_score: &mut HashMap<u32, VisitedPoint<f32, u32>>,
-    openset: &mut HybridHeap<u32, f32>,
-    to: &Point,
-    current_index: u32,
-    width: u32,
-    height: u32,
-    multiplier: u32,
-    min_weight: f32,
-    weights: &Vec<f32>,
-) {
-    let current_score = g_score[&current_index];
-    let current_point = Point::from_1d_index(width, current_index);
+    /// States of the found path in order, with the heading each cell was entered with when there is a turn penalty
+    pub fn ordered_states(&self) -> Option<Vec<HeadingState>> {
+        self.path_indexes.as_ref()?;
+        Some(
+            reconstruct_ordered_path(&self.g_score, self.to_key)
+                .into_iter()
+                .map(|key| self.state(key))
+                .collect(),
+        )
+    }
+
+    /// Cell and heading of a search state key, see visited_points
+    #[inline(always)]
+    pub fn state(&self, key: u32) -> HeadingState {
+        HeadingState {
+            index: self.cell(key),
+            heading: match self.turn_penalty {
+                Some(_) if key % HEADING_SLOTS < HEADING_SLOTS - 1 => {
+                    Some((key % HEADING_SLOTS) as u8)
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// Cost of a single move the way the search sees it, the cheaper of walking to a neighbour and any portal between the cells
+    /// Infinite if neither is possible, eg a wall, a one-way cell or cells too far apart without a portal
+    pub fn move_cost(&self, from_index: u32, to_index: u32, weights: &[f32]) -> f32 {
+        let from = Point::from_1d_index(self.width, from_index);
+        let to = Point::from_1d_index(self.width, to_index);
+
+        let exit_mask = self
+            .exit_masks
+            .get(from_index as usize)
+            .copied()
+            .unwrap_or(ALL_EXITS);
+
+        let walk = match from.x.abs_diff(to.x) <= 1
+            && from.y.abs_diff(to.y) <= 1
+            && from_index != to_index
+            && allows_exit(exit_mask, &from, &to)
+        {
+            true => match self.neighbour_weight(&from, &to, weights) {
+                weight if weight > 0.0 => weight,
+                _ => f32::INFINITY,
+            },
+            false => f32::INFINITY,
+        };
+
+        self.portals
+            .links_from(from_index)
+            .iter()
+            .filter(|link| link.to_index == to_index)
+            .map(|link| link.cost)
+            .fold(walk, f32::min)
+    }
 
-    for neighbour_index in get_neighbours(&current_point, width, height) {
-        let neighbour_point = Point::from_1d_index(width, neighbour_index);
-        let weight = calculate_weight(&current_point, &neighbour_point, &weights, width);
+    /// Consume the finished search into a result, None if the path hasnt been found (yet)
+    pub fn into_result(self) -> Option<PathResult> {
+        Some(PathResult {
+            from_index: self.from_index,
+            to_index: self.to_index,
+            total_distance: self.g_score.get(&self.to_key)?.score,
+            path_indexes: self.path_indexes?,
+            visited_indexes: self.g_score,
+        })
+    }
+
+    /// Take the goal state as the end of the path, returns its cost
+    fn reach_goal(&mut self, goal_key: u32) -> f32 {
+        self.to_index = self.cell(goal_key);
+        self.to_key = goal_key;
+        self.path_indexes = Some(
+            reconstruct_path(&self.g_score, goal_key)
+                .into_iter()
+                .map(|key| self.cell(key))
+                .collect(),
+        );
+        self.g_score[&goal_key].score
+    }
+
+    /// Cheapest visited state of any goal, goals can be entered with any heading when there is a turn penalty
+    fn best_goal_key(&self) -> Option<u32> {
+        let slots = match self.turn_penalty {
+            Some(_) => HEADING_SLOTS,
+            None => 1,
+        };
+
+        self.goal_indexes
+            .iter()
+            .flat_map(|goal| (0..slots).map(move |slot| goal * slots + slot))
+            .filter_map(|key| self.g_score.get(&key).map(|p| (key, p.score)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(key, _)| key)
+    }
 
-        // wall...
-        if weight <= 0.0 {
-            continue;
+    fn update_portal_bound(&mut self) {
+        self.portal_bound = self.portals.min_cost().map(|min_cost| {
+            min_cost
+                + self
+                    .portals
+                    .exit_indexes()
+                    .into_iter()
+                    .map(|index| self.goal_distance(&Point::from_1d_index(self.width, index)))
+                    .fold(f32::MAX, f32::min)
+        });
+    }
+
+    /// Search state key of the cell entered with the heading, the cell itself without a turn penalty
+    #[inline(always)]
+    fn key(&self, index: u32, heading: Option<u8>) -> u32 {
+        match self.turn_penalty {
+            Some(_) => index * HEADING_SLOTS + heading.map_or(HEADING_SLOTS - 1, u32::from),
+            None => index,
+        }
+    }
+
+    #[inline(always)]
+    fn cell(&self, key: u32) -> u32 {
+        match self.turn_penalty {
+            Some(_) => key / HEADING_SLOTS,
+            None => key,
+        }
+    }
+
+    /// Heuristical distance to the nearest goal
+    #[inline(always)]
+    fn goal_distance(&self, point: &Point) -> f32 {
+        if self.goals.len() > MULTI_TARGET_DIJKSTRA_THRESHOLD {
+            return 0.0;
         }
 
-        let tentative_g_score = current_score.score + weight;
+        self.goals
+            .iter()
+            .map(|goal| {
+                let distance =
+                    calculate_heuristical_distance(point, goal, self.multiplier, self.min_weight);
+
+                match &self.landmarks {
+                    Some(landmarks) => {
+                        distance.max(landmarks.lower_bound(
+                            point.to_1d_index(self.width),
+                            goal.to_1d_index(self.width),
+                        ))
+                    }
+                    None => distance,
+                }
+            })
+            .fold(f32::MAX, f32::min)
+    }
+
+    #[inline(always)]
+    fn heuristic(&self, point: &Point) -> f32 {
+        let distance = self.goal_distance(point);
+
+        match self.portal_bound {
+            Some(bound) => distance.min(bound),
+            None => distance,
+        }
+    }
+
+    #[inline(always)]
+    fn state_heuristic(&self, key: u32) -> f32 {
+        self.heuristic(&Point::from_1d_index(self.width, self.cell(key)))
+    }
+
+    fn expand(&mut self, current_key: u32, weights: &[f32]) {
+        let current_score = self.g_score[&current_key];
+        let current = self.state(current_key);
+        let current_index = current.index;
+        let current_point = Point::from_1d_index(self.width, current_index);
+
+        let exit_mask = self
+            .exit_masks
+            .get(current_index as usize)
+            .copied()
+            .unwrap_or(ALL_EXITS);
+
+        for neighbour_index in get_neighbours(&current_point, self.width, self.height) {
+            let neighbour_point = Point::from_1d_index(self.width, neighbour_index);
+
+            // one-way cell...
+            if exit_mask != ALL_EXITS && !allows_exit(exit_mask, &current_point, &neighbour_point) {
+                continue;
+            }
+
+            let weight = self.neighbour_weight(&current_point, &neighbour_point, weights);
+
+            // wall...
+            if weight <= 0.0 {
+                continue;
+            }
+
+            let (neighbour_key, turn_cost) = match self.turn_penalty {
+                Some(turn_penalty) => {
+                    let heading = Some(heading_between(&current_point, &neighbour_point));
+                    (
+                        self.key(neighbour_index, heading),
+                        turn_penalty * turn_steps(current.heading, heading) as f32,
+                    )
+                }
+                None => (neighbour_index, 0.0),
+            };
+
+            self.relax(
+                current_key,
+                neighbour_key,
+                current_score.score + weight + turn_cost,
+            );
+        }
+
+        // indexed so the links can be read while relaxing without copying them
+        for i in 0..self.portals.links_from(current_index).len() {
+            let link = self.portals.links_from(current_index)[i];
+
+            // portal leading into a wall...
+            if weights[link.to_index as usize] < 0.0 {
+                continue;
+            }
+
+            self.relax(
+                current_key,
+                self.key(link.to_index, None),
+                current_score.score + link.cost,
+            );
+        }
+    }
+
+    /// Weight of moving to a neighbouring cell, including the slope if there is elevation
+    #[inline(always)]
+    fn neighbour_weight(&self, from: &Point, to: &Point, weights: &[f32]) -> f32 {
+        match &self.elevation {
+            Some(elevation) => {
+                calculate_weight_with_elevation(from, to, weights, self.width, elevation)
+            }
+            None => calculate_weight(from, to, weights, self.width),
+        }
+    }
+
+    #[inline(always)]
+    fn relax(&mut self, current_key: u32, neighbour_key: u32, tentative_g_score: f32) {
+        let (current_index, neighbour_index) = (self.cell(current_key), self.cell(neighbour_key));
+
+        if (!self.blocked_cells.is_empty() && self.blocked_cells.contains(&neighbour_index))
+            || (!self.blocked_edges.is_empty()
+                && self
+                    .blocked_edges
+                    .contains(&(current_index, neighbour_index)))
+        {
+            return;
+        }
+
+        if !self.clearance.is_empty()
+            && !has_clearance(self.clearance[neighbour_index as usize], self.agent_radius)
+        {
+            return;
+        }
 
         // If this neighbour is already processed and the gscore through the current node is not lower, we can skip to the next
         // otherwise upsert the new score
-        match g_score.get(&neighbour_index) {
-            Some(p) if (*p).score <= tentative_g_score => continue,
-            _ => g_score.insert(
-                neighbour_index,
+        match self.g_score.get(&neighbour_key) {
+            Some(p) if p.score <= tentative_g_score => return,
+            _ => self.g_score.insert(
+                neighbour_key,
                 VisitedPoint {
                     score: tentative_g_score,
-                    came_from_key: current_index,
+                    came_from_key: current_key,
                 },
             ),
         };
 
+        // already expanded with the current inflation, it gets another go once the inflation is lowered
+        if !self.closed.is_empty() && self.closed.contains(&neighbour_key) {
+            self.inconsistent.insert(neighbour_key);
+            return;
+        }
+
         let tentative_f_score = tentative_g_score
-            + calculate_heuristical_distance(&neighbour_point, &to, multiplier, min_weight);
+            + self.inflation * self.heuristic(&Point::from_1d_index(self.width, neighbour_index));
 
         // If the neighbour node is seen for the first time, ie not open and not closed, put it in the openset
         // We can safely try to decrease the key, if the value is higher or doesnt exist, nothing will happen
-        match openset.get_value(neighbour_index) {
+        match self.openset.get_value(neighbour_key) {
             Some(v) if v > tentative_f_score => {
-                openset.change_value(neighbour_index, tentative_f_score)
+                self.openset.change_value(neighbour_key, tentative_f_score)
             }
-            _ => openset.push(neighbour_index, tentative_f_score),
+            _ => self.openset.push(neighbour_key, tentative_f_score),
         };
     }
 }
 
+/// Find path \o/
+pub fn find_path(
+    from: Point,
+    to: Point,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+) -> Option<PathResult> {
+    let mut path_finder = FindPath::new(from, to, width, height, multiplier, min_weight);
+    path_finder.run(weights)?;
+    path_finder.into_result()
+}
+
+/// Find path for an agent larger than a single cell, only cells with enough clearance for the radius are traversed
+#[allow(clippy::too_many_arguments)]
+pub fn find_path_for_agent(
+    from: Point,
+    to: Point,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+    clearance: &[f32],
+    agent_radius: f32,
+) -> Option<PathResult> {
+    let mut path_finder = FindPath::new(from, to, width, height, multiplier, min_weight)
+        .with_clearance(clearance.to_vec(), agent_radius);
+    path_finder.run(weights)?;
+    path_finder.into_result()
+}
+
+/// Find path where climbing and descending cost extra on top of the terrain weights, and too steep moves are impassable
+#[allow(clippy::too_many_arguments)]
+pub fn find_path_with_elevation(
+    from: Point,
+    to: Point,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+    elevation: &Elevation,
+) -> Option<PathResult> {
+    let mut path_finder = FindPath::new(from, to, width, height, multiplier, min_weight)
+        .with_elevation(elevation.clone());
+    path_finder.run(weights)?;
+    path_finder.into_result()
+}
+
+/// Find path with the ALT heuristic, see Landmarks
+#[allow(clippy::too_many_arguments)]
+pub fn find_path_with_landmarks(
+    from: Point,
+    to: Point,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+    landmarks: &Arc<Landmarks>,
+) -> Option<PathResult> {
+    let mut path_finder = FindPath::new(from, to, width, height, multiplier, min_weight)
+        .with_landmarks(landmarks.clone());
+    path_finder.run(weights)?;
+    path_finder.into_result()
+}
+
+/// Find path to whichever of the goals is cheapest to reach, the reached goal is the to_index of the result
+pub fn find_path_to_nearest(
+    from: Point,
+    goals: Vec<Point>,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+) -> Option<PathResult> {
+    let mut path_finder =
+        FindPath::new_multi_target(from, goals, width, height, multiplier, min_weight);
+    path_finder.run(weights)?;
+    path_finder.into_result()
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -548,4 +1130,84 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_find_path_to_nearest() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let result = find_path_to_nearest(
+            Point { x: 0, y: 0 },
+            vec![
+                Point { x: 9, y: 9 },
+                Point { x: 0, y: 5 },
+                Point { x: 9, y: 0 },
+            ],
+            10,
+            10,
+            1,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        assert_eq!(5.0, result.total_distance);
+        assert_eq!(50, result.to_index);
+        assert_eq!(HashSet::from([0, 10, 20, 30, 40]), result.path_indexes);
+    }
+
+    #[test]
+    fn test_find_path_to_nearest_dijkstra() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        // goals along the whole bottom row and right column, more than the threshold
+        let goals: Vec<Point> = (0..10)
+            .map(|x| Point { x, y: 9 })
+            .chain((0..9).map(|y| Point { x: 9, y }))
+            .chain((0..9).map(|y| Point { x: 8, y }))
+            .chain((0..9).map(|x| Point { x, y: 8 }))
+            .collect();
+        assert!(goals.len() > MULTI_TARGET_DIJKSTRA_THRESHOLD);
+
+        let mut path_finder =
+            FindPath::new_multi_target(Point { x: 2, y: 2 }, goals, 10, 10, 1, 1.0);
+
+        assert_eq!(Some(6.0), path_finder.run(&weights));
+        assert_eq!(
+            6.0,
+            path_finder.visited_points()[&path_finder.to_index].score
+        );
+        assert!(path_finder.reached_goal().is_some());
+    }
+
+    #[test]
+    fn test_components_give_up_right_away() {
+        #[rustfmt::skip]
+        let weights: Vec<f32> = vec![
+            1.0, 1.0, 1.0, -1.0, 1.0,
+            1.0, 1.0, 1.0, -1.0, 1.0,
+            1.0, 1.0, 1.0, -1.0, 1.0,
+        ];
+        let components = Arc::new(Components::new(5, 3, &weights));
+
+        let mut path_finder = FindPath::new(Point::new(0, 0), Point::new(4, 2), 5, 3, 1, 1.0)
+            .with_components(components.clone());
+
+        assert!(path_finder.is_known_unreachable());
+        assert_eq!(None, path_finder.run(&weights));
+        assert_eq!(1, path_finder.visited_points().len());
+
+        // still searched when the goal is reachable
+        path_finder.restart(Point::new(0, 0), Point::new(2, 2));
+        assert!(!path_finder.is_known_unreachable());
+        assert!(path_finder.run(&weights).is_some());
+
+        // portals may lead across walls
+        let mut portals = Portals::new(5, 3);
+        portals.add(&Point::new(2, 0), &Point::new(4, 0), 1.0, false);
+        let mut path_finder = FindPath::new(Point::new(0, 0), Point::new(4, 2), 5, 3, 1, 1.0)
+            .with_portals(portals)
+            .with_components(components);
+
+        assert!(path_finder.run(&weights).is_some());
+    }
 }
@@ -1,14 +1,15 @@
 use std::{
     collections::{HashMap, HashSet},
+    hash::Hash,
     ops::Mul,
 };
 
-use super::{astar::VisitedPoint, point::Point};
+use super::{astar::VisitedPoint, elevation::Elevation, point::Point};
 
 /// Calculates the weight from one cell to a neighbour. The weight is from the middle of the first cell to the middle of the second cell
 /// Moving diagonally increases weight.
 #[inline(always)]
-pub fn calculate_weight(from: &Point, to: &Point, weights: &Vec<f32>, width: u32) -> f32 {
+pub fn calculate_weight(from: &Point, to: &Point, weights: &[f32], width: u32) -> f32 {
     let mut to_weight = weights[to.to_1d_index(width) as usize];
 
     if to_weight < 0.0 {
@@ -25,6 +26,27 @@ pub fn calculate_weight(from: &Point, to: &Point, weights: &Vec<f32>, width: u32
     to_weight / 2.0 + from_weight / 2.0
 }
 
+/// Same as calculate_weight, with the slope cost between the cells added. Too steep moves are walls
+#[inline(always)]
+pub fn calculate_weight_with_elevation(
+    from: &Point,
+    to: &Point,
+    weights: &[f32],
+    width: u32,
+    elevation: &Elevation,
+) -> f32 {
+    let weight = calculate_weight(from, to, weights, width);
+
+    if weight < 0.0 {
+        return weight;
+    }
+
+    match elevation.edge_cost(from, to, width) {
+        Some(cost) => weight + cost,
+        None => -1.0,
+    }
+}
+
 /// Heuristic function... since we have an euclidean space, this will just be the euclidean distance with the minimum terrain weight
 /// For A* it is important to never overestimate the distance and therefore minimum weight is assumed
 pub fn calculate_heuristical_distance(
@@ -86,10 +108,11 @@ pub fn get_neighbours(point: &Point, width: u32, height: u32) -> Vec<u32> {
     neighbours
 }
 
-pub fn reconstruct_path(
-    visited: &HashMap<u32, VisitedPoint<f32, u32>>,
-    to_key: u32,
-) -> HashSet<u32> {
+/// Walk the came from keys back from the target, the keys can be plain cell indexes or richer search states
+pub fn reconstruct_path<K: Eq + Hash + Copy>(
+    visited: &HashMap<K, VisitedPoint<f32, K>>,
+    to_key: K,
+) -> HashSet<K> {
     let mut path = HashSet::new();
     let mut key = to_key;
 
@@ -104,3 +127,24 @@ pub fn reconstruct_path(
 
     path
 }
+
+/// Same as reconstruct_path, but the cells are returned in order from start to end, both included
+pub fn reconstruct_ordered_path<K: Eq + Hash + Copy>(
+    visited: &HashMap<K, VisitedPoint<f32, K>>,
+    to_key: K,
+) -> Vec<K> {
+    let mut path = vec![to_key];
+    let mut key = to_key;
+
+    while let Some(index) = visited.get(&key) {
+        if key == index.came_from_key {
+            break;
+        }
+
+        path.push(index.came_from_key);
+        key = index.came_from_key;
+    }
+
+    path.reverse();
+    path
+}
@@ -0,0 +1,119 @@
+use super::{astar::FindPath, point::Point};
+
+pub struct QueryResult {
+    pub path: Vec<u32>, // cells in order from start to end, both included, empty if unreachable
+    pub total_distance: Option<f32>,
+    pub nodes_visited_count: u32,
+}
+
+/// Find paths for many queries on the same map
+/// The queries are searched one after another with the same path finder, so the openset and scores are allocated once for the whole batch
+pub fn find_paths(
+    queries: &[(Point, Point)],
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+) -> Vec<QueryResult> {
+    let Some((from, to)) = queries.first() else {
+        return Vec::new();
+    };
+
+    find_paths_with(
+        &mut FindPath::new(
+            from.clone(),
+            to.clone(),
+            width,
+            height,
+            multiplier,
+            min_weight,
+        ),
+        queries,
+        weights,
+    )
+}
+
+/// Find paths for many queries using an existing path finder, eg one with portals or landmarks
+pub fn find_paths_with(
+    path_finder: &mut FindPath,
+    queries: &[(Point, Point)],
+    weights: &[f32],
+) -> Vec<QueryResult> {
+    queries
+        .iter()
+        .map(|(from, to)| run_query(path_finder, from, to, weights))
+        .collect()
+}
+
+/// Restart the path finder for one query and run it to the end
+pub fn run_query(
+    path_finder: &mut FindPath,
+    from: &Point,
+    to: &Point,
+    weights: &[f32],
+) -> QueryResult {
+    path_finder.restart(from.clone(), to.clone());
+
+    let total_distance = path_finder.run(weights);
+
+    QueryResult {
+        path: path_finder.ordered_path().unwrap_or_default(),
+        total_distance,
+        nodes_visited_count: path_finder.visited_points().len() as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::astar::find_path;
+
+    use super::*;
+
+    #[test]
+    fn test_same_as_separate_searches() {
+        #[rustfmt::skip]
+        let weights: Vec<f32> = vec![
+            1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+            1.0, -1.0, -1.0, -1.0, -1.0, 1.0,
+            1.0, 2.0, 1.0, 3.0, -1.0, 1.0,
+            1.0, -1.0, 1.0, 1.0, -1.0, -1.0,
+            1.0, -1.0, 1.0, 2.0, -1.0, 1.0,
+            1.0, 1.0, 1.0, 1.0, -1.0, 1.0,
+        ];
+
+        let queries = vec![
+            (Point::new(0, 0), Point::new(3, 3)),
+            (Point::new(5, 0), Point::new(0, 5)),
+            (Point::new(0, 0), Point::new(5, 5)), // walled off
+            (Point::new(2, 2), Point::new(2, 2)),
+        ];
+
+        let results = find_paths(&queries, 6, 6, 1, 1.0, &weights);
+
+        assert_eq!(queries.len(), results.len());
+
+        for ((from, to), result) in queries.iter().zip(&results) {
+            match find_path(from.clone(), to.clone(), 6, 6, 1, 1.0, &weights) {
+                Some(expected) => {
+                    assert_eq!(Some(expected.total_distance), result.total_distance);
+                    assert_eq!(Some(&from.to_1d_index(6)), result.path.first());
+                    assert_eq!(Some(&to.to_1d_index(6)), result.path.last());
+                    assert_eq!(
+                        expected.visited_indexes.len() as u32,
+                        result.nodes_visited_count
+                    );
+                }
+                None => {
+                    assert_eq!(None, result.total_distance);
+                    assert!(result.path.is_empty());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_queries() {
+        assert!(find_paths(&[], 1, 1, 1, 1.0, &[1.0]).is_empty());
+    }
+}
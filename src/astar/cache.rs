@@ -0,0 +1,342 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+};
+
+use super::{astar_utils::calculate_heuristical_distance, point::Point};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u32,
+    pub misses: u32,
+    pub invalidations: u32, // entries dropped because the map changed
+    pub evictions: u32,     // entries dropped to make room
+}
+
+pub struct CachedPath {
+    pub path: Vec<u32>, // cells in order from start to end, empty if unreachable
+    pub total_distance: Option<f32>,
+    margin: u32, // changes this many cells away from the path still affect it
+    bounds: (u32, u32, u32, u32), // min x, min y, max x, max y of the path grown by the margin
+    last_used: u64,
+}
+
+/// Least recently used cache of search results keyed by the end points and options, O being whatever else changes the result eg the heuristic multiplier
+/// Results are dropped when a cell changes in a way that could change them:
+/// - a change on or next to the path may make it more expensive or block it
+/// - a cell getting cheaper inside the ellipse where any cheaper path has to run may make a cheaper path possible
+/// - any cell getting cheaper may make unreachable targets reachable
+///
+/// The ellipse is bounded with the euclidean heuristic, so it does not hold with portals or cells cheaper than min weight, clear the cache for those
+pub struct PathCache<O> {
+    capacity: usize,
+    width: u32,
+    min_weight: f32,
+    entries: HashMap<(u32, u32, O), CachedPath>,
+    recency: BTreeMap<u64, (u32, u32, O)>, // keys by last use, oldest first
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl<O: Clone + Eq + Hash> PathCache<O> {
+    pub fn new(capacity: usize, width: u32, min_weight: f32) -> Self {
+        PathCache {
+            capacity,
+            width,
+            min_weight,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Cached result for the query, counted as a hit or a miss
+    pub fn get(&mut self, from_index: u32, to_index: u32, options: &O) -> Option<&CachedPath> {
+        let key = (from_index, to_index, options.clone());
+
+        match self.entries.get_mut(&key) {
+            Some(entry) => {
+                self.stats.hits += 1;
+                self.clock += 1;
+                self.recency.remove(&entry.last_used);
+                self.recency.insert(self.clock, key);
+                entry.last_used = self.clock;
+                Some(entry)
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Cache the result of a search, evicting the least recently used entry if the cache is full
+    /// Margin is how far from the path changes can still affect it, eg the agent radius when clearance is considered
+    pub fn insert(
+        &mut self,
+        from_index: u32,
+        to_index: u32,
+        options: O,
+        path: Vec<u32>,
+        total_distance: Option<f32>,
+        margin: u32,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let key = (from_index, to_index, options);
+
+        if let Some(previous) = self.entries.remove(&key) {
+            self.recency.remove(&previous.last_used);
+        } else if self.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = self.recency.pop_first() {
+                self.entries.remove(&oldest);
+                self.stats.evictions += 1;
+            }
+        }
+
+        let bounds = path.iter().fold(
+            (u32::MAX, u32::MAX, 0, 0),
+            |(min_x, min_y, max_x, max_y), &index| {
+                let point = Point::from_1d_index(self.width, index);
+                (
+                    min_x.min(point.x.saturating_sub(margin)),
+                    min_y.min(point.y.saturating_sub(margin)),
+                    max_x.max(point.x + margin),
+                    max_y.max(point.y + margin),
+                )
+            },
+        );
+
+        self.clock += 1;
+        self.recency.insert(self.clock, key.clone());
+        self.entries.insert(
+            key,
+            CachedPath {
+                path,
+                total_distance,
+                margin,
+                bounds,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    /// Drop the entries the weight change of the cell may affect, returns how many were dropped
+    pub fn invalidate_cell(&mut self, index: u32, previous_weight: f32, weight: f32) -> usize {
+        if previous_weight == weight {
+            return 0;
+        }
+
+        let got_cheaper = previous_weight < 0.0 || (weight >= 0.0 && weight < previous_weight);
+
+        // the heuristic would overestimate around the cell, so nothing can be ruled out
+        if got_cheaper && weight < self.min_weight {
+            return self.clear();
+        }
+
+        let cell = Point::from_1d_index(self.width, index);
+        let (width, min_weight) = (self.width, self.min_weight);
+
+        let stale: Vec<(u32, u32, O)> = self
+            .entries
+            .iter()
+            .filter(
+                |((from_index, to_index, _), entry)| match entry.total_distance {
+                    Some(distance) => {
+                        entry.is_near(&cell, width)
+                            || (got_cheaper
+                                && cheapest_through(
+                                    *from_index,
+                                    *to_index,
+                                    &cell,
+                                    entry.margin,
+                                    width,
+                                    min_weight,
+                                ) < distance)
+                    }
+                    None => got_cheaper,
+                },
+            )
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in &stale {
+            let entry = self.entries.remove(key).unwrap();
+            self.recency.remove(&entry.last_used);
+        }
+
+        self.stats.invalidations += stale.len() as u32;
+        stale.len()
+    }
+
+    /// Drop everything, eg when portals or elevation change, returns how many entries were dropped
+    pub fn clear(&mut self) -> usize {
+        let count = self.entries.len();
+        self.entries.clear();
+        self.recency.clear();
+        self.stats.invalidations += count as u32;
+        count
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl CachedPath {
+    /// Is the cell on the path or within the margin of it
+    fn is_near(&self, cell: &Point, width: u32) -> bool {
+        let (min_x, min_y, max_x, max_y) = self.bounds;
+
+        if cell.x < min_x || cell.x > max_x || cell.y < min_y || cell.y > max_y {
+            return false;
+        }
+
+        self.path.iter().any(|&index| {
+            let point = Point::from_1d_index(width, index);
+            point.x.abs_diff(cell.x) <= self.margin && point.y.abs_diff(cell.y) <= self.margin
+        })
+    }
+}
+
+/// Lower bound for the cost of any path from start to end passing within margin of the cell
+fn cheapest_through(
+    from_index: u32,
+    to_index: u32,
+    cell: &Point,
+    margin: u32,
+    width: u32,
+    min_weight: f32,
+) -> f32 {
+    let from = Point::from_1d_index(width, from_index);
+    let to = Point::from_1d_index(width, to_index);
+    let slack = calculate_heuristical_distance(
+        &Point::new(0, 0),
+        &Point::new(margin, margin),
+        1,
+        min_weight,
+    );
+
+    calculate_heuristical_distance(&from, cell, 1, min_weight)
+        + calculate_heuristical_distance(cell, &to, 1, min_weight)
+        - 2.0 * slack
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::{astar::find_path, astar_utils::reconstruct_ordered_path};
+
+    use super::*;
+
+    fn cache_search(
+        cache: &mut PathCache<u32>,
+        from: Point,
+        to: Point,
+        weights: &[f32],
+    ) -> Option<f32> {
+        let (from_index, to_index) = (from.to_1d_index(10), to.to_1d_index(10));
+
+        if let Some(entry) = cache.get(from_index, to_index, &1) {
+            return entry.total_distance;
+        }
+
+        let result = find_path(from, to, 10, 10, 1, 1.0, weights);
+        let total_distance = result.as_ref().map(|r| r.total_distance);
+        let path = result
+            .map(|r| reconstruct_ordered_path(&r.visited_indexes, to_index))
+            .unwrap_or_default();
+
+        cache.insert(from_index, to_index, 1, path, total_distance, 0);
+        total_distance
+    }
+
+    #[test]
+    fn test_least_recently_used_is_evicted() {
+        let mut cache: PathCache<u32> = PathCache::new(2, 10, 1.0);
+
+        cache.insert(0, 1, 1, vec![0, 1], Some(1.0), 0);
+        cache.insert(0, 2, 1, vec![0, 1, 2], Some(2.0), 0);
+        assert!(cache.get(0, 1, &1).is_some());
+
+        cache.insert(0, 3, 1, vec![0, 1, 2, 3], Some(3.0), 0);
+        assert_eq!(2, cache.len());
+        assert!(cache.get(0, 2, &1).is_none());
+        assert!(cache.get(0, 1, &1).is_some());
+        assert!(cache.get(0, 3, &1).is_some());
+
+        // options are part of the key
+        assert!(cache.get(0, 3, &2).is_none());
+
+        assert_eq!(
+            CacheStats {
+                hits: 3,
+                misses: 2,
+                invalidations: 0,
+                evictions: 1
+            },
+            cache.stats()
+        );
+    }
+
+    #[test]
+    fn test_invalidation() {
+        let mut weights = vec![1.0; 100];
+        for y in 0..9 {
+            weights[y * 10 + 5] = -1.0; // wall with a gap at the bottom
+        }
+
+        let mut cache: PathCache<u32> = PathCache::new(16, 10, 1.0);
+        let long = cache_search(&mut cache, Point::new(0, 0), Point::new(9, 0), &weights);
+        let short = cache_search(&mut cache, Point::new(0, 9), Point::new(2, 9), &weights);
+        let same = cache_search(&mut cache, Point::new(7, 7), Point::new(7, 7), &weights);
+
+        // far away and getting more expensive, nothing to do
+        assert_eq!(0, cache.invalidate_cell(99, 1.0, 5.0));
+
+        // opening the wall at the top makes a cheaper path for the long query possible, but is far from the short one
+        weights[5] = 1.0;
+        assert_eq!(1, cache.invalidate_cell(5, -1.0, 1.0));
+        assert!(cache.get(0, 9, &1).is_none());
+
+        let shorter = cache_search(&mut cache, Point::new(0, 0), Point::new(9, 0), &weights);
+        assert!(shorter < long);
+
+        // blocking a cell on the path drops it
+        weights[92] = 9.0;
+        assert_eq!(1, cache.invalidate_cell(92, 1.0, 9.0));
+        assert_ne!(
+            short,
+            cache_search(&mut cache, Point::new(0, 9), Point::new(2, 9), &weights)
+        );
+
+        // cheaper than the min weight, nothing can be ruled out
+        assert_eq!(3, cache.invalidate_cell(50, 1.0, 0.5));
+        assert!(cache.is_empty());
+        assert_eq!(Some(0.0), same);
+    }
+
+    #[test]
+    fn test_unreachable_invalidated_when_cheaper() {
+        let mut cache: PathCache<u32> = PathCache::new(16, 10, 1.0);
+        cache.insert(0, 99, 1, Vec::new(), None, 0);
+
+        assert_eq!(0, cache.invalidate_cell(50, 1.0, -1.0));
+        assert_eq!(1, cache.invalidate_cell(50, -1.0, 1.0));
+        assert_eq!(1, cache.stats().invalidations);
+    }
+}
@@ -0,0 +1,261 @@
+use std::{collections::HashSet, f32::consts::SQRT_2};
+
+use crate::hybridheap::HybridHeap;
+
+use super::{astar_utils::get_neighbours, dijkstra::distance_field, point::Point};
+
+/// Agent radius of a regular agent occupying a single cell, any passable cell has enough clearance for it
+pub const SINGLE_CELL_AGENT_RADIUS: f32 = 0.5;
+
+/// Distance from the center of each cell to the center of the nearest wall cell, where the map border also counts as a wall
+/// Walls have clearance 0 and cells next to a wall or the border have clearance 1
+pub fn clearance_map(weights: &[f32], width: u32, height: u32) -> Vec<f32> {
+    let walls: Vec<Point> = weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight < 0.0)
+        .map(|(index, _)| Point::from_1d_index(width, index as u32))
+        .collect();
+
+    // distances between cells, ignoring terrain
+    let uniform_weights = vec![1.0; weights.len()];
+    let field = distance_field(&walls, width, height, &uniform_weights);
+
+    field
+        .distances
+        .iter()
+        .enumerate()
+        .map(|(index, distance)| {
+            let point = Point::from_1d_index(width, index as u32);
+            distance.min(border_distance(&point, width, height))
+        })
+        .collect()
+}
+
+/// Update the clearance map after the cell at the index became a wall or stopped being one
+/// Only the cells the wall is (or was) nearest to are touched, instead of rebuilding the whole map
+pub fn update_clearance(
+    clearance: &mut [f32],
+    weights: &[f32],
+    width: u32,
+    height: u32,
+    index: u32,
+) {
+    let is_wall = weights[index as usize] < 0.0;
+    let was_wall = clearance[index as usize] == 0.0;
+
+    if is_wall && !was_wall {
+        // a new wall can only bring cells closer, spread from it while it does
+        clearance[index as usize] = 0.0;
+        spread_clearance(clearance, width, height, vec![index], |_| true);
+    } else if was_wall && !is_wall {
+        let wall = Point::from_1d_index(width, index);
+
+        // cells the removed wall was nearest to, they are reached through each other from the wall
+        let mut region: HashSet<u32> = HashSet::from([index]);
+        let mut stack = vec![index];
+        while let Some(current) = stack.pop() {
+            for neighbour in get_neighbours(&Point::from_1d_index(width, current), width, height) {
+                let point = Point::from_1d_index(width, neighbour);
+                let distance = octile_distance(&wall, &point);
+
+                if (clearance[neighbour as usize] - distance).abs() < 1e-3
+                    && region.insert(neighbour)
+                {
+                    stack.push(neighbour);
+                }
+            }
+        }
+
+        // start from the border and the cells outside of the region, which keep their nearest wall
+        for cell in &region {
+            let point = Point::from_1d_index(width, *cell);
+            let mut best = border_distance(&point, width, height);
+
+            for neighbour in get_neighbours(&point, width, height) {
+                if !region.contains(&neighbour) {
+                    let neighbour_point = Point::from_1d_index(width, neighbour);
+                    best = best.min(
+                        clearance[neighbour as usize] + octile_distance(&point, &neighbour_point),
+                    );
+                }
+            }
+
+            clearance[*cell as usize] = best;
+        }
+
+        let sources: Vec<u32> = region.iter().copied().collect();
+        spread_clearance(clearance, width, height, sources, |cell| {
+            region.contains(&cell)
+        });
+    }
+}
+
+/// Lower the clearance of cells reachable from the sources, as long as it gets lower
+fn spread_clearance(
+    clearance: &mut [f32],
+    width: u32,
+    height: u32,
+    sources: Vec<u32>,
+    allowed: impl Fn(u32) -> bool,
+) {
+    let mut openset: HybridHeap<u32, f32> = HybridHeap::with_capacity(sources.len());
+    for source in sources {
+        openset.push(source, clearance[source as usize]);
+    }
+
+    while let Some(current) = openset.pop() {
+        let point = Point::from_1d_index(width, current);
+
+        for neighbour in get_neighbours(&point, width, height) {
+            if !allowed(neighbour) {
+                continue;
+            }
+
+            let neighbour_point = Point::from_1d_index(width, neighbour);
+            let distance = clearance[current as usize] + octile_distance(&point, &neighbour_point);
+
+            if distance < clearance[neighbour as usize] {
+                clearance[neighbour as usize] = distance;
+
+                match openset.get_value(neighbour) {
+                    Some(_) => openset.change_value(neighbour, distance),
+                    None => openset.push(neighbour, distance),
+                }
+            }
+        }
+    }
+}
+
+/// Distance from the cell to the nearest cell just outside of the map
+#[inline(always)]
+fn border_distance(point: &Point, width: u32, height: u32) -> f32 {
+    (point.x + 1)
+        .min(point.y + 1)
+        .min(width - point.x)
+        .min(height - point.y) as f32
+}
+
+/// Distance with straight and diagonal steps, the same the distance field uses on uniform weights
+#[inline(always)]
+fn octile_distance(from: &Point, to: &Point) -> f32 {
+    let dx = from.x.abs_diff(to.x);
+    let dy = from.y.abs_diff(to.y);
+
+    dx.max(dy) as f32 - dx.min(dy) as f32 + dx.min(dy) as f32 * SQRT_2
+}
+
+/// Whether an agent with the radius fits in a cell with the clearance
+/// The wall edge is half a cell closer than its center, so a single cell agent (radius 0.5) fits anywhere with clearance 1
+#[inline(always)]
+pub fn has_clearance(clearance: f32, agent_radius: f32) -> bool {
+    clearance - 0.5 >= agent_radius
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::astar::find_path_for_agent;
+
+    use super::*;
+
+    #[test]
+    fn test_clearance_map() {
+        let mut weights: Vec<f32> = vec![1.0; 100];
+        weights[Point::new(5, 5).to_1d_index(10) as usize] = -1.0;
+
+        let clearance = clearance_map(&weights, 10, 10);
+
+        assert_eq!(0.0, clearance[55]);
+        assert_eq!(1.0, clearance[54]);
+        assert_eq!(1.0, clearance[0]);
+        assert_eq!(2.0, clearance[11]);
+        assert_eq!(3.0, clearance[52]);
+    }
+
+    #[test]
+    fn test_update_clearance_matches_full_map() {
+        let width = 12;
+        let height = 9;
+        let mut weights: Vec<f32> = vec![1.0; (width * height) as usize];
+        let mut clearance = clearance_map(&weights, width, height);
+
+        // add walls, then take some of them away again
+        let edits = [
+            (5, 4, -1.0),
+            (6, 4, -1.0),
+            (2, 7, -1.0),
+            (9, 1, -1.0),
+            (5, 4, 1.0),
+            (2, 7, 3.0),
+            (6, 4, 1.0),
+        ];
+        for (x, y, weight) in edits {
+            let index = Point::new(x, y).to_1d_index(width);
+            weights[index as usize] = weight;
+            update_clearance(&mut clearance, &weights, width, height, index);
+
+            let expected = clearance_map(&weights, width, height);
+            for (actual, expected) in clearance.iter().zip(&expected) {
+                assert!((actual - expected).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_has_clearance() {
+        assert!(has_clearance(1.0, SINGLE_CELL_AGENT_RADIUS));
+        assert!(!has_clearance(1.0, 1.0));
+        assert!(has_clearance(1.5, 1.0));
+    }
+
+    #[test]
+    fn test_find_path_for_agent_avoids_narrow_corridor() {
+        // narrow gap at the top and a wide one at the bottom of a wall
+        let width = 10;
+        let height = 12;
+        let mut weights: Vec<f32> = vec![1.0; (width * height) as usize];
+        for y in 0..height {
+            if y != 2 && !(7..=9).contains(&y) {
+                weights[Point::new(5, y).to_1d_index(width) as usize] = -1.0;
+            }
+        }
+
+        let clearance = clearance_map(&weights, width, height);
+
+        let small = find_path_for_agent(
+            Point::new(2, 2),
+            Point::new(8, 2),
+            width,
+            height,
+            1,
+            1.0,
+            &weights,
+            &clearance,
+            SINGLE_CELL_AGENT_RADIUS,
+        )
+        .unwrap();
+
+        assert_eq!(6.0, small.total_distance);
+
+        let large = find_path_for_agent(
+            Point::new(2, 2),
+            Point::new(8, 2),
+            width,
+            height,
+            1,
+            1.0,
+            &weights,
+            &clearance,
+            1.0,
+        )
+        .unwrap();
+
+        assert!(large.total_distance > 6.0);
+        for index in &large.path_indexes {
+            assert!(has_clearance(clearance[*index as usize], 1.0));
+        }
+        assert!(large
+            .path_indexes
+            .contains(&Point::new(5, 8).to_1d_index(width)));
+    }
+}
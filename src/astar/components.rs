@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use super::{astar_utils::get_neighbours, point::Point};
+
+/// Label of walls
+pub const NO_COMPONENT: u32 = u32::MAX;
+
+/// Connected components of the passable cells, two cells in different components can never reach each other without portals
+/// Walls are the only thing considered, so cells in the same component may still be unreachable eg because of one-way cells or clearance
+#[derive(Clone, Debug)]
+pub struct Components {
+    width: u32,
+    height: u32,
+    labels: Vec<u32>,
+    sizes: HashMap<u32, u32>, // number of cells in each component
+    next_label: u32,
+}
+
+impl Components {
+    pub fn new(width: u32, height: u32, weights: &[f32]) -> Self {
+        let mut components = Components {
+            width,
+            height,
+            labels: vec![NO_COMPONENT; (width * height) as usize],
+            sizes: HashMap::new(),
+            next_label: 0,
+        };
+
+        for index in 0..width * height {
+            if weights[index as usize] >= 0.0 && components.labels[index as usize] == NO_COMPONENT {
+                components.fill(index, NO_COMPONENT, weights);
+            }
+        }
+
+        components
+    }
+
+    /// Component id of the cell, None for walls
+    pub fn component(&self, index: u32) -> Option<u32> {
+        match self.labels[index as usize] {
+            NO_COMPONENT => None,
+            label => Some(label),
+        }
+    }
+
+    pub fn are_connected(&self, a: u32, b: u32) -> bool {
+        match (self.component(a), self.component(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn labels(&self) -> &[u32] {
+        &self.labels
+    }
+
+    /// Update after the weight of the cell changed, only the components around the cell are touched
+    pub fn update_cell(&mut self, index: u32, weights: &[f32]) {
+        let is_wall = weights[index as usize] < 0.0;
+        let was_wall = self.labels[index as usize] == NO_COMPONENT;
+
+        if is_wall == was_wall {
+            return;
+        }
+
+        let neighbours = get_neighbours(
+            &Point::from_1d_index(self.width, index),
+            self.width,
+            self.height,
+        );
+
+        if is_wall {
+            // removing the cell may split its component, each part gets a new label
+            let label = self.labels[index as usize];
+            self.labels[index as usize] = NO_COMPONENT;
+            self.sizes.remove(&label);
+
+            for neighbour in neighbours {
+                if self.labels[neighbour as usize] == label {
+                    self.fill(neighbour, label, weights);
+                }
+            }
+        } else {
+            // adding the cell joins the components around it into the largest one
+            let largest = neighbours
+                .iter()
+                .filter_map(|&n| self.component(n))
+                .max_by_key(|label| self.sizes[label]);
+
+            match largest {
+                Some(largest) => {
+                    self.labels[index as usize] = largest;
+                    *self.sizes.get_mut(&largest).unwrap() += 1;
+
+                    for neighbour in neighbours {
+                        match self.component(neighbour) {
+                            Some(label) if label != largest => {
+                                self.sizes.remove(&label);
+                                let size = self.relabel(neighbour, label, largest);
+                                *self.sizes.get_mut(&largest).unwrap() += size;
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                None => self.fill(index, NO_COMPONENT, weights),
+            }
+        }
+    }
+
+    /// Give the passable cells labeled from_label around the start a new label
+    fn fill(&mut self, start: u32, from_label: u32, weights: &[f32]) {
+        let label = self.next_label;
+        self.next_label += 1;
+
+        let size = self.flood(
+            start,
+            |labels, index| labels[index as usize] == from_label && weights[index as usize] >= 0.0,
+            label,
+        );
+
+        self.sizes.insert(label, size);
+    }
+
+    /// Move the cells of the from component into the to component, returns the number of cells moved
+    fn relabel(&mut self, start: u32, from_label: u32, to_label: u32) -> u32 {
+        self.flood(
+            start,
+            |labels, index| labels[index as usize] == from_label,
+            to_label,
+        )
+    }
+
+    fn flood(&mut self, start: u32, matches: impl Fn(&[u32], u32) -> bool, label: u32) -> u32 {
+        let mut stack = vec![start];
+        let mut size = 0;
+        self.labels[start as usize] = label;
+
+        while let Some(index) = stack.pop() {
+            size += 1;
+
+            for neighbour in get_neighbours(
+                &Point::from_1d_index(self.width, index),
+                self.width,
+                self.height,
+            ) {
+                if matches(&self.labels, neighbour) {
+                    self.labels[neighbour as usize] = label;
+                    stack.push(neighbour);
+                }
+            }
+        }
+
+        size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    fn split_weights() -> Vec<f32> {
+        vec![
+            1.0, 1.0, -1.0, 1.0, 1.0,
+            1.0, 1.0, -1.0, 1.0, 1.0,
+            1.0, 1.0, -1.0, 1.0, 1.0,
+        ]
+    }
+
+    #[test]
+    fn test_components() {
+        let components = Components::new(5, 3, &split_weights());
+
+        assert_eq!(2, components.count());
+        assert!(components.are_connected(0, 11));
+        assert!(components.are_connected(3, 14));
+        assert!(!components.are_connected(0, 4));
+        assert!(!components.are_connected(0, 2));
+        assert_eq!(None, components.component(2));
+    }
+
+    #[test]
+    fn test_update_merges_and_splits() {
+        let mut weights = split_weights();
+        let mut components = Components::new(5, 3, &weights);
+
+        // opening the wall joins both sides
+        weights[7] = 1.0;
+        components.update_cell(7, &weights);
+        assert_eq!(1, components.count());
+        assert!(components.are_connected(0, 4));
+
+        // and closing it splits them again
+        weights[7] = -1.0;
+        components.update_cell(7, &weights);
+        assert_eq!(2, components.count());
+        assert!(!components.are_connected(0, 4));
+        assert!(components.are_connected(0, 10));
+
+        // a lone cell gets a component of its own
+        for index in [6, 1, 11, 0, 5, 10] {
+            weights[index] = -1.0;
+            components.update_cell(index as u32, &weights);
+        }
+        assert_eq!(1, components.count());
+
+        weights[5] = 1.0;
+        components.update_cell(5, &weights);
+        assert_eq!(2, components.count());
+
+        // matches computing everything again
+        let fresh = Components::new(5, 3, &weights);
+        for a in 0..15 {
+            for b in 0..15 {
+                assert_eq!(fresh.are_connected(a, b), components.are_connected(a, b));
+            }
+        }
+    }
+}
@@ -0,0 +1,502 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::hybridheap::HybridHeap;
+
+use super::{
+    astar_utils::{calculate_heuristical_distance, calculate_weight, get_neighbours},
+    point::Point,
+    spacetime::{find_timed_path_until, ReservationTable, TimedCell},
+};
+
+/// Backwards A* from a goal towards an origin, resumed whenever the distance of a cell not yet closed is asked for
+/// Gives the true distance to the goal ignoring other agents, which makes a perfect heuristic for cooperative search
+/// Moves between passable cells cost the same both ways, so searching backwards gives the same distances
+pub struct ReverseResumableAStar {
+    origin: Point,
+    width: u32,
+    height: u32,
+    min_weight: f32,
+    openset: HybridHeap<u32, f32>,
+    g_score: HashMap<u32, f32>,
+    closed: HashSet<u32>,
+}
+
+impl ReverseResumableAStar {
+    pub fn new(goal: Point, origin: Point, width: u32, height: u32, min_weight: f32) -> Self {
+        let mut search = ReverseResumableAStar {
+            origin,
+            width,
+            height,
+            min_weight,
+            openset: HybridHeap::with_capacity(1000),
+            g_score: HashMap::with_capacity(1000),
+            closed: HashSet::with_capacity(1000),
+        };
+
+        let goal_index = goal.to_1d_index(width);
+        let f_score = search.heuristic(&goal);
+
+        search.g_score.insert(goal_index, 0.0);
+        search.openset.push(goal_index, f_score);
+        search
+    }
+
+    #[inline(always)]
+    fn heuristic(&self, point: &Point) -> f32 {
+        calculate_heuristical_distance(point, &self.origin, 1, self.min_weight)
+    }
+
+    /// True distance from the cell to the goal, None if the goal cant be reached from it
+    pub fn distance(&mut self, index: u32, weights: &[f32]) -> Option<f32> {
+        if self.closed.contains(&index) {
+            return Some(self.g_score[&index]);
+        }
+
+        while let Some(current_index) = self.openset.pop() {
+            self.closed.insert(current_index);
+
+            let current_score = self.g_score[&current_index];
+            let current_point = Point::from_1d_index(self.width, current_index);
+
+            for neighbour_index in get_neighbours(&current_point, self.width, self.height) {
+                if self.closed.contains(&neighbour_index) || weights[neighbour_index as usize] < 0.0
+                {
+                    continue;
+                }
+
+                let neighbour_point = Point::from_1d_index(self.width, neighbour_index);
+                let weight =
+                    calculate_weight(&neighbour_point, &current_point, weights, self.width);
+
+                // wall...
+                if weight <= 0.0 {
+                    continue;
+                }
+
+                let tentative_g_score = current_score + weight;
+
+                match self.g_score.get(&neighbour_index) {
+                    Some(score) if *score <= tentative_g_score => continue,
+                    _ => self.g_score.insert(neighbour_index, tentative_g_score),
+                };
+
+                let tentative_f_score = tentative_g_score + self.heuristic(&neighbour_point);
+
+                match self.openset.get_value(neighbour_index) {
+                    Some(v) if v > tentative_f_score => self
+                        .openset
+                        .change_value(neighbour_index, tentative_f_score),
+                    Some(_) => (),
+                    None => self.openset.push(neighbour_index, tentative_f_score),
+                };
+            }
+
+            if current_index == index {
+                return Some(current_score);
+            }
+        }
+
+        None
+    }
+}
+
+struct CooperativeAgent {
+    goal_index: u32,
+    position: u32,
+    window: u32,
+    plan: Vec<TimedCell>, // one cell per time step from the start of the current cycle
+    heuristic: ReverseResumableAStar,
+}
+
+/// Windowed Hierarchical Cooperative A* for many agents
+/// Every cycle the agents plan one after another, each searching only window time steps ahead through a shared reservation table,
+/// with the rest of the way estimated by its own reverse resumable search. Plans are followed for half of the smallest window before replanning,
+/// and the planning order is rotated every cycle so nobody is stuck at the lowest priority.
+/// Agents which have not planned yet hold their cell for the next time step, and an agent boxed in where someone has planned to pass makes them plan again
+/// Not complete and not optimal, but fast enough for hundreds of agents
+pub struct CooperativeAStar {
+    agents: Vec<CooperativeAgent>,
+    order: Vec<usize>,
+    queue: VecDeque<usize>, // agents still to plan in the current cycle
+    reservations: ReservationTable,
+    wait_cost: f32,
+    width: u32,
+    height: u32,
+    time: u32,
+    cycle_end: u32,
+}
+
+impl CooperativeAStar {
+    /// Agents are start, goal pairs, all with the same window
+    pub fn new(
+        agents: &[(Point, Point)],
+        window: u32,
+        wait_cost: f32,
+        width: u32,
+        height: u32,
+        min_weight: f32,
+    ) -> Self {
+        let agents: Vec<CooperativeAgent> = agents
+            .iter()
+            .map(|(start, goal)| CooperativeAgent {
+                goal_index: goal.to_1d_index(width),
+                position: start.to_1d_index(width),
+                window: window.max(1),
+                plan: Vec::new(),
+                heuristic: ReverseResumableAStar::new(
+                    goal.clone(),
+                    start.clone(),
+                    width,
+                    height,
+                    min_weight,
+                ),
+            })
+            .collect();
+
+        let mut cooperative = CooperativeAStar {
+            order: (0..agents.len()).collect(),
+            agents,
+            queue: VecDeque::new(),
+            reservations: ReservationTable::new(),
+            wait_cost,
+            width,
+            height,
+            time: 0,
+            cycle_end: 0,
+        };
+
+        cooperative.start_cycle();
+        cooperative
+    }
+
+    /// Change how far ahead an agent plans, used from the next cycle on
+    pub fn set_window(&mut self, agent: usize, window: u32) {
+        self.agents[agent].window = window.max(1);
+    }
+
+    pub fn time(&self) -> u32 {
+        self.time
+    }
+
+    pub fn agent_count(&self) -> usize {
+        self.agents.len()
+    }
+
+    /// Current cell of every agent
+    pub fn positions(&self) -> Vec<u32> {
+        self.agents.iter().map(|agent| agent.position).collect()
+    }
+
+    /// Cells the agent is going to visit during the rest of the current plan
+    pub fn planned_path(&self, agent: usize) -> Vec<u32> {
+        self.agents[agent]
+            .plan
+            .iter()
+            .filter(|cell| cell.time >= self.time)
+            .map(|cell| cell.index)
+            .collect()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.agents
+            .iter()
+            .all(|agent| agent.position == agent.goal_index)
+    }
+
+    /// Plan at most max_plans agents, and once every agent has a plan for the cycle, move all of them one time step
+    /// Returns true when every agent is at its goal
+    pub fn tick(&mut self, max_plans: u32, weights: &[f32]) -> bool {
+        if self.is_finished() {
+            return true;
+        }
+
+        for _ in 0..max_plans {
+            match self.queue.pop_front() {
+                Some(agent) => self.plan(agent, weights),
+                None => break,
+            }
+        }
+
+        if !self.queue.is_empty() {
+            return false;
+        }
+
+        self.time += 1;
+
+        for agent in self.agents.iter_mut() {
+            if let Some(cell) = agent.plan.iter().find(|cell| cell.time == self.time) {
+                agent.position = cell.index;
+            }
+        }
+
+        if self.time >= self.cycle_end {
+            self.start_cycle();
+        }
+
+        self.is_finished()
+    }
+
+    /// Run until every agent has arrived or the time runs out, returns whether they all arrived
+    pub fn run(&mut self, max_time: u32, weights: &[f32]) -> bool {
+        while self.time < max_time {
+            if self.tick(self.agents.len() as u32, weights) {
+                return true;
+            }
+        }
+
+        self.is_finished()
+    }
+
+    fn start_cycle(&mut self) {
+        let shortest_window = self.agents.iter().map(|a| a.window).min().unwrap_or(1);
+
+        self.cycle_end = self.time + (shortest_window / 2).max(1);
+
+        if !self.order.is_empty() {
+            self.order.rotate_left(1);
+        }
+
+        self.queue = self.order.iter().copied().collect();
+        self.reserve_plans();
+    }
+
+    /// Reserve the plans made this cycle, agents still in the queue hold their current cell for the next step
+    fn reserve_plans(&mut self) {
+        let queued: HashSet<usize> = self.queue.iter().copied().collect();
+        self.reservations.clear();
+
+        for (agent_id, agent) in self.agents.iter().enumerate() {
+            if queued.contains(&agent_id) {
+                self.reservations
+                    .reserve_cell(agent.position, self.time + 1);
+            } else {
+                self.reservations.reserve_path(&agent.plan, false);
+            }
+        }
+    }
+
+    fn plan(&mut self, agent_id: usize, weights: &[f32]) {
+        let width = self.width;
+        let time = self.time;
+        let agent = &mut self.agents[agent_id];
+        let position = agent.position;
+
+        self.reservations.release_cell(position, time + 1);
+
+        let window_end = time + agent.window;
+        let goal_index = agent.goal_index;
+        let reservations = &self.reservations;
+        let heuristic = &mut agent.heuristic;
+
+        // the window ends anywhere, or at the goal if the agent can stay there until the end of the window
+        let result = find_timed_path_until(
+            Point::from_1d_index(width, agent.position),
+            time,
+            self.wait_cost,
+            window_end,
+            width,
+            self.height,
+            weights,
+            reservations,
+            |point| {
+                heuristic
+                    .distance(point.to_1d_index(width), weights)
+                    .unwrap_or(f32::INFINITY)
+            },
+            |cell| {
+                cell.time == window_end
+                    || (cell.index == goal_index
+                        && (cell.time..=window_end)
+                            .all(|t| !reservations.is_cell_reserved(goal_index, t)))
+            },
+        );
+
+        let boxed_in = result.is_none();
+
+        // boxed in, wait where we are
+        let mut plan = match result {
+            Some(result) => result.path,
+            None => vec![TimedCell {
+                index: position,
+                time,
+            }],
+        };
+
+        let last = *plan.last().unwrap();
+        plan.extend(((last.time + 1)..=window_end).map(|time| TimedCell {
+            index: last.index,
+            time,
+        }));
+
+        agent.plan = plan;
+
+        // anyone planning to pass through has to plan again around the waiting agent
+        // they are never boxed in at their own cell by a waiting agent, so this ends
+        if boxed_in {
+            let conflicting: Vec<usize> = (0..self.agents.len())
+                .filter(|&other| other != agent_id && !self.queue.contains(&other))
+                .filter(|&other| {
+                    self.agents[other]
+                        .plan
+                        .iter()
+                        .any(|cell| cell.index == position && cell.time > time)
+                })
+                .collect();
+
+            if !conflicting.is_empty() {
+                for other in conflicting {
+                    self.queue.push_front(other);
+                }
+
+                self.reserve_plans();
+                return;
+            }
+        }
+
+        self.reservations
+            .reserve_path(&self.agents[agent_id].plan, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::astar::find_path;
+
+    use super::*;
+
+    /// Checks that no two agents share a cell or swap places at any step
+    fn run_without_collisions(cooperative: &mut CooperativeAStar, weights: &[f32]) -> bool {
+        let mut previous = cooperative.positions();
+
+        for _ in 0..200 {
+            let finished = cooperative.tick(cooperative.agent_count() as u32, weights);
+            let positions = cooperative.positions();
+
+            let unique: HashSet<&u32> = positions.iter().collect();
+            assert_eq!(positions.len(), unique.len());
+
+            for a in 0..positions.len() {
+                for b in (a + 1)..positions.len() {
+                    assert!(!(positions[a] == previous[b] && positions[b] == previous[a]));
+                }
+            }
+
+            if finished {
+                return true;
+            }
+
+            previous = positions;
+        }
+
+        false
+    }
+
+    #[test]
+    fn test_reverse_resumable_distance() {
+        let mut weights: Vec<f32> = vec![1.0; 100];
+        for y in 0..8 {
+            weights[Point::new(5, y).to_1d_index(10) as usize] = -1.0;
+        }
+
+        let mut search =
+            ReverseResumableAStar::new(Point::new(9, 0), Point::new(0, 0), 10, 10, 1.0);
+
+        for from in [Point::new(0, 0), Point::new(3, 7), Point::new(8, 8)] {
+            let expected = find_path(from.clone(), Point::new(9, 0), 10, 10, 1, 1.0, &weights)
+                .unwrap()
+                .total_distance;
+            let distance = search.distance(from.to_1d_index(10), &weights).unwrap();
+
+            assert!((expected - distance).abs() < 0.0001);
+        }
+
+        assert_eq!(
+            None,
+            search.distance(Point::new(5, 0).to_1d_index(10), &weights)
+        );
+    }
+
+    #[test]
+    fn test_swap_through_pocket() {
+        #[rustfmt::skip]
+        let weights: Vec<f32> = vec![
+            1.0, 1.0, 1.0, 1.0, 1.0,
+            -1.0, -1.0, 1.0, -1.0, -1.0,
+        ];
+
+        let mut cooperative = CooperativeAStar::new(
+            &[
+                (Point::new(0, 0), Point::new(4, 0)),
+                (Point::new(4, 0), Point::new(0, 0)),
+            ],
+            8,
+            0.5,
+            5,
+            2,
+            1.0,
+        );
+
+        assert!(run_without_collisions(&mut cooperative, &weights));
+    }
+
+    #[test]
+    fn test_many_agents_crossing() {
+        let weights: Vec<f32> = vec![1.0; 400];
+
+        // two rows of agents swapping sides
+        let agents: Vec<(Point, Point)> = (0..10)
+            .flat_map(|y| {
+                [
+                    (Point::new(0, y * 2), Point::new(19, y * 2)),
+                    (Point::new(19, y * 2 + 1), Point::new(0, y * 2 + 1)),
+                ]
+            })
+            .collect();
+
+        let mut cooperative = CooperativeAStar::new(&agents, 8, 0.5, 20, 20, 1.0);
+
+        assert!(run_without_collisions(&mut cooperative, &weights));
+        assert!(cooperative.time() >= 19);
+    }
+
+    #[test]
+    fn test_tick_budget() {
+        let weights: Vec<f32> = vec![1.0; 100];
+        let agents: Vec<(Point, Point)> = (0..4)
+            .map(|i| (Point::new(0, i * 2), Point::new(9, i * 2)))
+            .collect();
+
+        let mut cooperative = CooperativeAStar::new(&agents, 4, 0.5, 10, 10, 1.0);
+
+        // one plan per tick, time only moves once everyone has planned
+        for _ in 0..3 {
+            cooperative.tick(1, &weights);
+            assert_eq!(0, cooperative.time());
+        }
+
+        cooperative.tick(1, &weights);
+        assert_eq!(1, cooperative.time());
+
+        assert!(cooperative.run(100, &weights));
+    }
+
+    #[test]
+    fn test_boxed_in_agent_is_not_run_over() {
+        // one cell wide corridor, the agent in the middle has arrived and blocks the way
+        let weights: Vec<f32> = vec![1.0; 5];
+        let mut cooperative = CooperativeAStar::new(
+            &[
+                (Point::new(2, 0), Point::new(2, 0)),
+                (Point::new(0, 0), Point::new(4, 0)),
+            ],
+            8,
+            0.5,
+            5,
+            1,
+            1.0,
+        );
+
+        assert!(!run_without_collisions(&mut cooperative, &weights));
+        assert_eq!(vec![2, 1], cooperative.positions());
+    }
+}
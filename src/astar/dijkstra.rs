@@ -0,0 +1,173 @@
+use crate::hybridheap::HybridHeap;
+
+use super::{
+    astar_utils::{calculate_weight, get_neighbours},
+    point::Point,
+};
+
+/// Predecessor of sources and unreachable cells
+pub const NO_PREDECESSOR: u32 = u32::MAX;
+
+/// Distance from the nearest source for every cell on the map
+/// Unreachable cells and walls have infinite distance
+pub struct DistanceField {
+    pub width: u32,
+    pub height: u32,
+    pub distances: Vec<f32>,
+    pub predecessors: Vec<u32>, // index of the previous cell on the way from the nearest source, NO_PREDECESSOR for sources and unreachable cells
+}
+
+impl DistanceField {
+    pub fn distance(&self, point: &Point) -> f32 {
+        self.distances[point.to_1d_index(self.width) as usize]
+    }
+
+    pub fn is_reachable(&self, point: &Point) -> bool {
+        self.distance(point).is_finite()
+    }
+
+    pub fn predecessor(&self, point: &Point) -> Option<Point> {
+        match self.predecessors[point.to_1d_index(self.width) as usize] {
+            NO_PREDECESSOR => None,
+            index => Some(Point::from_1d_index(self.width, index)),
+        }
+    }
+
+    /// Largest finite distance, useful for normalizing when rendering
+    pub fn max_distance(&self) -> f32 {
+        self.distances
+            .iter()
+            .copied()
+            .filter(|d| d.is_finite())
+            .fold(0.0, f32::max)
+    }
+
+    /// Indexes of cells from the point back to the nearest source, both included
+    /// Empty if the point is unreachable
+    pub fn path_to_source(&self, point: &Point) -> Vec<u32> {
+        if !self.is_reachable(point) {
+            return Vec::new();
+        }
+
+        let mut path = vec![point.to_1d_index(self.width)];
+
+        while let Some(&index) = path.last() {
+            match self.predecessors[index as usize] {
+                NO_PREDECESSOR => break,
+                previous => path.push(previous),
+            }
+        }
+
+        path
+    }
+}
+
+/// Dijkstra from many sources over the whole map
+/// Since the weights are symmetric for passable cells, the distances are valid in both directions, ie to and from the nearest source
+pub fn distance_field(
+    sources: &[Point],
+    width: u32,
+    height: u32,
+    weights: &[f32],
+) -> DistanceField {
+    let mut distances = vec![f32::INFINITY; (width * height) as usize];
+    let mut predecessors = vec![NO_PREDECESSOR; (width * height) as usize];
+    let mut openset: HybridHeap<u32, f32> = HybridHeap::with_capacity(1000);
+
+    for source in sources {
+        let index = source.to_1d_index(width);
+
+        if weights[index as usize] < 0.0 || openset.contains_key(&index) {
+            continue;
+        }
+
+        distances[index as usize] = 0.0;
+        openset.push(index, 0.0);
+    }
+
+    while let Some(current_index) = openset.pop() {
+        let current_distance = distances[current_index as usize];
+        let current_point = Point::from_1d_index(width, current_index);
+
+        for neighbour_index in get_neighbours(&current_point, width, height) {
+            let neighbour_point = Point::from_1d_index(width, neighbour_index);
+            let weight = calculate_weight(&current_point, &neighbour_point, weights, width);
+
+            // wall...
+            if weight <= 0.0 {
+                continue;
+            }
+
+            let tentative_distance = current_distance + weight;
+
+            if tentative_distance >= distances[neighbour_index as usize] {
+                continue;
+            }
+
+            distances[neighbour_index as usize] = tentative_distance;
+            predecessors[neighbour_index as usize] = current_index;
+
+            if openset.contains_key(&neighbour_index) {
+                openset.change_value(neighbour_index, tentative_distance);
+            } else {
+                openset.push(neighbour_index, tentative_distance);
+            }
+        }
+    }
+
+    DistanceField {
+        width,
+        height,
+        distances,
+        predecessors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_field_single_source() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let field = distance_field(&[Point::new(0, 0)], 10, 10, &weights);
+
+        assert_eq!(0.0, field.distance(&Point::new(0, 0)));
+        assert_eq!(9.0, field.distance(&Point::new(9, 0)));
+        assert_eq!(12.727921, field.distance(&Point::new(9, 9)));
+        assert!(field.predecessor(&Point::new(0, 0)).is_none());
+        assert_eq!(10, field.path_to_source(&Point::new(9, 0)).len());
+    }
+
+    #[test]
+    fn test_distance_field_multi_source() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let field = distance_field(&[Point::new(0, 0), Point::new(9, 0)], 10, 10, &weights);
+
+        assert_eq!(4.0, field.distance(&Point::new(4, 0)));
+        assert_eq!(4.0, field.distance(&Point::new(5, 0)));
+        assert_eq!(
+            Some(9),
+            field.path_to_source(&Point::new(6, 0)).last().copied()
+        );
+    }
+
+    #[test]
+    fn test_distance_field_walls() {
+        // wall splitting the map in two
+        let mut weights: Vec<f32> = vec![1.0; 100];
+        for y in 0..10 {
+            weights[Point::new(5, y).to_1d_index(10) as usize] = -1.0;
+        }
+
+        let field = distance_field(&[Point::new(0, 0)], 10, 10, &weights);
+
+        assert!(field.is_reachable(&Point::new(4, 9)));
+        assert!(!field.is_reachable(&Point::new(5, 0)));
+        assert!(!field.is_reachable(&Point::new(9, 9)));
+        assert!(field.path_to_source(&Point::new(9, 9)).is_empty());
+        assert!((field.max_distance() - (5.0 + 4.0 * 2.0_f32.sqrt())).abs() < 0.0001);
+    }
+}
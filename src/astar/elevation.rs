@@ -0,0 +1,153 @@
+use super::point::Point;
+
+/// Height per cell on top of the terrain weights, for slope aware costs
+/// Climbing and descending both add to the edge cost, climbing usually more, and moves steeper than max_slope are impassable
+/// Both costs are kept non negative, so the regular distance heuristic stays admissible
+#[derive(Clone, Debug)]
+pub struct Elevation {
+    pub heights: Vec<f32>,
+    pub uphill_cost: f32,   // extra cost per unit of height climbed
+    pub downhill_cost: f32, // extra cost per unit of height descended
+    pub max_slope: f32,     // height difference per cell of distance
+}
+
+impl Elevation {
+    pub fn new(heights: Vec<f32>, uphill_cost: f32, downhill_cost: f32, max_slope: f32) -> Self {
+        Elevation {
+            heights,
+            uphill_cost: uphill_cost.max(0.0),
+            downhill_cost: downhill_cost.max(0.0),
+            max_slope,
+        }
+    }
+
+    /// Height difference between neighbouring cells divided by the distance between their centers, positive when climbing
+    #[inline(always)]
+    pub fn slope(&self, from: &Point, to: &Point, width: u32) -> f32 {
+        let rise = self.heights[to.to_1d_index(width) as usize]
+            - self.heights[from.to_1d_index(width) as usize];
+
+        if from.x != to.x && from.y != to.y {
+            rise / std::f32::consts::SQRT_2
+        } else {
+            rise
+        }
+    }
+
+    /// Extra cost of moving between neighbouring cells, None if the slope is too steep in either direction
+    #[inline(always)]
+    pub fn edge_cost(&self, from: &Point, to: &Point, width: u32) -> Option<f32> {
+        let slope = self.slope(from, to, width);
+
+        if slope.abs() > self.max_slope {
+            return None;
+        }
+
+        let rise = self.heights[to.to_1d_index(width) as usize]
+            - self.heights[from.to_1d_index(width) as usize];
+
+        if rise > 0.0 {
+            Some(rise * self.uphill_cost)
+        } else {
+            Some(-rise * self.downhill_cost)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::astar::{find_path, find_path_with_elevation};
+
+    use super::*;
+
+    #[test]
+    fn test_edge_cost_asymmetric() {
+        let elevation = Elevation::new(vec![0.0, 2.0, 10.0], 1.0, 0.25, 5.0);
+
+        assert_eq!(
+            Some(2.0),
+            elevation.edge_cost(&Point::new(0, 0), &Point::new(1, 0), 3)
+        );
+        assert_eq!(
+            Some(0.5),
+            elevation.edge_cost(&Point::new(1, 0), &Point::new(0, 0), 3)
+        );
+        assert_eq!(
+            None,
+            elevation.edge_cost(&Point::new(1, 0), &Point::new(2, 0), 3)
+        );
+        assert_eq!(
+            None,
+            elevation.edge_cost(&Point::new(2, 0), &Point::new(1, 0), 3)
+        );
+    }
+
+    #[test]
+    fn test_find_path_goes_around_hill() {
+        // hill in the middle of the map, tallest in the center
+        let heights: Vec<f32> = (0..100)
+            .map(|i| {
+                let point = Point::from_1d_index(10, i);
+                let distance = (point.x as f32 - 4.5).hypot(point.y as f32 - 4.5);
+                (4.0 - distance).max(0.0) * 3.0
+            })
+            .collect();
+        let elevation = Elevation::new(heights, 1.0, 0.5, 2.0);
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let flat = find_path(Point::new(0, 4), Point::new(9, 4), 10, 10, 1, 1.0, &weights).unwrap();
+        let hilly = find_path_with_elevation(
+            Point::new(0, 4),
+            Point::new(9, 4),
+            10,
+            10,
+            1,
+            1.0,
+            &weights,
+            &elevation,
+        )
+        .unwrap();
+
+        assert_eq!(9.0, flat.total_distance);
+        assert!(hilly.total_distance > flat.total_distance);
+
+        // the top of the hill is too steep to reach
+        assert!(!hilly
+            .path_indexes
+            .contains(&Point::new(4, 4).to_1d_index(10)));
+    }
+
+    #[test]
+    fn test_uphill_costs_more_than_downhill() {
+        // slope climbing towards the right
+        let heights: Vec<f32> = (0..10).map(|x| x as f32).collect();
+        let elevation = Elevation::new(heights, 2.0, 0.5, 1.5);
+        let weights: Vec<f32> = vec![1.0; 10];
+
+        let up = find_path_with_elevation(
+            Point::new(0, 0),
+            Point::new(9, 0),
+            10,
+            1,
+            1,
+            1.0,
+            &weights,
+            &elevation,
+        )
+        .unwrap();
+        let down = find_path_with_elevation(
+            Point::new(9, 0),
+            Point::new(0, 0),
+            10,
+            1,
+            1,
+            1.0,
+            &weights,
+            &elevation,
+        )
+        .unwrap();
+
+        assert_eq!(9.0 + 9.0 * 2.0, up.total_distance);
+        assert_eq!(9.0 + 9.0 * 0.5, down.total_distance);
+    }
+}
@@ -0,0 +1,106 @@
+use super::{
+    dijkstra::{distance_field, DistanceField, NO_PREDECESSOR},
+    point::Point,
+};
+
+/// Direction to the next cell towards a shared target for every cell on the map
+/// Built on a reverse dijkstra from the target, so any number of units can follow it without searching themselves
+pub struct FlowField {
+    pub width: u32,
+    pub height: u32,
+    pub target_index: u32,
+    pub directions: Vec<i8>, // flat dx, dy pairs per cell, 0, 0 for the target, walls and unreachable cells
+    field: DistanceField,
+}
+
+impl FlowField {
+    pub fn new(target: Point, width: u32, height: u32, weights: &[f32]) -> Self {
+        // weights are symmetric for passable cells, so distances from the target are also distances to the target
+        let field = distance_field(std::slice::from_ref(&target), width, height, weights);
+        let mut directions = vec![0; (width * height * 2) as usize];
+
+        for (index, &next_index) in field.predecessors.iter().enumerate() {
+            if next_index == NO_PREDECESSOR {
+                continue;
+            }
+
+            let point = Point::from_1d_index(width, index as u32);
+            let next = Point::from_1d_index(width, next_index);
+
+            directions[index * 2] = (next.x as i32 - point.x as i32) as i8;
+            directions[index * 2 + 1] = (next.y as i32 - point.y as i32) as i8;
+        }
+
+        FlowField {
+            width,
+            height,
+            target_index: target.to_1d_index(width),
+            directions,
+            field,
+        }
+    }
+
+    /// Direction towards the next cell, None for the target itself and cells which cannot reach it
+    pub fn direction(&self, point: &Point) -> Option<(i8, i8)> {
+        let index = point.to_1d_index(self.width) as usize;
+
+        match (self.directions[index * 2], self.directions[index * 2 + 1]) {
+            (0, 0) => None,
+            direction => Some(direction),
+        }
+    }
+
+    /// Next cell towards the target, None for the target itself and cells which cannot reach it
+    pub fn next_cell(&self, point: &Point) -> Option<Point> {
+        self.field.predecessor(point)
+    }
+
+    /// Remaining distance to the target, infinite if unreachable
+    pub fn distance(&self, point: &Point) -> f32 {
+        self.field.distance(point)
+    }
+
+    pub fn distance_field(&self) -> &DistanceField {
+        &self.field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_field_directions() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let flow_field = FlowField::new(Point::new(5, 5), 10, 10, &weights);
+
+        assert_eq!(None, flow_field.direction(&Point::new(5, 5)));
+        assert_eq!(Some((1, 0)), flow_field.direction(&Point::new(0, 5)));
+        assert_eq!(Some((-1, -1)), flow_field.direction(&Point::new(9, 9)));
+        assert_eq!(Some((0, 1)), flow_field.direction(&Point::new(5, 0)));
+    }
+
+    #[test]
+    fn test_flow_field_follow_to_target() {
+        // wall with a gap at the bottom
+        let mut weights: Vec<f32> = vec![1.0; 100];
+        for y in 0..9 {
+            weights[Point::new(5, y).to_1d_index(10) as usize] = -1.0;
+        }
+
+        let flow_field = FlowField::new(Point::new(9, 0), 10, 10, &weights);
+
+        let mut current = Point::new(0, 0);
+        let mut steps = 0;
+        while let Some(next) = flow_field.next_cell(&current) {
+            assert!(weights[next.to_1d_index(10) as usize] > 0.0);
+            current = next;
+            steps += 1;
+        }
+
+        assert_eq!(9, current.to_1d_index(10));
+        assert!(steps >= 9 + 9);
+        assert_eq!(None, flow_field.next_cell(&Point::new(5, 0)));
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::{
+    astar::VisitedPoint,
+    astar_utils::{
+        calculate_heuristical_distance, calculate_weight, get_neighbours, reconstruct_ordered_path,
+    },
+    point::Point,
+};
+
+pub struct FringePathResult {
+    pub path: Vec<u32>, // from start to end, both included
+    pub total_distance: f32,
+    pub expanded_nodes: u32,
+    pub iterations: u32,
+    pub max_fringe_size: u32, // most cells waiting in the now and later lists at the same time
+}
+
+/// Fringe search
+/// Like IDA* it searches with a growing f score threshold, but cells over the threshold are kept for the next iteration instead of starting over, and the g scores are cached so cells are not expanded again unless a cheaper way is found
+/// There is no heap, the fringe is two plain lists, which takes less memory than find_path. The cache still grows with the explored area
+/// With varying weights the threshold grows in tiny steps and the later list is walked through again every time, so it can be a lot slower than find_path
+pub fn find_path_fringe(
+    from: Point,
+    to: Point,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+) -> Option<FringePathResult> {
+    let from_index = from.to_1d_index(width);
+    let to_index = to.to_1d_index(width);
+
+    if weights[from_index as usize] < 0.0 || weights[to_index as usize] < 0.0 {
+        return None;
+    }
+
+    let heuristic = |index: u32| {
+        calculate_heuristical_distance(
+            &Point::from_1d_index(width, index),
+            &to,
+            multiplier,
+            min_weight,
+        )
+    };
+
+    let mut cache: HashMap<u32, VisitedPoint<f32, u32>> = HashMap::with_capacity(1000);
+    cache.insert(
+        from_index,
+        VisitedPoint {
+            score: 0.0,
+            came_from_key: from_index,
+        },
+    );
+
+    // cells are listed with the g score they had when listed, entries with an outdated score are skipped
+    let mut now: VecDeque<(u32, f32)> = VecDeque::from([(from_index, 0.0)]);
+    let mut later: Vec<(u32, f32)> = Vec::new();
+
+    let mut threshold = heuristic(from_index);
+    let mut expanded_nodes = 0;
+    let mut iterations = 0;
+    let mut max_fringe_size = 1;
+
+    while !now.is_empty() {
+        iterations += 1;
+
+        // smallest f score over the threshold, the threshold for the next iteration
+        let mut next_threshold = f32::INFINITY;
+
+        while let Some((current_index, listed_score)) = now.pop_front() {
+            let current_score = cache[&current_index].score;
+
+            if current_score < listed_score {
+                continue;
+            }
+
+            let f_score = current_score + heuristic(current_index);
+
+            if f_score > threshold {
+                next_threshold = next_threshold.min(f_score);
+                later.push((current_index, current_score));
+                continue;
+            }
+
+            if current_index == to_index {
+                return Some(FringePathResult {
+                    path: reconstruct_ordered_path(&cache, to_index),
+                    total_distance: current_score,
+                    expanded_nodes,
+                    iterations,
+                    max_fringe_size,
+                });
+            }
+
+            expanded_nodes += 1;
+
+            let current_point = Point::from_1d_index(width, current_index);
+
+            for neighbour_index in get_neighbours(&current_point, width, height) {
+                let neighbour_point = Point::from_1d_index(width, neighbour_index);
+                let weight = calculate_weight(&current_point, &neighbour_point, weights, width);
+
+                // wall...
+                if weight <= 0.0 {
+                    continue;
+                }
+
+                let tentative_g_score = current_score + weight;
+
+                match cache.get(&neighbour_index) {
+                    Some(p) if p.score <= tentative_g_score => continue,
+                    _ => cache.insert(
+                        neighbour_index,
+                        VisitedPoint {
+                            score: tentative_g_score,
+                            came_from_key: current_index,
+                        },
+                    ),
+                };
+
+                // children are looked at right after their parent, depth first within the threshold
+                now.push_front((neighbour_index, tentative_g_score));
+            }
+
+            max_fringe_size = max_fringe_size.max((now.len() + later.len()) as u32);
+        }
+
+        threshold = next_threshold;
+        now.extend(later.drain(..));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::astar::find_path;
+
+    use super::*;
+
+    #[test]
+    fn test_same_cost_as_find_path() {
+        let weights: Vec<f32> = (0..400)
+            .map(|i| {
+                let point = Point::from_1d_index(20, i);
+                if point.x == 10 && point.y > 3 {
+                    -1.0
+                } else {
+                    1.0 + ((point.x * 3 + point.y * 7) % 5) as f32
+                }
+            })
+            .collect();
+
+        for to in [Point::new(19, 19), Point::new(15, 2), Point::new(0, 19)] {
+            let expected = find_path(Point::new(0, 0), to.clone(), 20, 20, 1, 1.0, &weights)
+                .unwrap()
+                .total_distance;
+
+            let result =
+                find_path_fringe(Point::new(0, 0), to.clone(), 20, 20, 1, 1.0, &weights).unwrap();
+
+            assert!((expected - result.total_distance).abs() < 0.001);
+            assert_eq!(Some(&0), result.path.first());
+            assert_eq!(Some(&to.to_1d_index(20)), result.path.last());
+        }
+    }
+
+    #[test]
+    fn test_unreachable() {
+        #[rustfmt::skip]
+        let weights: Vec<f32> = vec![
+            1.0, -1.0, 1.0,
+            1.0, -1.0, 1.0,
+            1.0, -1.0, 1.0,
+        ];
+
+        assert!(
+            find_path_fringe(Point::new(0, 0), Point::new(2, 2), 3, 3, 1, 1.0, &weights).is_none()
+        );
+    }
+}
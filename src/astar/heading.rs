@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use super::{
+    astar::{FindPath, VisitedPoint},
+    point::Point,
+};
+
+/// Headings in 45 degree steps clockwise starting from east, y grows downwards
+pub const HEADINGS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// Search state of a heading aware search, the cell and the heading the cell was entered with
+/// Heading is an index into HEADINGS, None for the start cell unless an initial heading is given
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HeadingState {
+    pub index: u32,
+    pub heading: Option<u8>,
+}
+
+pub struct HeadingPathResult {
+    pub from_index: u32,
+    pub to_index: u32,
+    pub total_distance: f32,
+    pub path: Vec<HeadingState>, // states in order from start to end, both included
+    pub visited_states: HashMap<HeadingState, VisitedPoint<f32, HeadingState>>,
+}
+
+impl HeadingPathResult {
+    /// Cells of the path in order
+    pub fn path_indexes(&self) -> Vec<u32> {
+        self.path.iter().map(|state| state.index).collect()
+    }
+
+    /// Number of 45 degree turns along the path
+    pub fn turn_count(&self) -> u32 {
+        self.path
+            .windows(2)
+            .map(|w| turn_steps(w[0].heading, w[1].heading) as u32)
+            .sum()
+    }
+}
+
+/// Heading when moving between two neighbouring cells
+#[inline(always)]
+pub fn heading_between(from: &Point, to: &Point) -> u8 {
+    let direction = (
+        (to.x as i32 - from.x as i32).signum(),
+        (to.y as i32 - from.y as i32).signum(),
+    );
+
+    HEADINGS.iter().position(|h| *h == direction).unwrap() as u8
+}
+
+/// Number of 45 degree steps between two headings, 0 to 4. Changing from no heading is free
+#[inline(always)]
+pub fn turn_steps(from: Option<u8>, to: Option<u8>) -> u8 {
+    match (from, to) {
+        (Some(from), Some(to)) => {
+            let difference = (from as i32 - to as i32).rem_euclid(8) as u8;
+            difference.min(8 - difference)
+        }
+        _ => 0,
+    }
+}
+
+/// Find path where every 45 degree turn costs turn_penalty on top of the terrain weight, so straighter routes are preferred
+/// The search state is the cell and the heading, the target can be reached with any heading. See FindPath::with_turn_penalty
+#[allow(clippy::too_many_arguments)]
+pub fn find_path_with_turn_penalty(
+    from: Point,
+    to: Point,
+    initial_heading: Option<u8>,
+    turn_penalty: f32,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+) -> Option<HeadingPathResult> {
+    let mut path_finder = FindPath::new(from, to, width, height, multiplier, min_weight)
+        .with_turn_penalty(turn_penalty, initial_heading);
+    let total_distance = path_finder.run(weights)?;
+
+    let visited_states = path_finder
+        .visited_points()
+        .iter()
+        .map(|(key, visited)| {
+            (
+                path_finder.state(*key),
+                VisitedPoint {
+                    score: visited.score,
+                    came_from_key: path_finder.state(visited.came_from_key),
+                },
+            )
+        })
+        .collect();
+
+    Some(HeadingPathResult {
+        from_index: path_finder.from_index,
+        to_index: path_finder.to_index,
+        total_distance,
+        path: path_finder.ordered_states()?,
+        visited_states,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::{astar::find_path, portals::Portals};
+
+    use super::*;
+
+    #[test]
+    fn test_turn_steps() {
+        assert_eq!(0, turn_steps(Some(0), Some(0)));
+        assert_eq!(1, turn_steps(Some(0), Some(7)));
+        assert_eq!(2, turn_steps(Some(6), Some(0)));
+        assert_eq!(4, turn_steps(Some(1), Some(5)));
+        assert_eq!(0, turn_steps(None, Some(3)));
+    }
+
+    #[test]
+    fn test_heading_between() {
+        assert_eq!(0, heading_between(&Point::new(1, 1), &Point::new(2, 1)));
+        assert_eq!(3, heading_between(&Point::new(1, 1), &Point::new(0, 2)));
+        assert_eq!(6, heading_between(&Point::new(1, 1), &Point::new(1, 0)));
+    }
+
+    #[test]
+    fn test_no_penalty_matches_regular_search() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let regular =
+            find_path(Point::new(0, 0), Point::new(9, 3), 10, 10, 1, 1.0, &weights).unwrap();
+        let heading = find_path_with_turn_penalty(
+            Point::new(0, 0),
+            Point::new(9, 3),
+            None,
+            0.0,
+            10,
+            10,
+            1,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        assert_eq!(regular.total_distance, heading.total_distance);
+    }
+
+    #[test]
+    fn test_turn_penalty_prefers_straight_path() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let result = find_path_with_turn_penalty(
+            Point::new(0, 0),
+            Point::new(9, 3),
+            None,
+            2.0,
+            10,
+            10,
+            1,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        assert_eq!(1, result.turn_count());
+        assert_eq!(10, result.path.len());
+        assert!((result.total_distance - (6.0 + 3.0 * 2.0_f32.sqrt() + 2.0)).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_initial_heading() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        // facing west, turning around to go east costs four turns
+        let result = find_path_with_turn_penalty(
+            Point::new(5, 5),
+            Point::new(9, 5),
+            Some(4),
+            1.0,
+            10,
+            10,
+            1,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        assert_eq!(4.0 + 4.0, result.total_distance);
+        assert_eq!(Some(4), result.path[0].heading);
+    }
+
+    #[test]
+    fn test_turn_penalty_ticks_through_portals() {
+        #[rustfmt::skip]
+        let weights: Vec<f32> = vec![
+            1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0,
+            1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0,
+            1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0,
+        ];
+        let mut portals = Portals::new(10, 3);
+        portals.add(&Point::new(4, 1), &Point::new(6, 1), 1.0, false);
+
+        let mut path_finder = FindPath::new(Point::new(0, 1), Point::new(9, 1), 10, 3, 1, 1.0)
+            .with_portals(portals)
+            .with_turn_penalty(2.0, Some(0));
+
+        let mut ticks = 0;
+        let total_distance = loop {
+            ticks += 1;
+            if let Some(total_distance) = path_finder.tick(1, &weights) {
+                break total_distance;
+            }
+        };
+
+        assert!(ticks > 1);
+        assert_eq!(8.0, total_distance);
+        assert_eq!(
+            Some(vec![10, 11, 12, 13, 14, 16, 17, 18, 19]),
+            path_finder.ordered_path()
+        );
+        assert_eq!(
+            HeadingState {
+                index: 16,
+                heading: None
+            },
+            path_finder.ordered_states().unwrap()[5]
+        );
+    }
+}
@@ -0,0 +1,520 @@
+use std::{
+    collections::HashMap,
+    f32::consts::{PI, TAU},
+};
+
+use crate::hybridheap::HybridHeap;
+
+/// Continuous position and heading of a vehicle
+/// Coordinates are in cells, cell x, y covers x..x+1, y..y+1. Theta is in radians, 0 facing east and growing clockwise since y grows downwards
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pose {
+    pub x: f32,
+    pub y: f32,
+    pub theta: f32,
+}
+
+impl Pose {
+    pub fn new(x: f32, y: f32, theta: f32) -> Self {
+        Pose {
+            x,
+            y,
+            theta: normalize_angle(theta),
+        }
+    }
+
+    /// Pose after driving along an arc with the curvature, negative distance drives in reverse
+    #[inline(always)]
+    pub fn drive(&self, curvature: f32, distance: f32) -> Pose {
+        if curvature.abs() < f32::EPSILON {
+            return Pose::new(
+                self.x + distance * self.theta.cos(),
+                self.y + distance * self.theta.sin(),
+                self.theta,
+            );
+        }
+
+        let theta = self.theta + curvature * distance;
+
+        Pose::new(
+            self.x + (theta.sin() - self.theta.sin()) / curvature,
+            self.y + (self.theta.cos() - theta.cos()) / curvature,
+            theta,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct VehicleConfig {
+    pub min_turning_radius: f32, // in cells
+    pub step_length: f32, // arc length of each motion primitive, should be long enough to leave the current cell
+    pub steering_samples: u32, // number of curvatures between full left and full right, odd to include straight
+    pub heading_bins: u32,     // number of discrete headings for the closed set
+    pub allow_reverse: bool,
+    pub reverse_penalty: f32,  // cost multiplier when reversing
+    pub steering_penalty: f32, // extra cost per step when not driving straight
+    pub goal_distance_tolerance: f32,
+    pub goal_heading_tolerance: f32, // radians
+    pub max_expanded_nodes: u32, // give up after expanding this many states, the state space is large when the goal cant be reached
+}
+
+impl Default for VehicleConfig {
+    fn default() -> Self {
+        VehicleConfig {
+            min_turning_radius: 4.0,
+            step_length: 1.5,
+            steering_samples: 5,
+            heading_bins: 72,
+            allow_reverse: false,
+            reverse_penalty: 2.0,
+            steering_penalty: 0.05,
+            goal_distance_tolerance: 1.0,
+            goal_heading_tolerance: PI / 12.0,
+            max_expanded_nodes: 200_000,
+        }
+    }
+}
+
+impl VehicleConfig {
+    /// Zero turning radius, step length, steering samples or heading bins leave nothing sensible to search with
+    pub fn is_valid(&self) -> bool {
+        self.min_turning_radius.is_finite()
+            && self.min_turning_radius > 0.0
+            && self.step_length.is_finite()
+            && self.step_length > 0.0
+            && self.steering_samples > 0
+            && self.heading_bins > 0
+    }
+}
+
+pub struct VehiclePath {
+    pub poses: Vec<Pose>, // from start to goal, including intermediate poses along each arc
+    pub total_cost: f32,
+    pub expanded_count: u32,
+}
+
+/// Pose reached by driving an arc from the parent node, needed for rebuilding the smooth path
+/// Nodes are never overwritten, so the arc from the stored parent pose is the one that was collision checked
+#[derive(Clone, Copy)]
+struct HybridNode {
+    pose: Pose,
+    score: f32,
+    parent: usize, // index in the node list, the start is its own parent
+    curvature: f32,
+    distance: f32,
+}
+
+/// Number of points each arc is sampled at for collision checks and the returned path
+const ARC_SAMPLES: u32 = 6;
+
+/// Hybrid A* for car like vehicles
+/// States are continuous poses, but only the best pose per cell and heading bin is kept. Neighbours are arcs with a limited curvature
+/// Arcs are checked against the walls of the weight map and cost their length times the weights of the cells they pass
+/// The heuristic is the euclidean distance with the minimum weight, which ignores the turning radius and obstacles
+/// Returns None for an invalid config, headings that are not finite or when the goal isnt reached within the expansion budget
+pub fn find_vehicle_path(
+    start: Pose,
+    goal: Pose,
+    config: &VehicleConfig,
+    width: u32,
+    height: u32,
+    min_weight: f32,
+    weights: &[f32],
+) -> Option<VehiclePath> {
+    let weight_at = |x: f32, y: f32| -> Option<f32> {
+        if x < 0.0 || y < 0.0 || x >= width as f32 || y >= height as f32 {
+            return None;
+        }
+
+        let weight = weights[(y as u32 * width + x as u32) as usize];
+        (weight >= 0.0).then_some(weight)
+    };
+
+    let state_key = |pose: &Pose| -> u32 {
+        let cell = pose.y as u32 * width + pose.x as u32;
+        let bin = ((pose.theta / TAU) * config.heading_bins as f32) as u32 % config.heading_bins;
+        cell * config.heading_bins + bin
+    };
+
+    let heuristic = |pose: &Pose| -> f32 { (pose.x - goal.x).hypot(pose.y - goal.y) * min_weight };
+
+    if !config.is_valid() || !start.theta.is_finite() || !goal.theta.is_finite() {
+        return None;
+    }
+
+    weight_at(start.x, start.y)?;
+    weight_at(goal.x, goal.y)?;
+
+    let curvatures: Vec<f32> = (0..config.steering_samples)
+        .map(|i| {
+            let max_curvature = 1.0 / config.min_turning_radius;
+
+            match config.steering_samples {
+                1 => 0.0,
+                samples => -max_curvature + 2.0 * max_curvature * i as f32 / (samples - 1) as f32,
+            }
+        })
+        .collect();
+
+    let directions: &[f32] = if config.allow_reverse {
+        &[1.0, -1.0]
+    } else {
+        &[1.0]
+    };
+
+    let mut openset: HybridHeap<u32, f32> = HybridHeap::with_capacity(1000);
+    // best node per cell and heading bin, nodes replaced by cheaper ones stay in the list for their children
+    let mut best_nodes: HashMap<u32, usize> = HashMap::with_capacity(1000);
+    let mut nodes: Vec<HybridNode> = Vec::with_capacity(1000);
+    let mut expanded_count = 0;
+
+    let start_key = state_key(&start);
+
+    nodes.push(HybridNode {
+        pose: start,
+        score: 0.0,
+        parent: 0,
+        curvature: 0.0,
+        distance: 0.0,
+    });
+    best_nodes.insert(start_key, 0);
+    openset.push(start_key, heuristic(&start));
+
+    while let Some(current_key) = openset.pop() {
+        let current_node = best_nodes[&current_key];
+        let current_pose = nodes[current_node].pose;
+        let current_score = nodes[current_node].score;
+
+        if (current_pose.x - goal.x).hypot(current_pose.y - goal.y)
+            <= config.goal_distance_tolerance
+            && angle_difference(current_pose.theta, goal.theta) <= config.goal_heading_tolerance
+        {
+            return Some(VehiclePath {
+                poses: reconstruct_poses(&nodes, current_node),
+                total_cost: current_score,
+                expanded_count,
+            });
+        }
+
+        if expanded_count >= config.max_expanded_nodes {
+            return None;
+        }
+
+        expanded_count += 1;
+
+        for &direction in directions {
+            for &curvature in &curvatures {
+                let distance = config.step_length * direction;
+
+                // sample the arc, any sample in a wall or outside the map discards it
+                let mut cost = 0.0;
+                let mut collision = false;
+
+                for sample in 1..=ARC_SAMPLES {
+                    let pose = current_pose
+                        .drive(curvature, distance * sample as f32 / ARC_SAMPLES as f32);
+
+                    match weight_at(pose.x, pose.y) {
+                        Some(weight) => cost += weight * config.step_length / ARC_SAMPLES as f32,
+                        None => {
+                            collision = true;
+                            break;
+                        }
+                    }
+                }
+
+                if collision {
+                    continue;
+                }
+
+                if direction < 0.0 {
+                    cost *= config.reverse_penalty;
+                }
+
+                if curvature != 0.0 {
+                    cost += config.steering_penalty;
+                }
+
+                let next_pose = current_pose.drive(curvature, distance);
+                let next_key = state_key(&next_pose);
+                let tentative_g_score = current_score + cost;
+
+                if let Some(&node) = best_nodes.get(&next_key) {
+                    if nodes[node].score <= tentative_g_score {
+                        continue;
+                    }
+                }
+
+                best_nodes.insert(next_key, nodes.len());
+                nodes.push(HybridNode {
+                    pose: next_pose,
+                    score: tentative_g_score,
+                    parent: current_node,
+                    curvature,
+                    distance,
+                });
+
+                let tentative_f_score = tentative_g_score + heuristic(&next_pose);
+
+                match openset.get_value(next_key) {
+                    Some(v) if v > tentative_f_score => {
+                        openset.change_value(next_key, tentative_f_score)
+                    }
+                    Some(_) => (),
+                    None => openset.push(next_key, tentative_f_score),
+                };
+            }
+        }
+    }
+
+    None
+}
+
+/// Rebuild the poses from start to the node, sampling each arc from the stored pose of its parent like the collision checks did
+fn reconstruct_poses(nodes: &[HybridNode], to_node: usize) -> Vec<Pose> {
+    let mut chain = vec![to_node];
+    while let Some(&node) = chain.last() {
+        if node == 0 {
+            break;
+        }
+        chain.push(nodes[node].parent);
+    }
+    chain.reverse();
+
+    let mut poses = vec![nodes[0].pose];
+
+    for &node in &chain[1..] {
+        let node = nodes[node];
+        let from = nodes[node.parent].pose;
+
+        // arc samples as checked, the last one is the stored pose of the node
+        for sample in 1..ARC_SAMPLES {
+            poses.push(from.drive(
+                node.curvature,
+                node.distance * sample as f32 / ARC_SAMPLES as f32,
+            ));
+        }
+        poses.push(node.pose);
+    }
+
+    poses
+}
+
+/// Angle in 0..2pi
+#[inline(always)]
+pub fn normalize_angle(angle: f32) -> f32 {
+    angle.rem_euclid(TAU)
+}
+
+/// Absolute difference between two angles, 0..pi
+#[inline(always)]
+pub fn angle_difference(a: f32, b: f32) -> f32 {
+    let difference = normalize_angle(a - b);
+    difference.min(TAU - difference)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drive() {
+        let pose = Pose::new(5.0, 5.0, 0.0);
+
+        let straight = pose.drive(0.0, 2.0);
+        assert!((straight.x - 7.0).abs() < 0.0001);
+        assert!((straight.y - 5.0).abs() < 0.0001);
+
+        // quarter circle with radius 2, turning clockwise ie towards positive y
+        let arc = pose.drive(0.5, PI);
+        assert!((arc.x - 7.0).abs() < 0.0001);
+        assert!((arc.y - 7.0).abs() < 0.0001);
+        assert!((arc.theta - PI / 2.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_angle_difference() {
+        assert!((angle_difference(0.1, TAU - 0.1) - 0.2).abs() < 0.0001);
+        assert!((angle_difference(PI, 0.0) - PI).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_find_vehicle_path_straight() {
+        let weights: Vec<f32> = vec![1.0; 400];
+        let config = VehicleConfig::default();
+
+        let path = find_vehicle_path(
+            Pose::new(2.5, 10.5, 0.0),
+            Pose::new(17.5, 10.5, 0.0),
+            &config,
+            20,
+            20,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        let last = path.poses.last().unwrap();
+        assert!((last.x - 17.5).abs() <= config.goal_distance_tolerance);
+        assert!(path.total_cost >= 14.0);
+        assert!(path.total_cost < 16.0);
+    }
+
+    #[test]
+    fn test_find_vehicle_path_respects_turning_radius() {
+        let weights: Vec<f32> = vec![1.0; 900];
+        let config = VehicleConfig::default();
+
+        // turn around to face the opposite direction
+        let path = find_vehicle_path(
+            Pose::new(10.5, 15.5, 0.0),
+            Pose::new(10.5, 5.5, PI),
+            &config,
+            30,
+            30,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        let max_heading_change =
+            config.step_length / ARC_SAMPLES as f32 / config.min_turning_radius + 0.0001;
+
+        for step in path.poses.windows(2) {
+            assert!(angle_difference(step[0].theta, step[1].theta) <= max_heading_change);
+        }
+
+        let last = path.poses.last().unwrap();
+        assert!(angle_difference(last.theta, PI) <= config.goal_heading_tolerance);
+    }
+
+    #[test]
+    fn test_find_vehicle_path_walls() {
+        // wall with an opening too narrow for turning into
+        let mut weights: Vec<f32> = vec![1.0; 400];
+        for y in 0..20 {
+            weights[y * 20 + 10] = -1.0;
+        }
+
+        let path = find_vehicle_path(
+            Pose::new(2.5, 10.5, 0.0),
+            Pose::new(17.5, 10.5, 0.0),
+            &VehicleConfig::default(),
+            20,
+            20,
+            1.0,
+            &weights,
+        );
+
+        assert!(path.is_none());
+
+        // the goal cant be reached, so a small budget stops the search early
+        let budget = VehicleConfig {
+            max_expanded_nodes: 50,
+            ..Default::default()
+        };
+        assert!(find_vehicle_path(
+            Pose::new(2.5, 10.5, 0.0),
+            Pose::new(17.5, 10.5, 0.0),
+            &budget,
+            20,
+            20,
+            1.0,
+            &weights
+        )
+        .is_none());
+
+        weights[10 * 20 + 10] = 1.0;
+
+        let path = find_vehicle_path(
+            Pose::new(2.5, 10.5, 0.0),
+            Pose::new(17.5, 10.5, 0.0),
+            &VehicleConfig::default(),
+            20,
+            20,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        for pose in &path.poses {
+            assert!(weights[(pose.y as usize) * 20 + pose.x as usize] > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_find_vehicle_path_is_continuous() {
+        // obstacles make cheaper poses replace bins that already have children
+        let mut weights: Vec<f32> = vec![1.0; 900];
+        for y in 8..22 {
+            weights[y * 30 + 15] = -1.0;
+        }
+        for x in 5..12 {
+            weights[12 * 30 + x] = 3.0;
+        }
+        let config = VehicleConfig {
+            allow_reverse: true,
+            ..Default::default()
+        };
+
+        let path = find_vehicle_path(
+            Pose::new(3.5, 15.5, 0.0),
+            Pose::new(25.5, 15.5, 0.0),
+            &config,
+            30,
+            30,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        // consecutive samples are at most one sample of arc apart, so there are no jumps between arcs
+        let max_step = config.step_length / ARC_SAMPLES as f32 + 0.0001;
+        for step in path.poses.windows(2) {
+            assert!((step[0].x - step[1].x).hypot(step[0].y - step[1].y) <= max_step);
+        }
+        for pose in &path.poses {
+            assert!(weights[(pose.y as usize) * 30 + pose.x as usize] > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_find_vehicle_path_invalid_config() {
+        let weights: Vec<f32> = vec![1.0; 400];
+        let (start, goal) = (Pose::new(2.5, 10.5, 0.0), Pose::new(17.5, 10.5, 0.0));
+
+        for config in [
+            VehicleConfig {
+                min_turning_radius: 0.0,
+                ..Default::default()
+            },
+            VehicleConfig {
+                min_turning_radius: f32::NAN,
+                ..Default::default()
+            },
+            VehicleConfig {
+                heading_bins: 0,
+                ..Default::default()
+            },
+            VehicleConfig {
+                steering_samples: 0,
+                ..Default::default()
+            },
+        ] {
+            assert!(!config.is_valid());
+            assert!(find_vehicle_path(start, goal, &config, 20, 20, 1.0, &weights).is_none());
+        }
+
+        let goal = Pose::new(17.5, 10.5, f32::INFINITY);
+        assert!(find_vehicle_path(
+            start,
+            goal,
+            &VehicleConfig::default(),
+            20,
+            20,
+            1.0,
+            &weights
+        )
+        .is_none());
+    }
+}
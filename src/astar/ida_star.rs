@@ -0,0 +1,360 @@
+use std::collections::HashSet;
+
+use super::{
+    astar_utils::{calculate_heuristical_distance, calculate_weight, get_neighbours},
+    point::Point,
+};
+
+pub struct IdaPathResult {
+    pub path: Vec<u32>, // from start to end, both included
+    pub total_distance: f32,
+    pub expanded_nodes: u32, // over all iterations
+    pub iterations: u32,
+    pub max_depth: u32, // the memory used grows with this instead of the explored area
+}
+
+/// One cell on the current depth first path, with the neighbours still left to try
+struct Frame {
+    index: u32,
+    g_score: f32,
+    neighbours: Vec<u32>,
+    expanded: bool,
+}
+
+/// Direct mapped cache of the best g scores seen during an iteration, colliding cells simply overwrite each other
+struct TranspositionTable {
+    entries: Vec<(u32, f32)>,
+}
+
+impl TranspositionTable {
+    fn new(size: usize) -> Self {
+        TranspositionTable {
+            entries: vec![(u32::MAX, f32::INFINITY); size],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.fill((u32::MAX, f32::INFINITY));
+    }
+
+    /// False if the cell has been reached with at most this g score already
+    #[inline(always)]
+    fn improves(&self, index: u32, g_score: f32) -> bool {
+        match self.entries.get(self.slot(index)) {
+            Some(&(key, score)) if key == index => g_score < score,
+            _ => true,
+        }
+    }
+
+    #[inline(always)]
+    fn insert(&mut self, index: u32, g_score: f32) {
+        if !self.entries.is_empty() {
+            let slot = self.slot(index);
+            self.entries[slot] = (index, g_score);
+        }
+    }
+
+    #[inline(always)]
+    fn slot(&self, index: u32) -> usize {
+        index as usize % self.entries.len().max(1)
+    }
+}
+
+/// Iterative deepening A* (IDA*)
+/// Depth first searches with a growing f score threshold, only the current path is kept in memory, so memory grows with the path length instead of the explored area
+/// Cells are expanded over and over again in every iteration and via every path leading to them, which on 8 connected grids with their many equally long paths is hopeless without a transposition table
+/// The table remembers the best g score of table_size cells during an iteration, and cells reached again with no better score are skipped. 0 turns the table off
+/// Gives up with None after max_expanded_nodes
+#[allow(clippy::too_many_arguments)]
+pub fn find_path_ida(
+    from: Point,
+    to: Point,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+    table_size: usize,
+    max_expanded_nodes: u32,
+) -> Option<IdaPathResult> {
+    let from_index = from.to_1d_index(width);
+    let to_index = to.to_1d_index(width);
+
+    if weights[from_index as usize] < 0.0 || weights[to_index as usize] < 0.0 {
+        return None;
+    }
+
+    let heuristic = |index: u32| {
+        calculate_heuristical_distance(
+            &Point::from_1d_index(width, index),
+            &to,
+            multiplier,
+            min_weight,
+        )
+    };
+
+    let mut threshold = heuristic(from_index);
+    let mut expanded_nodes = 0;
+    let mut iterations = 0;
+    let mut max_depth = 0;
+
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut on_path: HashSet<u32> = HashSet::new();
+    let mut table = TranspositionTable::new(table_size);
+
+    loop {
+        iterations += 1;
+
+        // smallest f score over the threshold, the threshold for the next iteration
+        let mut next_threshold = f32::INFINITY;
+
+        stack.clear();
+        on_path.clear();
+        table.clear();
+        stack.push(Frame {
+            index: from_index,
+            g_score: 0.0,
+            neighbours: Vec::new(),
+            expanded: false,
+        });
+        on_path.insert(from_index);
+
+        while let Some(frame) = stack.last_mut() {
+            let current_index = frame.index;
+
+            if current_index == to_index {
+                return Some(IdaPathResult {
+                    total_distance: frame.g_score,
+                    path: stack.iter().map(|f| f.index).collect(),
+                    expanded_nodes,
+                    iterations,
+                    max_depth,
+                });
+            }
+
+            // first visit, collect the neighbours within the threshold, the most promising ones are tried first
+            if !frame.expanded {
+                frame.expanded = true;
+                expanded_nodes += 1;
+
+                if expanded_nodes >= max_expanded_nodes {
+                    return None;
+                }
+
+                let current_point = Point::from_1d_index(width, current_index);
+                let g_score = frame.g_score;
+
+                let mut neighbours: Vec<(u32, f32)> = Vec::with_capacity(8);
+
+                for neighbour_index in get_neighbours(&current_point, width, height) {
+                    if on_path.contains(&neighbour_index) {
+                        continue;
+                    }
+
+                    let neighbour_point = Point::from_1d_index(width, neighbour_index);
+                    let weight = calculate_weight(&current_point, &neighbour_point, weights, width);
+
+                    // wall...
+                    if weight <= 0.0 {
+                        continue;
+                    }
+
+                    let tentative_g_score = g_score + weight;
+
+                    if !table.improves(neighbour_index, tentative_g_score) {
+                        continue;
+                    }
+
+                    let f_score = tentative_g_score + heuristic(neighbour_index);
+
+                    if f_score > threshold {
+                        next_threshold = next_threshold.min(f_score);
+                        continue;
+                    }
+
+                    neighbours.push((neighbour_index, f_score));
+                }
+
+                // popped from the back, so the best one goes last
+                neighbours.sort_by(|a, b| b.1.total_cmp(&a.1));
+                frame.neighbours = neighbours.into_iter().map(|(index, _)| index).collect();
+            }
+
+            match frame.neighbours.pop() {
+                Some(neighbour_index) => {
+                    let current_point = Point::from_1d_index(width, current_index);
+                    let neighbour_point = Point::from_1d_index(width, neighbour_index);
+                    let g_score = frame.g_score
+                        + calculate_weight(&current_point, &neighbour_point, weights, width);
+
+                    // a sibling subtree may have got here cheaper in the meantime
+                    if !table.improves(neighbour_index, g_score) {
+                        continue;
+                    }
+
+                    table.insert(neighbour_index, g_score);
+                    on_path.insert(neighbour_index);
+                    stack.push(Frame {
+                        index: neighbour_index,
+                        g_score,
+                        neighbours: Vec::new(),
+                        expanded: false,
+                    });
+                    max_depth = max_depth.max(stack.len() as u32);
+                }
+                None => {
+                    // dead end, back up
+                    on_path.remove(&current_index);
+                    stack.pop();
+                }
+            }
+        }
+
+        if next_threshold.is_infinite() {
+            // nothing beyond the threshold either, unreachable
+            return None;
+        }
+
+        threshold = next_threshold;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::astar::find_path;
+
+    use super::*;
+
+    #[test]
+    fn test_straight_line() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let result = find_path_ida(
+            Point::new(0, 5),
+            Point::new(9, 5),
+            10,
+            10,
+            1,
+            1.0,
+            &weights,
+            0,
+            10000,
+        )
+        .unwrap();
+
+        assert_eq!(9.0, result.total_distance);
+        assert_eq!(10, result.path.len());
+        assert_eq!(10, result.max_depth);
+    }
+
+    #[test]
+    fn test_same_cost_as_find_path() {
+        #[rustfmt::skip]
+        let weights: Vec<f32> = vec![
+            1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+            1.0, -1.0, -1.0, -1.0, -1.0, 1.0,
+            1.0, 2.0, 1.0, 3.0, -1.0, 1.0,
+            1.0, -1.0, 1.0, 1.0, -1.0, 1.0,
+            1.0, -1.0, 1.0, 2.0, 1.0, 1.0,
+            1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+        ];
+
+        let expected = find_path(Point::new(0, 0), Point::new(3, 3), 6, 6, 1, 1.0, &weights)
+            .unwrap()
+            .total_distance;
+
+        let result = find_path_ida(
+            Point::new(0, 0),
+            Point::new(3, 3),
+            6,
+            6,
+            1,
+            1.0,
+            &weights,
+            0,
+            100000,
+        )
+        .unwrap();
+
+        assert!((expected - result.total_distance).abs() < 0.001);
+        assert_eq!(Some(&0), result.path.first());
+        assert_eq!(Some(&21), result.path.last());
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        let weights: Vec<f32> = (0..100)
+            .map(|i| {
+                let point = Point::from_1d_index(10, i);
+                if point.x == 5 && point.y > 2 {
+                    -1.0
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+
+        let expected = find_path(Point::new(0, 9), Point::new(9, 9), 10, 10, 1, 1.0, &weights)
+            .unwrap()
+            .total_distance;
+
+        // a full table and a small colliding one
+        for table_size in [100, 16] {
+            let result = find_path_ida(
+                Point::new(0, 9),
+                Point::new(9, 9),
+                10,
+                10,
+                1,
+                1.0,
+                &weights,
+                table_size,
+                u32::MAX,
+            )
+            .unwrap();
+
+            assert!((expected - result.total_distance).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn test_unreachable() {
+        #[rustfmt::skip]
+        let weights: Vec<f32> = vec![
+            1.0, -1.0, 1.0,
+            1.0, -1.0, 1.0,
+            1.0, -1.0, 1.0,
+        ];
+
+        assert!(find_path_ida(
+            Point::new(0, 0),
+            Point::new(2, 2),
+            3,
+            3,
+            1,
+            1.0,
+            &weights,
+            0,
+            10000
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_gives_up() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        assert!(find_path_ida(
+            Point::new(0, 0),
+            Point::new(9, 9),
+            10,
+            10,
+            1,
+            1.0,
+            &weights,
+            0,
+            5
+        )
+        .is_none());
+    }
+}
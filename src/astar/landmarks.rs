@@ -0,0 +1,320 @@
+use std::convert::TryInto;
+
+use super::{
+    dijkstra::{distance_field, NO_PREDECESSOR},
+    point::Point,
+};
+
+const SERIALIZATION_MAGIC: &[u8; 4] = b"ALT1";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LandmarkStrategy {
+    Farthest, // each landmark as far as possible from the ones already picked
+    Avoid,    // each landmark at the end of the part of the map the current landmarks cover worst
+}
+
+/// Landmarks with the distance from each of them to every cell, for the ALT (A*, landmarks, triangle inequality) heuristic
+/// The tables are only valid for the weights they were computed with. Raising weights or adding walls keeps the heuristic admissible, lowering them does not
+#[derive(Clone, Debug, PartialEq)]
+pub struct Landmarks {
+    pub width: u32,
+    pub height: u32,
+    indexes: Vec<u32>,
+    distances: Vec<Vec<f32>>, // one table per landmark, infinite for walls and unreachable cells
+}
+
+impl Landmarks {
+    /// Pick count landmarks with the strategy and compute their distance tables
+    pub fn select(
+        count: usize,
+        strategy: LandmarkStrategy,
+        width: u32,
+        height: u32,
+        weights: &[f32],
+    ) -> Self {
+        let mut landmarks = Landmarks {
+            width,
+            height,
+            indexes: Vec::with_capacity(count),
+            distances: Vec::with_capacity(count),
+        };
+
+        while landmarks.indexes.len() < count {
+            let next = match strategy {
+                LandmarkStrategy::Farthest => landmarks.farthest_cell(weights),
+                LandmarkStrategy::Avoid => landmarks
+                    .avoid_cell(weights)
+                    .or_else(|| landmarks.farthest_cell(weights)),
+            };
+
+            match next {
+                Some(index) => landmarks.add(index, weights),
+                None => break, // the map is full of landmarks or walls
+            }
+        }
+
+        landmarks
+    }
+
+    pub fn indexes(&self) -> &[u32] {
+        &self.indexes
+    }
+
+    /// Lower bound for the distance between two cells, the largest difference of their distances to any landmark
+    #[inline(always)]
+    pub fn lower_bound(&self, from_index: u32, to_index: u32) -> f32 {
+        self.distances
+            .iter()
+            .map(|table| {
+                let (from, to) = (table[from_index as usize], table[to_index as usize]);
+
+                // a landmark which cant reach both tells nothing
+                if from.is_finite() && to.is_finite() {
+                    (from - to).abs()
+                } else {
+                    0.0
+                }
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// Little endian: magic, width, height, landmark count, landmark indexes, then the distance tables
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let cells = (self.width * self.height) as usize;
+        let mut bytes = Vec::with_capacity(16 + self.indexes.len() * (4 + cells * 4));
+
+        bytes.extend_from_slice(SERIALIZATION_MAGIC);
+        bytes.extend_from_slice(&self.width.to_le_bytes());
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+        bytes.extend_from_slice(&(self.indexes.len() as u32).to_le_bytes());
+
+        for index in &self.indexes {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+
+        for distance in self.distances.iter().flatten() {
+            bytes.extend_from_slice(&distance.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// None if the bytes are not something to_bytes produced
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut words = bytes
+            .strip_prefix(SERIALIZATION_MAGIC)?
+            .chunks_exact(4)
+            .map(|chunk| chunk.try_into().unwrap());
+
+        let width = u32::from_le_bytes(words.next()?);
+        let height = u32::from_le_bytes(words.next()?);
+        let count = u32::from_le_bytes(words.next()?) as usize;
+        let cells = (width as usize).checked_mul(height as usize)?;
+
+        if bytes.len() != 16 + count.checked_mul(4 + cells.checked_mul(4)?)? {
+            return None;
+        }
+
+        let indexes: Vec<u32> = words.by_ref().take(count).map(u32::from_le_bytes).collect();
+
+        if indexes.iter().any(|&index| index as usize >= cells) {
+            return None;
+        }
+
+        let distances = (0..count)
+            .map(|_| words.by_ref().take(cells).map(f32::from_le_bytes).collect())
+            .collect();
+
+        Some(Landmarks {
+            width,
+            height,
+            indexes,
+            distances,
+        })
+    }
+
+    fn add(&mut self, index: u32, weights: &[f32]) {
+        let field = distance_field(
+            &[Point::from_1d_index(self.width, index)],
+            self.width,
+            self.height,
+            weights,
+        );
+
+        self.indexes.push(index);
+        self.distances.push(field.distances);
+    }
+
+    /// Cell farthest from the landmarks, or from a pseudo random cell for the first one
+    fn farthest_cell(&self, weights: &[f32]) -> Option<u32> {
+        let sources: Vec<Point> = match self.indexes.is_empty() {
+            true => vec![self.root(weights)?],
+            false => self
+                .indexes
+                .iter()
+                .map(|&index| Point::from_1d_index(self.width, index))
+                .collect(),
+        };
+
+        let field = distance_field(&sources, self.width, self.height, weights);
+
+        let (index, distance) = field
+            .distances
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| d.is_finite())
+            .max_by(|a, b| a.1.total_cmp(b.1))?;
+
+        (*distance > 0.0).then_some(index as u32)
+    }
+
+    /// Grow a shortest path tree from a pseudo random root and weigh every cell by how badly the current landmarks bound its distance from the root
+    /// Subtrees with a landmark in them are covered already, walking down the heaviest of the others ends up at the new landmark
+    fn avoid_cell(&self, weights: &[f32]) -> Option<u32> {
+        let root = self.root(weights)?;
+        let root_index = root.to_1d_index(self.width);
+        let field = distance_field(&[root], self.width, self.height, weights);
+
+        let mut order: Vec<u32> = (0..self.width * self.height)
+            .filter(|&i| field.distances[i as usize].is_finite())
+            .collect();
+
+        // children are farther from the root than their parents, so they are summed up first
+        order.sort_by(|a, b| field.distances[*b as usize].total_cmp(&field.distances[*a as usize]));
+
+        let mut size = vec![0.0; field.distances.len()];
+        let mut covered = vec![false; field.distances.len()];
+        let mut children: Vec<Vec<u32>> = vec![Vec::new(); field.distances.len()];
+
+        for &index in &self.indexes {
+            covered[index as usize] = true;
+        }
+
+        for &index in &order {
+            let i = index as usize;
+
+            if !covered[i] {
+                size[i] += field.distances[i] - self.lower_bound(root_index, index);
+            }
+
+            match field.predecessors[i] {
+                NO_PREDECESSOR => (),
+                parent => {
+                    children[parent as usize].push(index);
+
+                    if covered[i] {
+                        covered[parent as usize] = true;
+                    } else {
+                        size[parent as usize] += size[i];
+                    }
+                }
+            }
+        }
+
+        let mut current = root_index;
+
+        while let Some(&next) = children[current as usize]
+            .iter()
+            .filter(|&&child| !covered[child as usize])
+            .max_by(|a, b| size[**a as usize].total_cmp(&size[**b as usize]))
+        {
+            current = next;
+        }
+
+        (!covered[current as usize]).then_some(current)
+    }
+
+    /// Passable cell picked with a multiplicative hash of the landmark count, so the selection is the same every time
+    fn root(&self, weights: &[f32]) -> Option<Point> {
+        let start =
+            (self.indexes.len() as u32 + 1).wrapping_mul(2654435761) as usize % weights.len();
+
+        (0..weights.len())
+            .map(|i| (start + i) % weights.len())
+            .find(|&i| weights[i] >= 0.0)
+            .map(|i| Point::from_1d_index(self.width, i as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::astar::astar::{find_path, find_path_with_landmarks};
+
+    use super::*;
+
+    fn maze_weights(width: u32, height: u32) -> Vec<f32> {
+        (0..width * height)
+            .map(|i| {
+                let point = Point::from_1d_index(width, i);
+                if point.x % 6 == 3 && (point.y + point.x) % 12 > 2 {
+                    -1.0
+                } else {
+                    1.0 + ((point.x * 5 + point.y * 3) % 4) as f32
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_farthest_picks_corners() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let landmarks = Landmarks::select(4, LandmarkStrategy::Farthest, 10, 10, &weights);
+
+        let mut corners = landmarks.indexes().to_vec();
+        corners.sort();
+        assert_eq!(vec![0, 9, 90, 99], corners);
+    }
+
+    #[test]
+    fn test_lower_bound_is_admissible() {
+        let weights = maze_weights(24, 18);
+
+        for strategy in [LandmarkStrategy::Farthest, LandmarkStrategy::Avoid] {
+            let landmarks = Landmarks::select(4, strategy, 24, 18, &weights);
+            assert_eq!(4, landmarks.indexes().len());
+
+            for from in [0, 50, 200, 431] {
+                let field = distance_field(&[Point::from_1d_index(24, from)], 24, 18, &weights);
+
+                for to in 0..24 * 18 {
+                    let distance = field.distances[to as usize];
+                    if distance.is_finite() {
+                        assert!(landmarks.lower_bound(from, to) <= distance + 0.001);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_serialization_round_trip() {
+        let weights = maze_weights(12, 9);
+        let landmarks = Landmarks::select(3, LandmarkStrategy::Avoid, 12, 9, &weights);
+
+        let bytes = landmarks.to_bytes();
+        assert_eq!(Some(landmarks), Landmarks::from_bytes(&bytes));
+
+        assert_eq!(None, Landmarks::from_bytes(&bytes[..bytes.len() - 1]));
+        assert_eq!(None, Landmarks::from_bytes(b"nope"));
+    }
+
+    #[test]
+    fn test_fewer_nodes_expanded() {
+        let weights = maze_weights(60, 40);
+        let landmarks = Landmarks::select(8, LandmarkStrategy::Avoid, 60, 40, &weights);
+
+        let from = Point::new(0, 20);
+        let to = Point::new(59, 5);
+
+        let plain = find_path(from.clone(), to.clone(), 60, 40, 1, 1.0, &weights).unwrap();
+        let alt =
+            find_path_with_landmarks(from, to, 60, 40, 1, 1.0, &weights, &Arc::new(landmarks))
+                .unwrap();
+
+        assert!((plain.total_distance - alt.total_distance).abs() < 0.001);
+        assert!(alt.visited_indexes.len() < plain.visited_indexes.len());
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::HashSet;
+
+use crate::hybridheap::HybridHeap;
+
+use super::{
+    point::Point,
+    spacetime::{find_timed_path, ReservationTable, SpaceTimeResult},
+};
+
+/// Two agents in the same cell, or swapping places, at the same time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Conflict {
+    Vertex {
+        agents: (usize, usize),
+        index: u32,
+        time: u32,
+    },
+    Edge {
+        agents: (usize, usize),
+        from: u32, // first agent moves from -> to, the second one to -> from
+        to: u32,
+        time: u32, // time the moves start at
+    },
+}
+
+pub struct MapfResult {
+    pub paths: Vec<SpaceTimeResult>, // one per agent, in the order of the queries
+    pub total_distance: f32,         // sum of the costs of all paths
+    pub expanded_nodes: u32,         // constraint tree nodes expanded
+}
+
+/// Node of the constraint tree, the constraints of each agent are kept as a reservation table
+struct ConstraintNode {
+    constraints: Vec<ReservationTable>,
+    paths: Vec<SpaceTimeResult>,
+    cost: f32,
+}
+
+/// First conflict between any two of the paths, agents stay at their goal after arriving
+pub fn find_first_conflict(paths: &[SpaceTimeResult]) -> Option<Conflict> {
+    let last_time = paths.iter().map(|p| p.arrival_time()).max()?;
+
+    for time in 0..=last_time {
+        for a in 0..paths.len() {
+            for b in (a + 1)..paths.len() {
+                let (a_now, b_now) = (paths[a].position(time), paths[b].position(time));
+
+                if a_now == b_now {
+                    return Some(Conflict::Vertex {
+                        agents: (a, b),
+                        index: a_now,
+                        time,
+                    });
+                }
+
+                let (a_next, b_next) = (paths[a].position(time + 1), paths[b].position(time + 1));
+
+                if a_now == b_next && b_now == a_next {
+                    return Some(Conflict::Edge {
+                        agents: (a, b),
+                        from: a_now,
+                        to: a_next,
+                        time,
+                    });
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Collision free timed paths for many agents with Conflict-Based Search
+/// Each agent is searched on its own with space-time A*, and whenever two paths conflict the search branches in two, forbidding the conflict for either agent
+/// The sum of costs is optimal. Gives up with None after max_nodes constraint tree nodes, or if agents share a start or a goal
+#[allow(clippy::too_many_arguments)]
+pub fn find_multi_agent_paths(
+    agents: &[(Point, Point)],
+    wait_cost: f32,
+    max_nodes: u32,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+) -> Option<MapfResult> {
+    let starts: HashSet<u32> = agents.iter().map(|(s, _)| s.to_1d_index(width)).collect();
+    let goals: HashSet<u32> = agents.iter().map(|(_, g)| g.to_1d_index(width)).collect();
+
+    if starts.len() != agents.len() || goals.len() != agents.len() {
+        return None;
+    }
+
+    // long enough for every agent to wait for every other one
+    let max_time = (width + height) * (agents.len() as u32 + 1);
+
+    let plan = |agent: usize, constraints: &ReservationTable| {
+        let (from, to) = &agents[agent];
+
+        find_timed_path(
+            from.clone(),
+            to.clone(),
+            0,
+            wait_cost,
+            max_time,
+            width,
+            height,
+            multiplier,
+            min_weight,
+            weights,
+            constraints,
+        )
+    };
+
+    let constraints = vec![ReservationTable::new(); agents.len()];
+    let paths = (0..agents.len())
+        .map(|agent| plan(agent, &constraints[agent]))
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut nodes: Vec<Option<ConstraintNode>> = Vec::new();
+    let mut openset: HybridHeap<u32, f32> = HybridHeap::with_capacity(100);
+
+    let cost = paths.iter().map(|p| p.total_distance).sum();
+    nodes.push(Some(ConstraintNode {
+        constraints,
+        paths,
+        cost,
+    }));
+    openset.push(0, cost);
+
+    let mut expanded_nodes = 0;
+
+    while let Some(node_id) = openset.pop() {
+        // expanded nodes arent needed any more
+        let node = nodes[node_id as usize].take().unwrap();
+
+        let conflict = match find_first_conflict(&node.paths) {
+            Some(conflict) => conflict,
+            None => {
+                return Some(MapfResult {
+                    total_distance: node.cost,
+                    paths: node.paths,
+                    expanded_nodes,
+                })
+            }
+        };
+
+        expanded_nodes += 1;
+
+        if expanded_nodes >= max_nodes {
+            return None;
+        }
+
+        let (agents_in_conflict, time) = match conflict {
+            Conflict::Vertex { agents, time, .. } | Conflict::Edge { agents, time, .. } => {
+                (agents, time)
+            }
+        };
+
+        for (n, agent) in [agents_in_conflict.0, agents_in_conflict.1]
+            .into_iter()
+            .enumerate()
+        {
+            let mut constraints = node.constraints.clone();
+
+            match conflict {
+                Conflict::Vertex { index, .. } => constraints[agent].reserve_cell(index, time),
+                // is_move_reserved looks for the opposite move, so the forbidden move is reserved the other way around
+                Conflict::Edge { from, to, .. } => {
+                    let (from, to) = if n == 0 { (from, to) } else { (to, from) };
+                    constraints[agent].reserve_edge(to, from, time);
+                }
+            }
+
+            let Some(path) = plan(agent, &constraints[agent]) else {
+                continue;
+            };
+
+            let mut paths = node.paths.clone();
+            paths[agent] = path;
+
+            let cost = paths.iter().map(|p| p.total_distance).sum();
+            let child_id = nodes.len() as u32;
+
+            nodes.push(Some(ConstraintNode {
+                constraints,
+                paths,
+                cost,
+            }));
+            openset.push(child_id, cost);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start x, start y, goal x, goal y of each agent
+    fn agents(queries: &[[u32; 4]]) -> Vec<(Point, Point)> {
+        queries
+            .iter()
+            .map(|q| (Point::new(q[0], q[1]), Point::new(q[2], q[3])))
+            .collect()
+    }
+
+    #[test]
+    fn test_find_first_conflict() {
+        let weights: Vec<f32> = vec![1.0; 5];
+        let reservations = ReservationTable::new();
+
+        let path = |from: u32, to: u32| {
+            find_timed_path(
+                Point::new(from, 0),
+                Point::new(to, 0),
+                0,
+                1.0,
+                10,
+                5,
+                1,
+                1,
+                1.0,
+                &weights,
+                &reservations,
+            )
+            .unwrap()
+        };
+
+        // 0 -> 4 and 4 -> 0 meet in the middle
+        assert_eq!(
+            Some(Conflict::Vertex {
+                agents: (0, 1),
+                index: 2,
+                time: 2
+            }),
+            find_first_conflict(&[path(0, 4), path(4, 0)])
+        );
+
+        // 0 -> 3 and 3 -> 0 swap between 1 and 2
+        assert_eq!(
+            Some(Conflict::Edge {
+                agents: (0, 1),
+                from: 1,
+                to: 2,
+                time: 1
+            }),
+            find_first_conflict(&[path(0, 3), path(3, 0)])
+        );
+
+        assert_eq!(None, find_first_conflict(&[path(0, 1), path(4, 3)]));
+    }
+
+    #[test]
+    fn test_swap_through_pocket() {
+        // corridor with a pocket below the middle cell
+        #[rustfmt::skip]
+        let weights: Vec<f32> = vec![
+            1.0, 1.0, 1.0, 1.0, 1.0,
+            -1.0, -1.0, 1.0, -1.0, -1.0,
+        ];
+
+        let result = find_multi_agent_paths(
+            &agents(&[[0, 0, 4, 0], [4, 0, 0, 0]]),
+            0.5,
+            1000,
+            5,
+            2,
+            1,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        assert_eq!(None, find_first_conflict(&result.paths));
+        assert!(result
+            .paths
+            .iter()
+            .any(|p| p.path.iter().any(|c| c.index == 7)));
+        assert_eq!(4, result.paths[0].to_index);
+        assert_eq!(0, result.paths[1].to_index);
+    }
+
+    #[test]
+    fn test_crossing_agents() {
+        let weights: Vec<f32> = vec![1.0; 49];
+
+        let result = find_multi_agent_paths(
+            &agents(&[[0, 3, 6, 3], [3, 0, 3, 6], [6, 3, 0, 3], [3, 6, 3, 0]]),
+            0.5,
+            1000,
+            7,
+            7,
+            1,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        assert_eq!(None, find_first_conflict(&result.paths));
+        assert!(result.total_distance >= 24.0);
+        assert!(result.expanded_nodes > 0);
+    }
+
+    #[test]
+    fn test_shared_goal_fails() {
+        let weights: Vec<f32> = vec![1.0; 25];
+
+        assert!(find_multi_agent_paths(
+            &agents(&[[0, 0, 4, 4], [4, 0, 4, 4]]),
+            0.5,
+            1000,
+            5,
+            5,
+            1,
+            1.0,
+            &weights,
+        )
+        .is_none());
+    }
+}
@@ -1,3 +1,30 @@
+pub mod alternatives;
+pub mod anytime;
+#[allow(clippy::module_inception)]
 pub mod astar;
 pub mod astar_utils;
+pub mod batch;
+pub mod cache;
+pub mod clearance;
+pub mod components;
+pub mod cooperative;
+pub mod dijkstra;
+pub mod elevation;
+pub mod flowfield;
+pub mod fringe;
+pub mod heading;
+pub mod hybrid_astar;
+pub mod ida_star;
+pub mod landmarks;
+pub mod mapf;
+pub mod oneway;
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+pub mod parallel;
 pub mod point;
+pub mod point3d;
+pub mod portals;
+pub mod route;
+pub mod smoothing;
+pub mod spacetime;
+pub mod tsp;
+pub mod voxel;
@@ -0,0 +1,131 @@
+use bmp::Image;
+
+use super::{
+    heading::{heading_between, HEADINGS},
+    point::Point,
+};
+
+/// Exit mask of a regular cell, bit n allows leaving the cell in the heading HEADINGS[n]
+pub const ALL_EXITS: u8 = 0xFF;
+
+/// Blue values at or above this, with red and green 0, mark one-way cells in a map image
+pub const ONE_WAY_BLUE_THRESHOLD: u8 = 0x80;
+
+/// Mask of a one-way cell, eg a conveyor belt, leaving is allowed in the heading and the two 45 degree neighbours of it
+pub fn one_way_mask(heading: u8) -> u8 {
+    let heading = heading % 8;
+    let left = (heading + 7) % 8;
+    let right = (heading + 1) % 8;
+
+    (1 << heading) | (1 << left) | (1 << right)
+}
+
+/// Whether moving between the neighbouring cells is allowed by the exit mask of the from cell
+#[inline(always)]
+pub fn allows_exit(mask: u8, from: &Point, to: &Point) -> bool {
+    mask & (1 << heading_between(from, to)) != 0
+}
+
+/// Read exit masks from a map image
+/// Pure blue pixels, red and green 0 and blue at least ONE_WAY_BLUE_THRESHOLD, are one-way cells where the lowest three bits of blue pick the heading from HEADINGS
+/// Every other pixel allows all exits
+pub fn image_to_exit_masks(image: &Image) -> Vec<u8> {
+    let width = image.get_width();
+    let height = image.get_height();
+
+    let mut masks = vec![ALL_EXITS; (width * height) as usize];
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = image.get_pixel(x, y);
+
+            if pixel.r == 0 && pixel.g == 0 && pixel.b >= ONE_WAY_BLUE_THRESHOLD {
+                masks[Point::new(x, y).to_1d_index(width) as usize] = one_way_mask(pixel.b & 0b111);
+            }
+        }
+    }
+
+    masks
+}
+
+/// Main heading of a one-way cell, None if the mask isnt one made with one_way_mask
+pub fn one_way_heading(mask: u8) -> Option<u8> {
+    (0..HEADINGS.len() as u8).find(|heading| one_way_mask(*heading) == mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::astar::{find_path, FindPath};
+
+    use super::*;
+
+    #[test]
+    fn test_one_way_mask() {
+        assert_eq!(0b1000_0011, one_way_mask(0));
+        assert_eq!(0b0001_1100, one_way_mask(3));
+        assert_eq!(Some(3), one_way_heading(one_way_mask(3)));
+        assert_eq!(None, one_way_heading(ALL_EXITS));
+
+        let mask = one_way_mask(0);
+        assert!(allows_exit(mask, &Point::new(1, 1), &Point::new(2, 1)));
+        assert!(allows_exit(mask, &Point::new(1, 1), &Point::new(2, 0)));
+        assert!(!allows_exit(mask, &Point::new(1, 1), &Point::new(0, 1)));
+        assert!(!allows_exit(mask, &Point::new(1, 1), &Point::new(1, 2)));
+    }
+
+    #[test]
+    fn test_image_to_exit_masks() {
+        let mut image = Image::new(3, 1);
+        image.set_pixel(0, 0, bmp::Pixel::new(0, 0, 0x84));
+        image.set_pixel(1, 0, bmp::Pixel::new(0, 0, 0x40));
+        image.set_pixel(2, 0, bmp::Pixel::new(255, 255, 255));
+
+        assert_eq!(
+            vec![one_way_mask(4), ALL_EXITS, ALL_EXITS],
+            image_to_exit_masks(&image)
+        );
+    }
+
+    #[test]
+    fn test_one_way_aisle() {
+        // single row aisle in the middle going east, wall above it and an open row below
+        let width = 10;
+        let height = 3;
+        let mut weights: Vec<f32> = vec![1.0; (width * height) as usize];
+        let mut masks = vec![ALL_EXITS; (width * height) as usize];
+
+        for x in 1..width - 1 {
+            weights[Point::new(x, 0).to_1d_index(width) as usize] = -1.0;
+            masks[Point::new(x, 1).to_1d_index(width) as usize] = one_way_mask(0);
+        }
+
+        let east = find_path(
+            Point::new(0, 1),
+            Point::new(9, 1),
+            width,
+            height,
+            1,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        let mut path_finder =
+            FindPath::new(Point::new(0, 1), Point::new(9, 1), width, height, 1, 1.0)
+                .with_exit_masks(masks.clone());
+        assert_eq!(Some(east.total_distance), path_finder.run(&weights));
+
+        // going west the aisle cant be used, the path takes the bottom row instead
+        let mut path_finder =
+            FindPath::new(Point::new(9, 1), Point::new(0, 1), width, height, 1, 1.0)
+                .with_exit_masks(masks);
+        let cost = path_finder.run(&weights).unwrap();
+        assert!(cost > east.total_distance);
+
+        let path = path_finder.ordered_path().unwrap();
+        for step in path.windows(2) {
+            let from = Point::from_1d_index(width, step[0]);
+            assert!(from.y != 1 || from.x == 0 || from.x == 9 || step[1] > step[0]);
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Barrier, Mutex,
+    },
+    thread,
+};
+
+use rayon::prelude::*;
+
+use crate::hybridheap::HybridHeap;
+
+use super::{
+    astar::{FindPath, VisitedPoint},
+    astar_utils::{
+        calculate_heuristical_distance, calculate_weight, get_neighbours, reconstruct_ordered_path,
+    },
+    batch::{run_query, QueryResult},
+    point::Point,
+};
+
+/// A cell with its score and where it was reached from, sent to the thread owning it
+type Message = (u32, VisitedPoint<f32, u32>);
+
+/// Nodes each HDA* thread expands before the threads exchange the nodes they found for each other
+pub const HDA_EXPANSIONS_PER_ROUND: usize = 64;
+
+/// Find paths for many queries on the same map, spread over the rayon thread pool
+/// Each worker reuses a path finder for the queries it picks up, the results are in the same order as the queries
+pub fn find_paths_parallel(
+    queries: &[(Point, Point)],
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+) -> Vec<QueryResult> {
+    find_paths_parallel_with(
+        || {
+            FindPath::new(
+                Point::new(0, 0),
+                Point::new(0, 0),
+                width,
+                height,
+                multiplier,
+                min_weight,
+            )
+        },
+        queries,
+        weights,
+    )
+}
+
+/// Same as find_paths_parallel with path finders made by new_path_finder, eg ones with portals or landmarks
+pub fn find_paths_parallel_with(
+    new_path_finder: impl Fn() -> FindPath + Send + Sync,
+    queries: &[(Point, Point)],
+    weights: &[f32],
+) -> Vec<QueryResult> {
+    queries
+        .par_iter()
+        .map_init(new_path_finder, |path_finder, (from, to)| {
+            run_query(path_finder, from, to, weights)
+        })
+        .collect()
+}
+
+/// Experimental parallel A* for a single huge query, loosely HDA* (hash distributed A*)
+/// Every cell is owned by one thread picked by a hash of its index, and only the owner keeps its score and puts it in its openset.
+/// The threads expand their own nodes in rounds and hand over the neighbours they find to the owners between rounds.
+/// The first path found is not necessarily the shortest, so the search keeps going until no thread has anything cheaper left.
+/// Lots of nodes travel between threads, so this only pays off on big maps with expensive searches
+#[allow(clippy::too_many_arguments)]
+pub fn find_path_hda(
+    from: Point,
+    to: Point,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+    threads: usize,
+) -> QueryResult {
+    let threads = threads.max(1);
+
+    let search = HdaSearch {
+        to_index: to.to_1d_index(width),
+        to,
+        width,
+        height,
+        multiplier,
+        min_weight,
+        weights,
+        threads,
+        inboxes: (0..threads).map(|_| Mutex::new(Vec::new())).collect(),
+        lowest_f_scores: (0..threads)
+            .map(|_| AtomicU32::new(f32::INFINITY.to_bits()))
+            .collect(),
+        best_distance: AtomicU32::new(f32::INFINITY.to_bits()),
+        barrier: Barrier::new(threads),
+    };
+
+    let from_index = from.to_1d_index(width);
+    search.inboxes[search.owner(from_index)]
+        .lock()
+        .unwrap()
+        .push((
+            from_index,
+            VisitedPoint {
+                score: 0.0,
+                came_from_key: from_index,
+            },
+        ));
+
+    let g_scores: Vec<HashMap<u32, VisitedPoint<f32, u32>>> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|thread| {
+                let search = &search;
+                scope.spawn(move || search.run_partition(thread))
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    // every cell is in exactly one of the partitions
+    let visited: HashMap<u32, VisitedPoint<f32, u32>> = g_scores.into_iter().flatten().collect();
+    let best_distance = f32::from_bits(search.best_distance.load(Ordering::Relaxed));
+
+    match best_distance.is_finite() {
+        true => QueryResult {
+            path: reconstruct_ordered_path(&visited, search.to_index),
+            total_distance: Some(best_distance),
+            nodes_visited_count: visited.len() as u32,
+        },
+        false => QueryResult {
+            path: Vec::new(),
+            total_distance: None,
+            nodes_visited_count: visited.len() as u32,
+        },
+    }
+}
+
+/// State shared by the HDA* threads
+/// Scores are non negative, so their f32 bits compare like the floats and can live in atomics
+struct HdaSearch<'a> {
+    to: Point,
+    to_index: u32,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &'a [f32],
+    threads: usize,
+    inboxes: Vec<Mutex<Vec<Message>>>, // nodes found by other threads, waiting for the owner
+    lowest_f_scores: Vec<AtomicU32>,   // top of each openset at the end of the round
+    best_distance: AtomicU32,          // cheapest path found so far
+    barrier: Barrier,
+}
+
+impl HdaSearch<'_> {
+    /// Thread owning the cell, the multiplicative hash spreads neighbouring cells over the threads to keep them all busy
+    #[inline(always)]
+    fn owner(&self, index: u32) -> usize {
+        (index.wrapping_mul(2654435761) >> 16) as usize % self.threads
+    }
+
+    #[inline(always)]
+    fn f_score(&self, index: u32, g_score: f32) -> f32 {
+        g_score
+            + calculate_heuristical_distance(
+                &Point::from_1d_index(self.width, index),
+                &self.to,
+                self.multiplier,
+                self.min_weight,
+            )
+    }
+
+    fn best_distance(&self) -> f32 {
+        f32::from_bits(self.best_distance.load(Ordering::Relaxed))
+    }
+
+    /// Search the cells owned by the thread until all threads are done, returns the scores of the owned cells
+    fn run_partition(&self, thread: usize) -> HashMap<u32, VisitedPoint<f32, u32>> {
+        let mut openset: HybridHeap<u32, f32> = HybridHeap::new();
+        let mut g_score: HashMap<u32, VisitedPoint<f32, u32>> = HashMap::new();
+        let mut outboxes: Vec<Vec<Message>> = vec![Vec::new(); self.threads];
+
+        loop {
+            let received = std::mem::take(&mut *self.inboxes[thread].lock().unwrap());
+
+            for (index, visited) in received {
+                self.relax(&mut openset, &mut g_score, index, visited);
+            }
+
+            for _ in 0..HDA_EXPANSIONS_PER_ROUND {
+                // nothing cheaper than the best path left here
+                match openset.peek_value() {
+                    Some(f_score) if f_score < self.best_distance() => (),
+                    _ => break,
+                }
+
+                let current_index = openset.pop().unwrap();
+                let current_score = g_score[&current_index].score;
+
+                if current_index == self.to_index {
+                    self.best_distance
+                        .fetch_min(current_score.to_bits(), Ordering::Relaxed);
+                    continue;
+                }
+
+                let current_point = Point::from_1d_index(self.width, current_index);
+
+                for neighbour_index in get_neighbours(&current_point, self.width, self.height) {
+                    let weight = calculate_weight(
+                        &current_point,
+                        &Point::from_1d_index(self.width, neighbour_index),
+                        self.weights,
+                        self.width,
+                    );
+
+                    // wall...
+                    if weight <= 0.0 {
+                        continue;
+                    }
+
+                    let visited = VisitedPoint {
+                        score: current_score + weight,
+                        came_from_key: current_index,
+                    };
+
+                    match self.owner(neighbour_index) {
+                        owner if owner == thread => {
+                            self.relax(&mut openset, &mut g_score, neighbour_index, visited)
+                        }
+                        owner => outboxes[owner].push((neighbour_index, visited)),
+                    }
+                }
+            }
+
+            for (owner, outbox) in outboxes.iter_mut().enumerate() {
+                if !outbox.is_empty() {
+                    self.inboxes[owner].lock().unwrap().append(outbox);
+                }
+            }
+
+            self.lowest_f_scores[thread].store(
+                openset.peek_value().unwrap_or(f32::INFINITY).to_bits(),
+                Ordering::Relaxed,
+            );
+
+            // nobody touches the inboxes or scores between the barriers, so every thread comes to the same conclusion
+            self.barrier.wait();
+
+            let best_distance = self.best_distance();
+            let done =
+                self.lowest_f_scores.iter().all(|f_score| {
+                    f32::from_bits(f_score.load(Ordering::Relaxed)) >= best_distance
+                }) && self
+                    .inboxes
+                    .iter()
+                    .all(|inbox| inbox.lock().unwrap().is_empty());
+
+            self.barrier.wait();
+
+            if done {
+                return g_score;
+            }
+        }
+    }
+
+    fn relax(
+        &self,
+        openset: &mut HybridHeap<u32, f32>,
+        g_score: &mut HashMap<u32, VisitedPoint<f32, u32>>,
+        index: u32,
+        visited: VisitedPoint<f32, u32>,
+    ) {
+        match g_score.get(&index) {
+            Some(p) if p.score <= visited.score => return,
+            _ => g_score.insert(index, visited),
+        };
+
+        let f_score = self.f_score(index, visited.score);
+
+        // cant lead to anything cheaper than the best path, keep the score for the path but dont bother expanding
+        if f_score >= self.best_distance() {
+            return;
+        }
+
+        match openset.get_value(index) {
+            Some(_) => openset.change_value(index, f_score),
+            None => openset.push(index, f_score),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::{astar::find_path, batch::find_paths};
+
+    use super::*;
+
+    fn maze_weights(width: u32, height: u32) -> Vec<f32> {
+        (0..width * height)
+            .map(|i| {
+                let point = Point::from_1d_index(width, i);
+                if point.x % 6 == 3 && (point.y + point.x) % 12 > 2 {
+                    -1.0
+                } else {
+                    1.0 + ((point.x * 5 + point.y * 3) % 4) as f32
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_batch_same_as_sequential() {
+        let weights = maze_weights(30, 20);
+
+        let queries: Vec<(Point, Point)> = (0..40)
+            .map(|n| {
+                (
+                    Point::new(n * 7 % 30, n * 3 % 20),
+                    Point::new((n * 11 + 5) % 30, (n * 13 + 2) % 20),
+                )
+            })
+            .collect();
+
+        let sequential = find_paths(&queries, 30, 20, 1, 1.0, &weights);
+        let parallel = find_paths_parallel(&queries, 30, 20, 1, 1.0, &weights);
+
+        assert_eq!(sequential.len(), parallel.len());
+
+        for (a, b) in sequential.iter().zip(&parallel) {
+            assert_eq!(a.total_distance, b.total_distance);
+            assert_eq!(a.path, b.path);
+        }
+    }
+
+    #[test]
+    fn test_hda_finds_shortest_path() {
+        let weights = maze_weights(60, 40);
+        let (from, to) = (Point::new(0, 20), Point::new(59, 5));
+
+        let expected = find_path(from.clone(), to.clone(), 60, 40, 1, 1.0, &weights).unwrap();
+
+        for threads in [1, 2, 3, 4] {
+            let result = find_path_hda(from.clone(), to.clone(), 60, 40, 1, 1.0, &weights, threads);
+
+            assert!((expected.total_distance - result.total_distance.unwrap()).abs() < 0.001);
+            assert_eq!(Some(&from.to_1d_index(60)), result.path.first());
+            assert_eq!(Some(&to.to_1d_index(60)), result.path.last());
+
+            // the path adds up to the distance
+            let cost: f32 = result
+                .path
+                .windows(2)
+                .map(|pair| {
+                    calculate_weight(
+                        &Point::from_1d_index(60, pair[0]),
+                        &Point::from_1d_index(60, pair[1]),
+                        &weights,
+                        60,
+                    )
+                })
+                .sum();
+            assert!((expected.total_distance - cost).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn test_hda_unreachable() {
+        #[rustfmt::skip]
+        let weights: Vec<f32> = vec![
+            1.0, 1.0, -1.0, 1.0,
+            1.0, 1.0, -1.0, 1.0,
+            1.0, 1.0, -1.0, 1.0,
+        ];
+
+        let result = find_path_hda(
+            Point::new(0, 0),
+            Point::new(3, 2),
+            4,
+            3,
+            1,
+            1.0,
+            &weights,
+            2,
+        );
+
+        assert_eq!(None, result.total_distance);
+        assert!(result.path.is_empty());
+        assert_eq!(6, result.nodes_visited_count);
+
+        let result = find_path_hda(
+            Point::new(1, 1),
+            Point::new(1, 1),
+            4,
+            3,
+            1,
+            1.0,
+            &weights,
+            2,
+        );
+        assert_eq!(Some(0.0), result.total_distance);
+        assert_eq!(vec![5], result.path);
+    }
+}
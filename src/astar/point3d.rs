@@ -0,0 +1,30 @@
+use wasm_bindgen::prelude::wasm_bindgen;
+
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Point3 {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+#[wasm_bindgen]
+impl Point3 {
+    pub fn new(x: u32, y: u32, z: u32) -> Self {
+        Self { x, y, z }
+    }
+
+    #[inline(always)]
+    pub fn to_1d_index(&self, width: u32, height: u32) -> u32 {
+        (self.z * height + self.y) * width + self.x
+    }
+
+    #[inline(always)]
+    pub fn from_1d_index(width: u32, height: u32, index: u32) -> Self {
+        Point3 {
+            x: index % width,
+            y: (index / width) % height,
+            z: index / (width * height),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+
+use super::point::Point;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PortalLink {
+    pub to_index: u32,
+    pub cost: f32,
+}
+
+/// Links between arbitrary cells, eg doors between distant rooms or teleporters
+/// Links are directed, bidirectional links are simply stored as two links
+#[derive(Clone, Default)]
+pub struct Portals {
+    width: u32,
+    height: u32,
+    links: HashMap<u32, Vec<PortalLink>>,
+}
+
+impl Portals {
+    pub fn new(width: u32, height: u32) -> Self {
+        Portals {
+            width,
+            height,
+            links: HashMap::new(),
+        }
+    }
+
+    /// Add link from one cell to another with the cost of traversing it
+    /// If a link between the cells already exists, the cost is updated
+    /// Returns false and adds nothing if the cost isnt positive, free or negative links would break the search and the heuristic cap,
+    /// or if either end is outside the map
+    pub fn add(&mut self, from: &Point, to: &Point, cost: f32, bidirectional: bool) -> bool {
+        if !cost.is_finite() || cost <= 0.0 {
+            return false;
+        }
+
+        if [from, to]
+            .iter()
+            .any(|point| point.x >= self.width || point.y >= self.height)
+        {
+            return false;
+        }
+
+        let from_index = from.to_1d_index(self.width);
+        let to_index = to.to_1d_index(self.width);
+
+        self.insert(from_index, to_index, cost);
+
+        if bidirectional {
+            self.insert(to_index, from_index, cost);
+        }
+
+        true
+    }
+
+    /// Remove links between the cells in both directions
+    pub fn remove(&mut self, from: &Point, to: &Point) {
+        let from_index = from.to_1d_index(self.width);
+        let to_index = to.to_1d_index(self.width);
+
+        for (a, b) in [(from_index, to_index), (to_index, from_index)] {
+            if let Some(links) = self.links.get_mut(&a) {
+                links.retain(|link| link.to_index != b);
+
+                if links.is_empty() {
+                    self.links.remove(&a);
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.links.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Links leaving the cell, empty if none
+    #[inline(always)]
+    pub fn links_from(&self, index: u32) -> &[PortalLink] {
+        match self.links.get(&index) {
+            Some(links) => links,
+            None => &[],
+        }
+    }
+
+    /// Cheapest traversal cost of any link, None if there are no links
+    pub fn min_cost(&self) -> Option<f32> {
+        self.links
+            .values()
+            .flatten()
+            .map(|link| link.cost)
+            .reduce(f32::min)
+    }
+
+    /// Indexes of all cells where a link ends
+    pub fn exit_indexes(&self) -> HashSet<u32> {
+        self.links
+            .values()
+            .flatten()
+            .map(|link| link.to_index)
+            .collect()
+    }
+
+    /// Indexes of all cells where a link starts or ends
+    pub fn endpoint_indexes(&self) -> HashSet<u32> {
+        let mut endpoints = self.exit_indexes();
+        endpoints.extend(self.links.keys());
+        endpoints
+    }
+
+    fn insert(&mut self, from_index: u32, to_index: u32, cost: f32) {
+        let links = self.links.entry(from_index).or_default();
+
+        match links.iter_mut().find(|link| link.to_index == to_index) {
+            Some(link) => link.cost = cost,
+            None => links.push(PortalLink { to_index, cost }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::astar::FindPath;
+
+    use super::*;
+
+    #[test]
+    fn test_add_rejects_non_positive_cost() {
+        let mut portals = Portals::new(10, 10);
+
+        assert!(!portals.add(&Point::new(0, 0), &Point::new(9, 9), 0.0, true));
+        assert!(!portals.add(&Point::new(0, 0), &Point::new(9, 9), -1.0, false));
+        assert!(!portals.add(&Point::new(0, 0), &Point::new(9, 9), f32::NAN, false));
+        assert!(portals.is_empty());
+
+        assert!(portals.add(&Point::new(0, 0), &Point::new(9, 9), 0.5, false));
+        assert_eq!(Some(0.5), portals.min_cost());
+    }
+
+    #[test]
+    fn test_add_rejects_off_map_ends() {
+        let mut portals = Portals::new(10, 5);
+
+        assert!(!portals.add(&Point::new(0, 0), &Point::new(10, 0), 1.0, false));
+        assert!(!portals.add(&Point::new(0, 5), &Point::new(9, 4), 1.0, true));
+        assert!(portals.is_empty());
+
+        assert!(portals.add(&Point::new(0, 0), &Point::new(9, 4), 1.0, false));
+    }
+
+    #[test]
+    fn test_add_remove() {
+        let mut portals = Portals::new(10, 10);
+
+        portals.add(&Point::new(0, 0), &Point::new(9, 9), 2.0, true);
+        portals.add(&Point::new(1, 0), &Point::new(5, 5), 3.0, false);
+
+        assert_eq!(
+            &[PortalLink {
+                to_index: 99,
+                cost: 2.0
+            }],
+            portals.links_from(0)
+        );
+        assert_eq!(
+            &[PortalLink {
+                to_index: 0,
+                cost: 2.0
+            }],
+            portals.links_from(99)
+        );
+        assert!(portals.links_from(55).is_empty());
+        assert_eq!(Some(2.0), portals.min_cost());
+        assert_eq!(HashSet::from([0, 1, 55, 99]), portals.endpoint_indexes());
+
+        portals.remove(&Point::new(9, 9), &Point::new(0, 0));
+
+        assert!(portals.links_from(0).is_empty());
+        assert!(portals.links_from(99).is_empty());
+        assert_eq!(Some(3.0), portals.min_cost());
+    }
+
+    #[test]
+    fn test_find_path_through_portal() {
+        // wall across the middle of the map, only way through is the portal
+        let mut weights: Vec<f32> = vec![1.0; 100];
+        for x in 0..10 {
+            weights[Point::new(x, 5).to_1d_index(10) as usize] = -1.0;
+        }
+
+        let mut portals = Portals::new(10, 10);
+        portals.add(&Point::new(0, 4), &Point::new(9, 6), 1.5, false);
+
+        let mut path_finder = FindPath::new(Point::new(0, 0), Point::new(9, 9), 10, 10, 1, 1.0)
+            .with_portals(portals.clone());
+
+        assert_eq!(Some(8.5), path_finder.run(&weights));
+        assert!(path_finder.path_indexes.unwrap().contains(&69));
+
+        // portal only goes one way
+        let mut path_finder =
+            FindPath::new(Point::new(9, 9), Point::new(0, 0), 10, 10, 1, 1.0).with_portals(portals);
+
+        assert_eq!(None, path_finder.run(&weights));
+    }
+
+    #[test]
+    fn test_find_path_prefers_cheaper_route() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let mut portals = Portals::new(10, 10);
+        portals.add(&Point::new(0, 0), &Point::new(9, 0), 20.0, true);
+
+        let mut path_finder =
+            FindPath::new(Point::new(0, 0), Point::new(9, 0), 10, 10, 1, 1.0).with_portals(portals);
+
+        assert_eq!(Some(9.0), path_finder.run(&weights));
+    }
+}
@@ -0,0 +1,137 @@
+use super::{astar::FindPath, point::Point};
+
+pub struct RouteResult {
+    pub path: Vec<u32>, // cells in order from start to end, both included
+    pub leg_costs: Vec<f32>,
+    pub total_distance: f32,
+}
+
+/// Find route from start through each waypoint in order to the end
+/// Each leg is searched separately with the same path finder so allocations are reused between legs
+/// Returns None if any of the legs cannot be completed
+#[allow(clippy::too_many_arguments)]
+pub fn find_route(
+    from: Point,
+    waypoints: &[Point],
+    to: Point,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+) -> Option<RouteResult> {
+    let stops: Vec<Point> = std::iter::once(from)
+        .chain(waypoints.iter().cloned())
+        .chain(std::iter::once(to))
+        .collect();
+
+    find_route_with(
+        &mut FindPath::new(
+            stops[0].clone(),
+            stops[1].clone(),
+            width,
+            height,
+            multiplier,
+            min_weight,
+        ),
+        &stops,
+        weights,
+    )
+}
+
+/// Find route through all the stops in order using an existing path finder, eg one with portals
+pub fn find_route_with(
+    path_finder: &mut FindPath,
+    stops: &[Point],
+    weights: &[f32],
+) -> Option<RouteResult> {
+    let mut path: Vec<u32> = Vec::new();
+    let mut leg_costs: Vec<f32> = Vec::with_capacity(stops.len().saturating_sub(1));
+
+    for leg in stops.windows(2) {
+        path_finder.restart(leg[0].clone(), leg[1].clone());
+
+        leg_costs.push(path_finder.run(weights)?);
+
+        let leg_path = path_finder.ordered_path()?;
+
+        // the first cell of a leg is the last cell of the previous one
+        let skip = if path.is_empty() { 0 } else { 1 };
+        path.extend(leg_path.into_iter().skip(skip));
+    }
+
+    Some(RouteResult {
+        path,
+        total_distance: leg_costs.iter().sum(),
+        leg_costs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_route() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let result = find_route(
+            Point::new(0, 0),
+            &[Point::new(9, 0), Point::new(9, 9)],
+            Point::new(0, 9),
+            10,
+            10,
+            1,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        assert_eq!(vec![9.0, 9.0, 9.0], result.leg_costs);
+        assert_eq!(27.0, result.total_distance);
+        assert_eq!(28, result.path.len());
+        assert_eq!(Some(&0), result.path.first());
+        assert_eq!(Some(&9), result.path.get(9));
+        assert_eq!(Some(&99), result.path.get(18));
+        assert_eq!(Some(&90), result.path.last());
+    }
+
+    #[test]
+    fn test_find_route_without_waypoints() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let result = find_route(
+            Point::new(0, 0),
+            &[],
+            Point::new(9, 9),
+            10,
+            10,
+            1,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        assert_eq!(vec![12.727921], result.leg_costs);
+        assert_eq!(vec![0, 11, 22, 33, 44, 55, 66, 77, 88, 99], result.path);
+    }
+
+    #[test]
+    fn test_find_route_unreachable_waypoint() {
+        let mut weights: Vec<f32> = vec![1.0; 100];
+        weights[55] = -1.0;
+
+        let result = find_route(
+            Point::new(0, 0),
+            &[Point::new(5, 5)],
+            Point::new(9, 9),
+            10,
+            10,
+            1,
+            1.0,
+            &weights,
+        );
+
+        assert!(result.is_none());
+    }
+}
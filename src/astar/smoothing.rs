@@ -0,0 +1,477 @@
+use super::{astar_utils::calculate_weight, point::Point, portals::Portals};
+
+/// Ordered points of a path in cell coordinates, cell x, y covers x..x+1, y..y+1 so cell centers are at x + 0.5, y + 0.5
+pub struct SmoothedPath {
+    pub points: Vec<(f32, f32)>,
+    pub cost: f32, // evaluated over the weights along the actual segments, not the grid path it came from, plus the cost of any portal hops
+}
+
+#[inline(always)]
+fn cell_center(index: u32, width: u32) -> (f32, f32) {
+    let point = Point::from_1d_index(width, index);
+    (point.x as f32 + 0.5, point.y as f32 + 0.5)
+}
+
+/// Cost of moving along a straight segment, the length inside every cell times the weight of the cell, None if it crosses a wall or leaves the map
+/// Between the centers of neighbouring cells this is the same as calculate_weight, and like the grid search it can slip diagonally between two walls touching at a corner
+pub fn segment_cost(
+    from: (f32, f32),
+    to: (f32, f32),
+    weights: &[f32],
+    width: u32,
+    height: u32,
+) -> Option<f32> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx.hypot(dy);
+
+    let (mut x, mut y) = (from.0.floor() as i64, from.1.floor() as i64);
+    let (step_x, step_y) = (dx.signum() as i64, dy.signum() as i64);
+
+    // parameter along the segment where the next vertical and horizontal cell borders are crossed
+    let border = |start: f32, cell: i64, delta: f32| {
+        if delta > 0.0 {
+            (cell as f32 + 1.0 - start) / delta
+        } else if delta < 0.0 {
+            (start - cell as f32) / -delta
+        } else {
+            f32::INFINITY
+        }
+    };
+    let (mut t_max_x, mut t_max_y) = (border(from.0, x, dx), border(from.1, y, dy));
+    let (t_delta_x, t_delta_y) = (1.0 / dx.abs(), 1.0 / dy.abs());
+
+    let mut t = 0.0;
+    let mut cost = 0.0;
+
+    loop {
+        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+            return None;
+        }
+
+        let weight = weights[(y * width as i64 + x) as usize];
+
+        // wall...
+        if weight < 0.0 {
+            return None;
+        }
+
+        let t_next = t_max_x.min(t_max_y).min(1.0);
+        cost += (t_next - t) * length * weight;
+
+        if t_next >= 1.0 {
+            return Some(cost);
+        }
+
+        // exactly through a corner steps diagonally
+        if t_max_x <= t_max_y {
+            x += step_x;
+            t_max_x += t_delta_x;
+        }
+        if t_max_y <= t_next {
+            y += step_y;
+            t_max_y += t_delta_y;
+        }
+
+        t = t_next;
+    }
+}
+
+pub fn line_of_sight(
+    from: (f32, f32),
+    to: (f32, f32),
+    weights: &[f32],
+    width: u32,
+    height: u32,
+) -> bool {
+    segment_cost(from, to, weights, width, height).is_some()
+}
+
+/// Cost of the whole polyline, None if any segment crosses a wall
+pub fn polyline_cost(
+    points: &[(f32, f32)],
+    weights: &[f32],
+    width: u32,
+    height: u32,
+) -> Option<f32> {
+    points
+        .windows(2)
+        .map(|segment| segment_cost(segment[0], segment[1], weights, width, height))
+        .sum()
+}
+
+/// Keep only the cells where the path turns, the cost stays the same
+pub fn remove_collinear(path: &[u32], weights: &[f32], width: u32, height: u32) -> SmoothedPath {
+    let direction = |a: u32, b: u32| {
+        let (a, b) = (
+            Point::from_1d_index(width, a),
+            Point::from_1d_index(width, b),
+        );
+        (b.x as i64 - a.x as i64, b.y as i64 - a.y as i64)
+    };
+
+    let mut points: Vec<(f32, f32)> = path
+        .first()
+        .map(|&i| cell_center(i, width))
+        .into_iter()
+        .collect();
+
+    for step in path.windows(3) {
+        if direction(step[0], step[1]) != direction(step[1], step[2]) {
+            points.push(cell_center(step[1], width));
+        }
+    }
+
+    if path.len() > 1 {
+        points.push(cell_center(path[path.len() - 1], width));
+    }
+
+    finish(points, weights, width, height)
+}
+
+/// String pulling, from every kept cell jump straight to the farthest later cell in sight
+/// A shortcut is only taken if it is no more expensive than following the grid path, so crossing expensive terrain diagonally is not traded for a shorter line
+pub fn shortcut(path: &[u32], weights: &[f32], width: u32, height: u32) -> SmoothedPath {
+    let centers: Vec<(f32, f32)> = path.iter().map(|&i| cell_center(i, width)).collect();
+
+    // cost of the grid path up to every cell
+    let mut grid_cost = vec![0.0; centers.len()];
+    for i in 1..centers.len() {
+        grid_cost[i] = grid_cost[i - 1]
+            + segment_cost(centers[i - 1], centers[i], weights, width, height)
+                .unwrap_or(f32::INFINITY);
+    }
+
+    let mut points: Vec<(f32, f32)> = centers.first().copied().into_iter().collect();
+    let mut anchor = 0;
+
+    while anchor + 1 < centers.len() {
+        let next = (anchor + 2..centers.len())
+            .rev()
+            .find(|&j| {
+                segment_cost(centers[anchor], centers[j], weights, width, height)
+                    .is_some_and(|cost| cost <= grid_cost[j] - grid_cost[anchor] + 0.0001)
+            })
+            .unwrap_or(anchor + 1);
+
+        points.push(centers[next]);
+        anchor = next;
+    }
+
+    finish(points, weights, width, height)
+}
+
+/// Chaikin corner cutting, every corner is replaced by points at a quarter and three quarters of its segments
+/// Corners where the cut would cross a wall are kept as they are, the end points never move
+pub fn chaikin(
+    points: &[(f32, f32)],
+    iterations: u32,
+    weights: &[f32],
+    width: u32,
+    height: u32,
+) -> SmoothedPath {
+    let lerp =
+        |a: (f32, f32), b: (f32, f32), t: f32| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+
+    let mut points = points.to_vec();
+
+    for _ in 0..iterations {
+        if points.len() < 3 {
+            break;
+        }
+
+        let mut cut = vec![points[0]];
+
+        for corner in points.windows(3) {
+            let (q, r) = (
+                lerp(corner[0], corner[1], 0.75),
+                lerp(corner[1], corner[2], 0.25),
+            );
+
+            if line_of_sight(q, r, weights, width, height) {
+                cut.push(q);
+                cut.push(r);
+            } else {
+                cut.push(corner[1]);
+            }
+        }
+
+        cut.push(points[points.len() - 1]);
+        points = cut;
+    }
+
+    finish(points, weights, width, height)
+}
+
+/// Catmull-Rom spline through the points, samples points per segment
+/// Spans of the spline crossing a wall fall back to the straight segment
+pub fn catmull_rom(
+    points: &[(f32, f32)],
+    samples: u32,
+    weights: &[f32],
+    width: u32,
+    height: u32,
+) -> SmoothedPath {
+    if points.len() < 3 || samples < 2 {
+        return finish(points.to_vec(), weights, width, height);
+    }
+
+    let mut smoothed = vec![points[0]];
+
+    for i in 0..points.len() - 1 {
+        // the end points are mirrored to get the missing control points
+        let p0 = if i == 0 {
+            (
+                2.0 * points[0].0 - points[1].0,
+                2.0 * points[0].1 - points[1].1,
+            )
+        } else {
+            points[i - 1]
+        };
+        let (p1, p2) = (points[i], points[i + 1]);
+        let p3 = if i + 2 < points.len() {
+            points[i + 2]
+        } else {
+            (2.0 * p2.0 - p1.0, 2.0 * p2.1 - p1.1)
+        };
+
+        let span: Vec<(f32, f32)> = (1..=samples)
+            .map(|s| {
+                let t = s as f32 / samples as f32;
+                let (t2, t3) = (t * t, t * t * t);
+                let blend = |a: f32, b: f32, c: f32, d: f32| {
+                    0.5 * (2.0 * b
+                        + (c - a) * t
+                        + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2
+                        + (3.0 * b - a - 3.0 * c + d) * t3)
+                };
+                (blend(p0.0, p1.0, p2.0, p3.0), blend(p0.1, p1.1, p2.1, p3.1))
+            })
+            .collect();
+
+        let clear = std::iter::once(p1)
+            .chain(span.iter().copied())
+            .collect::<Vec<_>>()
+            .windows(2)
+            .all(|segment| line_of_sight(segment[0], segment[1], weights, width, height));
+
+        if clear {
+            smoothed.extend(span);
+        } else {
+            smoothed.push(p2);
+        }
+    }
+
+    finish(smoothed, weights, width, height)
+}
+
+/// Smooth every leg between the portal hops of a path on its own, the hops are kept as they are and cost the cheapest link between their ends
+/// A move is a hop if the cells are not neighbours, or a link between them is cheaper than walking
+pub fn smooth_legs(
+    path: &[u32],
+    portals: &Portals,
+    weights: &[f32],
+    width: u32,
+    mut smooth: impl FnMut(&[u32]) -> SmoothedPath,
+) -> SmoothedPath {
+    let mut smoothed = SmoothedPath {
+        points: Vec::new(),
+        cost: 0.0,
+    };
+    let mut leg_start = 0;
+
+    for i in 1..=path.len() {
+        let hop_cost = match path.get(i) {
+            Some(&to) => {
+                let from = path[i - 1];
+                let link_cost = portals
+                    .links_from(from)
+                    .iter()
+                    .filter(|link| link.to_index == to)
+                    .map(|link| link.cost)
+                    .fold(f32::INFINITY, f32::min);
+
+                let (a, b) = (
+                    Point::from_1d_index(width, from),
+                    Point::from_1d_index(width, to),
+                );
+                let is_neighbour = a.x.abs_diff(b.x) <= 1 && a.y.abs_diff(b.y) <= 1;
+
+                match is_neighbour && calculate_weight(&a, &b, weights, width) <= link_cost {
+                    true => continue,
+                    false => link_cost,
+                }
+            }
+            None => 0.0,
+        };
+
+        let leg = smooth(&path[leg_start..i]);
+        smoothed.points.extend(leg.points);
+        smoothed.cost += leg.cost + hop_cost;
+        leg_start = i;
+    }
+
+    smoothed
+}
+
+fn finish(points: Vec<(f32, f32)>, weights: &[f32], width: u32, height: u32) -> SmoothedPath {
+    SmoothedPath {
+        cost: polyline_cost(&points, weights, width, height).unwrap_or(f32::INFINITY),
+        points,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::astar::FindPath;
+
+    use super::*;
+
+    /// Open map with a wall sticking up from the bottom in the middle
+    fn walled_weights() -> Vec<f32> {
+        (0..200)
+            .map(|i| {
+                let point = Point::from_1d_index(20, i);
+                if point.x == 10 && point.y > 3 {
+                    -1.0
+                } else {
+                    1.0
+                }
+            })
+            .collect()
+    }
+
+    fn grid_path(weights: &[f32], from: Point, to: Point) -> (Vec<u32>, f32) {
+        let mut path_finder = FindPath::new(from, to, 20, 10, 1, 1.0);
+        let cost = path_finder.run(weights).unwrap();
+        (path_finder.ordered_path().unwrap(), cost)
+    }
+
+    #[test]
+    fn test_segment_cost_matches_grid() {
+        let weights: Vec<f32> = (0..16).map(|i| 1.0 + i as f32).collect();
+
+        // straight and diagonal neighbours, same as calculate_weight
+        assert_eq!(
+            Some(2.5),
+            segment_cost((1.5, 0.5), (2.5, 0.5), &weights, 4, 4)
+        );
+        let diagonal = segment_cost((0.5, 0.5), (1.5, 1.5), &weights, 4, 4).unwrap();
+        assert!((diagonal - 2.0_f32.sqrt() * 3.5).abs() < 0.0001);
+
+        let mut walled = weights.clone();
+        walled[2] = -1.0;
+        assert_eq!(None, segment_cost((0.5, 0.5), (3.5, 0.5), &walled, 4, 4));
+        assert_eq!(None, segment_cost((0.5, 0.5), (4.5, 0.5), &weights, 4, 4));
+    }
+
+    #[test]
+    fn test_remove_collinear_keeps_cost() {
+        let weights = walled_weights();
+        let (path, cost) = grid_path(&weights, Point::new(0, 9), Point::new(19, 9));
+
+        let smoothed = remove_collinear(&path, &weights, 20, 10);
+
+        assert!(smoothed.points.len() < path.len());
+        assert!((smoothed.cost - cost).abs() < 0.001);
+        assert_eq!(Some(&(0.5, 9.5)), smoothed.points.first());
+        assert_eq!(Some(&(19.5, 9.5)), smoothed.points.last());
+    }
+
+    #[test]
+    fn test_shortcut_is_cheaper_and_clear() {
+        let weights = walled_weights();
+        let (path, cost) = grid_path(&weights, Point::new(0, 9), Point::new(19, 9));
+
+        let smoothed = shortcut(&path, &weights, 20, 10);
+
+        assert!(smoothed.cost <= cost);
+        assert!(smoothed.points.len() <= 5);
+        assert_eq!(
+            Some(smoothed.cost),
+            polyline_cost(&smoothed.points, &weights, 20, 10)
+        );
+    }
+
+    #[test]
+    fn test_shortcut_avoids_expensive_terrain() {
+        // a costly patch between the start and the end, the grid path goes around it
+        let weights: Vec<f32> = (0..200)
+            .map(|i| {
+                let point = Point::from_1d_index(20, i);
+                if (5..15).contains(&point.x) && (2..8).contains(&point.y) {
+                    10.0
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+        let (path, cost) = grid_path(&weights, Point::new(0, 5), Point::new(19, 5));
+
+        let smoothed = shortcut(&path, &weights, 20, 10);
+
+        assert!(smoothed.cost <= cost + 0.001);
+    }
+
+    #[test]
+    fn test_splines_stay_clear() {
+        let weights = walled_weights();
+        let (path, _) = grid_path(&weights, Point::new(0, 9), Point::new(19, 9));
+        let pulled = shortcut(&path, &weights, 20, 10);
+
+        let smoothed = chaikin(&pulled.points, 3, &weights, 20, 10);
+        assert!(smoothed.cost.is_finite());
+        assert_eq!(pulled.points.first(), smoothed.points.first());
+        assert_eq!(pulled.points.last(), smoothed.points.last());
+
+        let smoothed = catmull_rom(&pulled.points, 8, &weights, 20, 10);
+        assert!(smoothed.cost.is_finite());
+        assert_eq!(pulled.points.first(), smoothed.points.first());
+        assert_eq!(pulled.points.last(), smoothed.points.last());
+    }
+
+    #[test]
+    fn test_chaikin_cuts_open_corners() {
+        let weights: Vec<f32> = vec![1.0; 200];
+        let turn = [(0.5, 0.5), (9.5, 0.5), (9.5, 9.5)];
+
+        let smoothed = chaikin(&turn, 2, &weights, 20, 10);
+
+        // two corners after the first round, each cut into two again
+        assert_eq!(6, smoothed.points.len());
+        assert!(smoothed.cost < polyline_cost(&turn, &weights, 20, 10).unwrap());
+    }
+
+    #[test]
+    fn test_smooth_legs_through_portal() {
+        // the wall goes all the way, the only way across is the portal
+        let weights: Vec<f32> = (0..200)
+            .map(|i| match Point::from_1d_index(20, i).x {
+                10 => -1.0,
+                _ => 1.0,
+            })
+            .collect();
+        let mut portals = Portals::new(20, 10);
+        portals.add(&Point::new(9, 2), &Point::new(11, 2), 3.0, false);
+
+        let mut path_finder = FindPath::new(Point::new(0, 9), Point::new(19, 9), 20, 10, 1, 1.0)
+            .with_portals(portals.clone());
+        let cost = path_finder.run(&weights).unwrap();
+        let path = path_finder.ordered_path().unwrap();
+
+        let collinear = smooth_legs(&path, &portals, &weights, 20, |leg| {
+            remove_collinear(leg, &weights, 20, 10)
+        });
+        assert!((collinear.cost - cost).abs() < 0.001);
+        assert!(collinear.points.contains(&(9.5, 2.5)));
+        assert!(collinear.points.contains(&(11.5, 2.5)));
+
+        let pulled = smooth_legs(&path, &portals, &weights, 20, |leg| {
+            shortcut(leg, &weights, 20, 10)
+        });
+        assert!(pulled.cost <= cost + 0.001);
+        assert_eq!(
+            vec![(0.5, 9.5), (9.5, 2.5), (11.5, 2.5), (19.5, 9.5)],
+            pulled.points
+        );
+    }
+}
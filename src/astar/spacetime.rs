@@ -0,0 +1,611 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::hybridheap::HybridHeap;
+
+use super::{
+    astar::VisitedPoint,
+    astar_utils::{
+        calculate_heuristical_distance, calculate_weight, get_neighbours, reconstruct_ordered_path,
+    },
+    point::Point,
+};
+
+/// Search state of a space-time search, the cell and the time step the cell is occupied at
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimedCell {
+    pub index: u32,
+    pub time: u32,
+}
+
+/// Obstacle following a path one cell per time step, eg a patrolling guard
+/// Looping obstacles start over from the beginning, others stay at the last cell
+#[derive(Clone, Debug)]
+pub struct MovingObstacle {
+    pub path: Vec<u32>,
+    pub looping: bool,
+}
+
+impl MovingObstacle {
+    pub fn new(path: Vec<u32>, looping: bool) -> Self {
+        MovingObstacle { path, looping }
+    }
+
+    /// Walk the path back and forth forever
+    pub fn patrol(path: Vec<u32>) -> Self {
+        let mut patrol = path.clone();
+
+        if path.len() > 2 {
+            patrol.extend(path[1..path.len() - 1].iter().rev());
+        }
+
+        MovingObstacle::new(patrol, true)
+    }
+
+    #[inline(always)]
+    pub fn position(&self, time: u32) -> Option<u32> {
+        if self.path.is_empty() {
+            return None;
+        }
+
+        let step = if self.looping {
+            time as usize % self.path.len()
+        } else {
+            (time as usize).min(self.path.len() - 1)
+        };
+
+        Some(self.path[step])
+    }
+
+    /// Whether the obstacle never enters the cell from the time onwards
+    /// Looping obstacles are checked over one full cycle, others until they stop at the last cell
+    pub fn is_free_after(&self, index: u32, time: u32) -> bool {
+        if self.looping {
+            return (0..self.path.len() as u32)
+                .all(|step| self.position(time + step) != Some(index));
+        }
+
+        let first = (time as usize).min(self.path.len().saturating_sub(1));
+        self.path
+            .get(first..)
+            .is_none_or(|rest| !rest.contains(&index))
+    }
+}
+
+/// Cells and moves taken by someone else at given time steps
+#[derive(Clone, Debug, Default)]
+pub struct ReservationTable {
+    cells: HashSet<(u32, u32)>,       // index, time
+    edges: HashSet<(u32, u32, u32)>,  // from, to, time the move starts at
+    parked: HashMap<u32, u32>,        // index, time from which the cell stays occupied
+    last_reserved: HashMap<u32, u32>, // latest reserved time of each cell, for checking if a goal can be stayed at
+    obstacles: Vec<MovingObstacle>,
+}
+
+impl ReservationTable {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn reserve_cell(&mut self, index: u32, time: u32) {
+        self.cells.insert((index, time));
+
+        let last = self.last_reserved.entry(index).or_insert(time);
+        *last = (*last).max(time);
+    }
+
+    /// Reserve moving from a cell to another starting at the time, so nobody can move the other way at the same time
+    pub fn reserve_edge(&mut self, from: u32, to: u32, time: u32) {
+        self.edges.insert((from, to, time));
+    }
+
+    /// Free a cell reserved with reserve_cell, eg a placeholder for an agent which is about to plan
+    /// The latest reserved time of the cell is kept, so is_free_after stays conservative until the table is cleared
+    pub fn release_cell(&mut self, index: u32, time: u32) {
+        self.cells.remove(&(index, time));
+    }
+
+    /// Occupy the cell from the time onwards, eg an agent which has arrived
+    pub fn park(&mut self, index: u32, from_time: u32) {
+        let parked = self.parked.entry(index).or_insert(from_time);
+        *parked = (*parked).min(from_time);
+    }
+
+    /// Reserve every cell and move of a timed path, optionally staying at the last cell for good
+    pub fn reserve_path(&mut self, path: &[TimedCell], park_at_end: bool) {
+        for cell in path {
+            self.reserve_cell(cell.index, cell.time);
+        }
+
+        for step in path.windows(2) {
+            if step[0].index != step[1].index {
+                self.reserve_edge(step[0].index, step[1].index, step[0].time);
+            }
+        }
+
+        if let (true, Some(last)) = (park_at_end, path.last()) {
+            self.park(last.index, last.time);
+        }
+    }
+
+    pub fn add_obstacle(&mut self, obstacle: MovingObstacle) {
+        self.obstacles.push(obstacle);
+    }
+
+    pub fn obstacles(&self) -> &[MovingObstacle] {
+        &self.obstacles
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.edges.clear();
+        self.parked.clear();
+        self.last_reserved.clear();
+        self.obstacles.clear();
+    }
+
+    #[inline(always)]
+    pub fn is_cell_reserved(&self, index: u32, time: u32) -> bool {
+        self.cells.contains(&(index, time))
+            || self.parked.get(&index).is_some_and(|from| *from <= time)
+            || self
+                .obstacles
+                .iter()
+                .any(|obstacle| obstacle.position(time) == Some(index))
+    }
+
+    /// Whether someone moves the opposite way at the same time, ie the two would swap places
+    #[inline(always)]
+    pub fn is_move_reserved(&self, from: u32, to: u32, time: u32) -> bool {
+        self.edges.contains(&(to, from, time))
+            || self.obstacles.iter().any(|obstacle| {
+                obstacle.position(time) == Some(to) && obstacle.position(time + 1) == Some(from)
+            })
+    }
+
+    /// Whether the cell can be stayed at from the time onwards, nothing reserved and no obstacle passing through it later
+    #[inline(always)]
+    pub fn is_free_after(&self, index: u32, time: u32) -> bool {
+        !self.parked.contains_key(&index)
+            && self
+                .last_reserved
+                .get(&index)
+                .is_none_or(|last| *last < time)
+            && self
+                .obstacles
+                .iter()
+                .all(|obstacle| obstacle.is_free_after(index, time))
+    }
+}
+
+#[derive(Clone)]
+pub struct SpaceTimeResult {
+    pub from_index: u32,
+    pub to_index: u32,
+    pub total_distance: f32,
+    pub path: Vec<TimedCell>, // one cell per time step from the start time to the arrival, both included
+    pub visited_count: u32,
+}
+
+impl SpaceTimeResult {
+    /// Cell occupied at the time, the start before the path and the goal after it
+    /// The path has a cell for every time step, so it is indexed directly
+    #[inline(always)]
+    pub fn position(&self, time: u32) -> u32 {
+        match time.checked_sub(self.path[0].time) {
+            Some(step) => self.path[(step as usize).min(self.path.len() - 1)].index,
+            None => self.from_index,
+        }
+    }
+
+    pub fn arrival_time(&self) -> u32 {
+        self.path.last().unwrap().time
+    }
+}
+
+/// Space-time A*, every move to a neighbour takes one time step and staying in place costs wait_cost per step
+/// Cells and moves reserved in the table are avoided, and the goal is only accepted once the agent can stay there for good
+/// Time steps beyond max_time are not searched, so unreachable goals dont make the search wait forever
+#[allow(clippy::too_many_arguments)]
+pub fn find_timed_path(
+    from: Point,
+    to: Point,
+    start_time: u32,
+    wait_cost: f32,
+    max_time: u32,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+    reservations: &ReservationTable,
+) -> Option<SpaceTimeResult> {
+    let target = to.clone();
+    let heuristic =
+        move |point: &Point| calculate_heuristical_distance(point, &target, multiplier, min_weight);
+
+    find_timed_path_with_heuristic(
+        from,
+        to,
+        start_time,
+        wait_cost,
+        max_time,
+        width,
+        height,
+        weights,
+        reservations,
+        heuristic,
+    )
+}
+
+/// Same as find_timed_path, with any admissible heuristic for the remaining distance to the goal
+#[allow(clippy::too_many_arguments)]
+pub fn find_timed_path_with_heuristic(
+    from: Point,
+    to: Point,
+    start_time: u32,
+    wait_cost: f32,
+    max_time: u32,
+    width: u32,
+    height: u32,
+    weights: &[f32],
+    reservations: &ReservationTable,
+    heuristic: impl FnMut(&Point) -> f32,
+) -> Option<SpaceTimeResult> {
+    let to_index = to.to_1d_index(width);
+
+    if weights[to_index as usize] < 0.0 {
+        return None;
+    }
+
+    find_timed_path_until(
+        from,
+        start_time,
+        wait_cost,
+        max_time,
+        width,
+        height,
+        weights,
+        reservations,
+        heuristic,
+        |cell| cell.index == to_index && reservations.is_free_after(to_index, cell.time),
+    )
+}
+
+/// Space-time A* which stops at the first state accepted by is_goal, eg for searching only up to a time window
+/// The to_index of the result is the cell the search stopped at
+#[allow(clippy::too_many_arguments)]
+pub fn find_timed_path_until(
+    from: Point,
+    start_time: u32,
+    wait_cost: f32,
+    max_time: u32,
+    width: u32,
+    height: u32,
+    weights: &[f32],
+    reservations: &ReservationTable,
+    mut heuristic: impl FnMut(&Point) -> f32,
+    is_goal: impl Fn(&TimedCell) -> bool,
+) -> Option<SpaceTimeResult> {
+    // negative wait costs would make waiting around cheaper than arriving
+    let wait_cost = wait_cost.max(0.0);
+
+    let mut openset: HybridHeap<TimedCell, f32> = HybridHeap::with_capacity(1000);
+    let mut g_score: HashMap<TimedCell, VisitedPoint<f32, TimedCell>> =
+        HashMap::with_capacity(1000);
+
+    let from_index = from.to_1d_index(width);
+
+    if weights[from_index as usize] < 0.0 {
+        return None;
+    }
+
+    let start = TimedCell {
+        index: from_index,
+        time: start_time,
+    };
+
+    g_score.insert(
+        start,
+        VisitedPoint {
+            score: 0.0,
+            came_from_key: start,
+        },
+    );
+    openset.push(start, heuristic(&from));
+
+    let mut visited_count = 0;
+
+    while let Some(current) = openset.pop() {
+        let current_score = g_score[&current].score;
+
+        if is_goal(&current) {
+            return Some(SpaceTimeResult {
+                from_index,
+                to_index: current.index,
+                total_distance: current_score,
+                path: reconstruct_ordered_path(&g_score, current),
+                visited_count,
+            });
+        }
+
+        visited_count += 1;
+
+        if current.time >= max_time {
+            continue;
+        }
+
+        let current_point = Point::from_1d_index(width, current.index);
+        let next_time = current.time + 1;
+
+        let moves = get_neighbours(&current_point, width, height)
+            .into_iter()
+            .map(|index| {
+                let weight = calculate_weight(
+                    &current_point,
+                    &Point::from_1d_index(width, index),
+                    weights,
+                    width,
+                );
+                (index, weight)
+            })
+            .chain(std::iter::once((current.index, wait_cost)));
+
+        for (neighbour_index, cost) in moves {
+            // wall...
+            if neighbour_index != current.index && cost <= 0.0 {
+                continue;
+            }
+
+            if reservations.is_cell_reserved(neighbour_index, next_time)
+                || reservations.is_move_reserved(current.index, neighbour_index, current.time)
+            {
+                continue;
+            }
+
+            let neighbour = TimedCell {
+                index: neighbour_index,
+                time: next_time,
+            };
+            let tentative_g_score = current_score + cost;
+
+            match g_score.get(&neighbour) {
+                Some(p) if p.score <= tentative_g_score => continue,
+                _ => g_score.insert(
+                    neighbour,
+                    VisitedPoint {
+                        score: tentative_g_score,
+                        came_from_key: current,
+                    },
+                ),
+            };
+
+            let tentative_f_score =
+                tentative_g_score + heuristic(&Point::from_1d_index(width, neighbour_index));
+
+            match openset.get_value(neighbour) {
+                Some(v) if v > tentative_f_score => {
+                    openset.change_value(neighbour, tentative_f_score)
+                }
+                Some(_) => (),
+                None => openset.push(neighbour, tentative_f_score),
+            };
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Corridor of 5 cells with a pocket below the middle one
+    fn corridor_with_pocket() -> Vec<f32> {
+        vec![
+            1.0, 1.0, 1.0, 1.0, 1.0, //
+            -1.0, -1.0, 1.0, -1.0, -1.0,
+        ]
+    }
+
+    #[test]
+    fn test_moving_obstacle_position() {
+        let patrol = MovingObstacle::patrol(vec![1, 2, 3]);
+        let positions: Vec<u32> = (0..6).map(|t| patrol.position(t).unwrap()).collect();
+        assert_eq!(vec![1, 2, 3, 2, 1, 2], positions);
+
+        let once = MovingObstacle::new(vec![1, 2], false);
+        assert_eq!(Some(2), once.position(10));
+    }
+
+    #[test]
+    fn test_swap_is_reserved() {
+        let mut reservations = ReservationTable::new();
+        reservations.add_obstacle(MovingObstacle::new(vec![1, 0], false));
+
+        assert!(reservations.is_move_reserved(0, 1, 0));
+        assert!(!reservations.is_move_reserved(0, 1, 1));
+    }
+
+    #[test]
+    fn test_waits_for_obstacle() {
+        let weights = corridor_with_pocket();
+        let mut reservations = ReservationTable::new();
+
+        // obstacle blocks the middle for three steps and then steps into the pocket
+        reservations.add_obstacle(MovingObstacle::new(vec![2, 2, 2, 7], false));
+
+        let result = find_timed_path(
+            Point::new(0, 0),
+            Point::new(4, 0),
+            0,
+            0.5,
+            20,
+            5,
+            2,
+            1,
+            1.0,
+            &weights,
+            &reservations,
+        )
+        .unwrap();
+
+        assert_eq!(4.5, result.total_distance);
+        assert_eq!(5, result.arrival_time());
+
+        for cell in &result.path {
+            assert!(!reservations.is_cell_reserved(cell.index, cell.time));
+        }
+    }
+
+    #[test]
+    fn test_steps_aside_for_reserved_path() {
+        let weights = corridor_with_pocket();
+        let mut reservations = ReservationTable::new();
+
+        // someone else walks through the corridor from right to left
+        let other: Vec<TimedCell> = [4, 3, 2, 1, 0]
+            .iter()
+            .enumerate()
+            .map(|(time, &index)| TimedCell {
+                index,
+                time: time as u32,
+            })
+            .collect();
+        reservations.reserve_path(&other, false);
+
+        let result = find_timed_path(
+            Point::new(1, 0),
+            Point::new(4, 0),
+            0,
+            0.5,
+            20,
+            5,
+            2,
+            1,
+            1.0,
+            &weights,
+            &reservations,
+        )
+        .unwrap();
+
+        // the only way past is through the pocket
+        assert!(result.path.iter().any(|cell| cell.index == 7));
+
+        for cell in &result.path {
+            assert!(!reservations.is_cell_reserved(cell.index, cell.time));
+        }
+
+        for step in result.path.windows(2) {
+            assert!(!reservations.is_move_reserved(step[0].index, step[1].index, step[0].time));
+        }
+    }
+
+    #[test]
+    fn test_goal_must_be_free_after_arrival() {
+        let weights: Vec<f32> = vec![1.0; 25];
+        let mut reservations = ReservationTable::new();
+        reservations.reserve_cell(4, 10);
+
+        let result = find_timed_path(
+            Point::new(0, 0),
+            Point::new(4, 0),
+            0,
+            0.5,
+            50,
+            5,
+            5,
+            1,
+            1.0,
+            &weights,
+            &reservations,
+        )
+        .unwrap();
+
+        assert!(result.arrival_time() > 10);
+        assert_eq!(4, result.position(100));
+    }
+
+    #[test]
+    fn test_goal_on_patrol_is_never_free() {
+        let weights: Vec<f32> = vec![1.0; 25];
+        let mut reservations = ReservationTable::new();
+
+        // guard walks the bottom row back and forth, passing the goal every cycle
+        reservations.add_obstacle(MovingObstacle::patrol(vec![20, 21, 22, 23, 24]));
+
+        assert!(!reservations.is_free_after(22, 100));
+        assert!(reservations.is_free_after(4, 0));
+
+        assert!(find_timed_path(
+            Point::new(0, 0),
+            Point::new(2, 4),
+            0,
+            0.5,
+            30,
+            5,
+            5,
+            1,
+            1.0,
+            &weights,
+            &reservations,
+        )
+        .is_none());
+
+        // a guard passing once only delays the arrival
+        let mut reservations = ReservationTable::new();
+        reservations.add_obstacle(MovingObstacle::new(vec![20, 21, 22, 23, 24, 19, 14], false));
+
+        assert!(!reservations.is_free_after(24, 2));
+        assert!(reservations.is_free_after(24, 5));
+        assert!(!reservations.is_free_after(14, 100));
+    }
+
+    #[test]
+    fn test_position_before_during_and_after_path() {
+        let weights: Vec<f32> = vec![1.0; 25];
+
+        let result = find_timed_path(
+            Point::new(0, 0),
+            Point::new(4, 0),
+            3,
+            0.5,
+            50,
+            5,
+            5,
+            1,
+            1.0,
+            &weights,
+            &ReservationTable::new(),
+        )
+        .unwrap();
+
+        assert_eq!(7, result.arrival_time());
+        assert_eq!(
+            vec![0, 0, 1, 2, 3, 4, 4],
+            [0, 3, 4, 5, 6, 7, 100].map(|time| result.position(time))
+        );
+    }
+
+    #[test]
+    fn test_unreachable_within_max_time() {
+        let weights = corridor_with_pocket();
+        let mut reservations = ReservationTable::new();
+        reservations.park(2, 0);
+        reservations.park(7, 0);
+
+        assert!(find_timed_path(
+            Point::new(0, 0),
+            Point::new(4, 0),
+            0,
+            0.5,
+            20,
+            5,
+            2,
+            1,
+            1.0,
+            &weights,
+            &reservations,
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,455 @@
+use super::{astar::FindPath, point::Point, route::RouteResult};
+
+/// Above this many targets the exact solver gets too slow and 2-opt/Or-opt is used instead
+pub const HELD_KARP_MAX_TARGETS: usize = 12;
+
+pub struct TourResult {
+    pub order: Vec<usize>, // indexes of the targets in visiting order
+    pub route: RouteResult,
+}
+
+/// Costs and paths between every pair of stops, index 0 being the start
+struct PairwisePaths {
+    costs: Vec<Vec<f32>>,
+    paths: Vec<Vec<Option<Vec<u32>>>>, // only the upper triangle is stored when symmetric, the other direction is the same path reversed
+    symmetric: bool,
+}
+
+impl PairwisePaths {
+    /// Both directions of every pair are searched unless the path finder costs the same both ways
+    fn new(stops: &[Point], path_finder: &mut FindPath, weights: &[f32]) -> Self {
+        let count = stops.len();
+        let symmetric = path_finder.is_symmetric();
+        let mut costs = vec![vec![0.0; count]; count];
+        let mut paths = vec![vec![None; count]; count];
+
+        for i in 0..count {
+            for j in 0..count {
+                if i == j || (symmetric && j < i) {
+                    continue;
+                }
+
+                path_finder.restart(stops[i].clone(), stops[j].clone());
+
+                let cost = path_finder.run(weights).unwrap_or(f32::INFINITY);
+                costs[i][j] = cost;
+                paths[i][j] = path_finder.ordered_path();
+
+                if symmetric {
+                    costs[j][i] = cost;
+                }
+            }
+        }
+
+        PairwisePaths {
+            costs,
+            paths,
+            symmetric,
+        }
+    }
+
+    fn path(&self, from: usize, to: usize) -> Vec<u32> {
+        if from < to || !self.symmetric {
+            self.paths[from][to].clone().unwrap_or_default()
+        } else {
+            let mut path = self.paths[to][from].clone().unwrap_or_default();
+            path.reverse();
+            path
+        }
+    }
+
+    /// Cost of visiting the stops in order, sequence always starts with 0
+    fn sequence_cost(&self, sequence: &[usize]) -> f32 {
+        sequence.windows(2).map(|w| self.costs[w[0]][w[1]]).sum()
+    }
+}
+
+/// Find the cheapest order of visiting all targets starting from the start point, optionally returning back to start
+/// Pairwise costs are computed with the regular search, the order is solved exactly with Held-Karp for small sets and with 2-opt and Or-opt for larger ones
+/// Returns None if there is no order where every leg can be travelled, eg a target cannot be reached or cannot be left
+#[allow(clippy::too_many_arguments)]
+pub fn find_tour(
+    from: Point,
+    targets: &[Point],
+    return_to_start: bool,
+    width: u32,
+    height: u32,
+    multiplier: u32,
+    min_weight: f32,
+    weights: &[f32],
+) -> Option<TourResult> {
+    let mut path_finder = FindPath::new(
+        from.clone(),
+        from.clone(),
+        width,
+        height,
+        multiplier,
+        min_weight,
+    );
+
+    find_tour_with(&mut path_finder, from, targets, return_to_start, weights)
+}
+
+/// Same as find_tour, using an existing path finder, eg one with portals
+pub fn find_tour_with(
+    path_finder: &mut FindPath,
+    from: Point,
+    targets: &[Point],
+    return_to_start: bool,
+    weights: &[f32],
+) -> Option<TourResult> {
+    let stops: Vec<Point> = std::iter::once(from)
+        .chain(targets.iter().cloned())
+        .collect();
+    let pairwise = PairwisePaths::new(&stops, path_finder, weights);
+
+    if pairwise.costs[0].iter().any(|c| c.is_infinite()) {
+        return None;
+    }
+
+    let sequence = if targets.len() <= HELD_KARP_MAX_TARGETS {
+        held_karp(&pairwise.costs, return_to_start)
+    } else {
+        improve_sequence(
+            nearest_neighbour(&pairwise.costs, return_to_start),
+            &pairwise,
+        )
+    };
+
+    // with one-way moves a target reachable from the start may still be a dead end, the best sequence then has an unreachable leg or misses stops
+    if sequence.len() < stops.len() || pairwise.sequence_cost(&sequence).is_infinite() {
+        return None;
+    }
+
+    let mut path: Vec<u32> = Vec::new();
+    let mut leg_costs: Vec<f32> = Vec::with_capacity(sequence.len());
+
+    for leg in sequence.windows(2) {
+        leg_costs.push(pairwise.costs[leg[0]][leg[1]]);
+
+        // the first cell of a leg is the last cell of the previous one
+        let skip = if path.is_empty() { 0 } else { 1 };
+        path.extend(pairwise.path(leg[0], leg[1]).into_iter().skip(skip));
+    }
+
+    Some(TourResult {
+        order: sequence
+            .iter()
+            .filter(|&&stop| stop != 0)
+            .map(|stop| stop - 1)
+            .collect(),
+        route: RouteResult {
+            path,
+            total_distance: leg_costs.iter().sum(),
+            leg_costs,
+        },
+    })
+}
+
+/// Exact solution, O(2^n * n^2)
+/// Returns the sequence of stops starting with 0, and ending with 0 if returning to start
+fn held_karp(costs: &[Vec<f32>], return_to_start: bool) -> Vec<usize> {
+    let targets = costs.len() - 1;
+
+    if targets == 0 {
+        return if return_to_start { vec![0, 0] } else { vec![0] };
+    }
+
+    let full_mask = (1usize << targets) - 1;
+
+    // best[mask][last] is the cheapest way of visiting the targets in mask, starting from 0 and ending at target last
+    let mut best = vec![vec![f32::INFINITY; targets]; full_mask + 1];
+    let mut parent = vec![vec![usize::MAX; targets]; full_mask + 1];
+
+    for last in 0..targets {
+        best[1 << last][last] = costs[0][last + 1];
+    }
+
+    for mask in 1..=full_mask {
+        for last in 0..targets {
+            if mask & (1 << last) == 0 || best[mask][last].is_infinite() {
+                continue;
+            }
+
+            for next in 0..targets {
+                if mask & (1 << next) != 0 {
+                    continue;
+                }
+
+                let next_mask = mask | (1 << next);
+                let cost = best[mask][last] + costs[last + 1][next + 1];
+
+                if cost < best[next_mask][next] {
+                    best[next_mask][next] = cost;
+                    parent[next_mask][next] = last;
+                }
+            }
+        }
+    }
+
+    let closing_cost = |last: usize| {
+        if return_to_start {
+            costs[last + 1][0]
+        } else {
+            0.0
+        }
+    };
+
+    let mut last = (0..targets)
+        .min_by(|&a, &b| {
+            (best[full_mask][a] + closing_cost(a))
+                .total_cmp(&(best[full_mask][b] + closing_cost(b)))
+        })
+        .unwrap();
+
+    let mut mask = full_mask;
+    let mut sequence = Vec::with_capacity(targets + 2);
+
+    while last != usize::MAX {
+        sequence.push(last + 1);
+        let previous = parent[mask][last];
+        mask &= !(1 << last);
+        last = previous;
+    }
+
+    sequence.push(0);
+    sequence.reverse();
+
+    if return_to_start {
+        sequence.push(0);
+    }
+
+    sequence
+}
+
+/// Greedy starting point for the improvement heuristics
+fn nearest_neighbour(costs: &[Vec<f32>], return_to_start: bool) -> Vec<usize> {
+    let mut visited = vec![false; costs.len()];
+    let mut sequence = vec![0];
+    visited[0] = true;
+
+    while sequence.len() < costs.len() {
+        let current = *sequence.last().unwrap();
+        let next = (0..costs.len())
+            .filter(|&i| !visited[i])
+            .min_by(|&a, &b| costs[current][a].total_cmp(&costs[current][b]))
+            .unwrap();
+
+        visited[next] = true;
+        sequence.push(next);
+    }
+
+    if return_to_start {
+        sequence.push(0);
+    }
+
+    sequence
+}
+
+/// Apply 2-opt and Or-opt moves until neither improves the sequence any more
+/// The first stop, and the last one when returning to start, are kept in place
+fn improve_sequence(mut sequence: Vec<usize>, pairwise: &PairwisePaths) -> Vec<usize> {
+    let fixed_end = sequence.len() > 1 && sequence.last() == Some(&0);
+    let last_movable = sequence.len() - if fixed_end { 2 } else { 1 };
+
+    let mut best_cost = pairwise.sequence_cost(&sequence);
+    let mut improved = true;
+
+    while improved {
+        improved = false;
+
+        // 2-opt, reverse a segment
+        for i in 1..last_movable {
+            for k in (i + 1)..=last_movable {
+                sequence[i..=k].reverse();
+                let cost = pairwise.sequence_cost(&sequence);
+
+                if cost < best_cost - f32::EPSILON {
+                    best_cost = cost;
+                    improved = true;
+                } else {
+                    sequence[i..=k].reverse();
+                }
+            }
+        }
+
+        // Or-opt, move a segment of 1 to 3 stops elsewhere, either way around
+        for segment_length in 1..=3 {
+            let mut i = 1;
+
+            while i + segment_length - 1 <= last_movable {
+                let mut candidate = sequence.clone();
+                let segment: Vec<usize> = candidate.drain(i..i + segment_length).collect();
+                let reversed_segment: Vec<usize> = segment.iter().rev().copied().collect();
+                let mut moved = false;
+
+                'insert: for insert_at in 1..=(last_movable + 1 - segment_length) {
+                    for segment in [&segment, &reversed_segment] {
+                        let mut attempt = candidate.clone();
+                        attempt.splice(insert_at..insert_at, segment.iter().copied());
+                        let cost = pairwise.sequence_cost(&attempt);
+
+                        if cost < best_cost - f32::EPSILON {
+                            best_cost = cost;
+                            sequence = attempt;
+                            improved = true;
+                            moved = true;
+                            break 'insert;
+                        }
+                    }
+                }
+
+                if !moved {
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::astar::portals::Portals;
+
+    use super::*;
+
+    fn corners() -> Vec<Point> {
+        vec![
+            Point::new(9, 9),
+            Point::new(9, 0),
+            Point::new(0, 9),
+            Point::new(5, 0),
+        ]
+    }
+
+    #[test]
+    fn test_find_tour_small() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let result = find_tour(
+            Point::new(0, 0),
+            &corners(),
+            false,
+            10,
+            10,
+            1,
+            1.0,
+            &weights,
+        )
+        .unwrap();
+
+        assert_eq!(vec![3, 1, 0, 2], result.order);
+        assert_eq!(27.0, result.route.total_distance);
+        assert_eq!(Some(&0), result.route.path.first());
+        assert_eq!(Some(&90), result.route.path.last());
+    }
+
+    #[test]
+    fn test_find_tour_return_to_start() {
+        let weights: Vec<f32> = vec![1.0; 100];
+
+        let result =
+            find_tour(Point::new(0, 0), &corners(), true, 10, 10, 1, 1.0, &weights).unwrap();
+
+        assert_eq!(36.0, result.route.total_distance);
+        assert_eq!(5, result.route.leg_costs.len());
+        assert_eq!(Some(&0), result.route.path.last());
+    }
+
+    #[test]
+    fn test_find_tour_unreachable() {
+        let mut weights: Vec<f32> = vec![1.0; 100];
+        weights[99] = -1.0;
+
+        assert!(find_tour(
+            Point::new(0, 0),
+            &corners(),
+            false,
+            10,
+            10,
+            1,
+            1.0,
+            &weights
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_find_tour_one_way_portal() {
+        // a wall splits the corridor, the only way across is a one-way portal
+        let mut weights: Vec<f32> = vec![1.0; 10];
+        weights[5] = -1.0;
+
+        let mut portals = Portals::new(10, 1);
+        portals.add(&Point::new(4, 0), &Point::new(6, 0), 1.0, false);
+
+        let mut path_finder =
+            FindPath::new(Point::new(0, 0), Point::new(0, 0), 10, 1, 1, 1.0).with_portals(portals);
+
+        let there = find_tour_with(
+            &mut path_finder,
+            Point::new(0, 0),
+            &[Point::new(9, 0)],
+            false,
+            &weights,
+        )
+        .unwrap();
+        assert_eq!(8.0, there.route.total_distance);
+        assert_eq!(Some(&9), there.route.path.last());
+
+        // no way back
+        assert!(find_tour_with(
+            &mut path_finder,
+            Point::new(0, 0),
+            &[Point::new(9, 0)],
+            true,
+            &weights,
+        )
+        .is_none());
+
+        // the target on the far side has to come last
+        let tour = find_tour_with(
+            &mut path_finder,
+            Point::new(0, 0),
+            &[Point::new(8, 0), Point::new(2, 0)],
+            false,
+            &weights,
+        )
+        .unwrap();
+        assert_eq!(vec![1, 0], tour.order);
+    }
+
+    #[test]
+    fn test_improve_matches_held_karp() {
+        let weights: Vec<f32> = vec![1.0; 400];
+        let targets: Vec<Point> = [
+            (3, 17),
+            (15, 2),
+            (8, 8),
+            (19, 19),
+            (0, 10),
+            (12, 14),
+            (6, 1),
+        ]
+        .iter()
+        .map(|&(x, y)| Point::new(x, y))
+        .collect();
+
+        let mut stops = vec![Point::new(0, 0)];
+        stops.extend(targets.iter().cloned());
+
+        let mut path_finder = FindPath::new(Point::new(0, 0), Point::new(0, 0), 20, 20, 1, 1.0);
+        let pairwise = PairwisePaths::new(&stops, &mut path_finder, &weights);
+
+        let exact = pairwise.sequence_cost(&held_karp(&pairwise.costs, false));
+        let heuristic = pairwise.sequence_cost(&improve_sequence(
+            nearest_neighbour(&pairwise.costs, false),
+            &pairwise,
+        ));
+
+        assert!(heuristic >= exact);
+        assert!(heuristic <= exact * 1.1);
+    }
+}
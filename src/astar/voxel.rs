@@ -0,0 +1,398 @@
+use std::collections::HashSet;
+
+use bmp::Image;
+
+use crate::{astar::point3d::Point3, utils::image_layers_to_weight_map};
+
+use super::{
+    astar::{FindPath, PathResult},
+    oneway::ALL_EXITS,
+    point::Point,
+    portals::Portals,
+};
+
+/// Exit mask allowing only the moves along the axes, east, south, west and north in HEADINGS
+const AXIS_EXITS: u8 = 0b0101_0101;
+
+/// Exit mask bits of the headings leaving a cell upwards and downwards in HEADINGS
+const UP_EXITS: u8 = 0b1110_0000;
+const DOWN_EXITS: u8 = 0b0000_1110;
+
+/// Which cells count as neighbours in the voxel grid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Only the faces, ie left, right, front, back, up and down
+    Six,
+    /// Faces, edges and corners
+    TwentySix,
+}
+
+/// Multi floor map where each floor is a regular 2d weight map stacked on top of each other
+/// Moving between floors is only possible where a vertical transition (stairs, ladder...) has been added
+pub struct VoxelMap {
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub weights: Vec<f32>,
+    vertical_transitions: HashSet<u32>, // indexes of the lower cell of each transition
+}
+
+impl VoxelMap {
+    /// None if there is not one weight per cell
+    pub fn new(width: u32, height: u32, depth: u32, weights: Vec<f32>) -> Option<Self> {
+        if (width * height * depth) as usize != weights.len() {
+            return None;
+        }
+
+        Some(VoxelMap {
+            width,
+            height,
+            depth,
+            weights,
+            vertical_transitions: HashSet::new(),
+        })
+    }
+
+    /// Create map from a stack of images, first image is the ground floor
+    /// None if there are no images or they are not all the same size
+    pub fn from_layers(
+        layers: &[Image],
+        min_output_weight: f32,
+        max_output_weight: f32,
+    ) -> Option<Self> {
+        let width = layers.first()?.get_width();
+        let height = layers.first()?.get_height();
+
+        if layers
+            .iter()
+            .any(|layer| layer.get_width() != width || layer.get_height() != height)
+        {
+            return None;
+        }
+
+        VoxelMap::new(
+            width,
+            height,
+            layers.len() as u32,
+            image_layers_to_weight_map(layers, min_output_weight, max_output_weight),
+        )
+    }
+
+    /// Allow moving between the floor of the specified point and the floor above it
+    /// The transition works in both directions, ie stairs and ladders can be used to go down as well
+    pub fn add_vertical_transition(&mut self, lower: &Point3) {
+        self.vertical_transitions
+            .insert(lower.to_1d_index(self.width, self.height));
+    }
+
+    pub fn remove_vertical_transition(&mut self, lower: &Point3) {
+        self.vertical_transitions
+            .remove(&lower.to_1d_index(self.width, self.height));
+    }
+
+    pub fn is_vertical_transition(&self, point: &Point3) -> bool {
+        self.vertical_transitions
+            .contains(&point.to_1d_index(self.width, self.height))
+    }
+
+    /// Get the indexes of neighbouring cells, oob indexes are naturally not returned
+    /// Cells on other floors are only returned if the lower of the two cells is a vertical transition
+    pub fn get_neighbours(&self, point: &Point3, connectivity: Connectivity) -> Vec<u32> {
+        let mut neighbours: Vec<u32> = Vec::with_capacity(match connectivity {
+            Connectivity::Six => 6,
+            Connectivity::TwentySix => 26,
+        });
+
+        for dz in -1..=1i32 {
+            for dy in -1..=1i32 {
+                for dx in -1..=1i32 {
+                    let axes_moved = dx.abs() + dy.abs() + dz.abs();
+
+                    if axes_moved == 0 || (connectivity == Connectivity::Six && axes_moved > 1) {
+                        continue;
+                    }
+
+                    let x = point.x as i32 + dx;
+                    let y = point.y as i32 + dy;
+                    let z = point.z as i32 + dz;
+
+                    if x < 0
+                        || y < 0
+                        || z < 0
+                        || x >= self.width as i32
+                        || y >= self.height as i32
+                        || z >= self.depth as i32
+                    {
+                        continue;
+                    }
+
+                    let neighbour = Point3::new(x as u32, y as u32, z as u32);
+
+                    if dz != 0 {
+                        let lower = if dz > 0 { point } else { &neighbour };
+
+                        if !self.is_vertical_transition(lower) {
+                            continue;
+                        }
+                    }
+
+                    neighbours.push(neighbour.to_1d_index(self.width, self.height));
+                }
+            }
+        }
+
+        neighbours
+    }
+
+    /// Calculates the weight from one cell to a neighbour, same as the 2d version but the step length can be 1, sqrt(2) or sqrt(3)
+    #[inline(always)]
+    pub fn calculate_weight(&self, from: &Point3, to: &Point3) -> f32 {
+        let to_weight = self.weights[to.to_1d_index(self.width, self.height) as usize];
+
+        if to_weight < 0.0 {
+            return to_weight;
+        }
+
+        let from_weight = self.weights[from.to_1d_index(self.width, self.height) as usize];
+
+        let axes_moved =
+            (from.x != to.x) as u32 + (from.y != to.y) as u32 + (from.z != to.z) as u32;
+        let step_length = (axes_moved as f32).sqrt();
+
+        (to_weight / 2.0 + from_weight / 2.0) * step_length
+    }
+
+    /// Point in the map of stacked floors, the index is the same as the index of the 3d point
+    fn stacked_point(&self, point: &Point3) -> Point {
+        Point::new(point.x, point.z * self.height + point.y)
+    }
+
+    /// Links from each vertical transition to the cells above it it leads to, and back
+    fn transition_portals(&self, connectivity: Connectivity) -> Portals {
+        let mut portals = Portals::new(self.width, self.height * self.depth);
+
+        for &index in &self.vertical_transitions {
+            let lower = Point3::from_1d_index(self.width, self.height, index);
+
+            for upper_index in self.get_neighbours(&lower, connectivity) {
+                let upper = Point3::from_1d_index(self.width, self.height, upper_index);
+
+                // walls at either end
+                if upper.z == lower.z
+                    || self.weights[index as usize] < 0.0
+                    || self.weights[upper_index as usize] < 0.0
+                {
+                    continue;
+                }
+
+                portals.add(
+                    &self.stacked_point(&lower),
+                    &self.stacked_point(&upper),
+                    self.calculate_weight(&lower, &upper),
+                    true,
+                );
+            }
+        }
+
+        portals
+    }
+
+    /// Moves along the floors only, and only along the axes with six connectivity
+    fn floor_exit_masks(&self, connectivity: Connectivity) -> Vec<u8> {
+        let floor_mask = match connectivity {
+            Connectivity::Six => AXIS_EXITS,
+            Connectivity::TwentySix => ALL_EXITS,
+        };
+
+        (0..self.width * self.height * self.depth)
+            .map(|index| match (index / self.width) % self.height {
+                0 if self.height == 1 => floor_mask & !UP_EXITS & !DOWN_EXITS,
+                0 => floor_mask & !UP_EXITS,
+                y if y == self.height - 1 => floor_mask & !DOWN_EXITS,
+                _ => floor_mask,
+            })
+            .collect()
+    }
+}
+
+/// Find path across floors \o/
+/// The floors are searched as a single 2d map with the floors stacked below each other, which is what the indexes are already.
+/// Exit masks keep the search from walking off the edge of a floor onto the next one, and the vertical transitions are portals between the floors
+pub fn find_path_3d(
+    from: Point3,
+    to: Point3,
+    map: &VoxelMap,
+    connectivity: Connectivity,
+    multiplier: u32,
+    min_weight: f32,
+) -> Option<PathResult> {
+    let mut path_finder = FindPath::new(
+        map.stacked_point(&from),
+        map.stacked_point(&to),
+        map.width,
+        map.height * map.depth,
+        multiplier,
+        min_weight,
+    )
+    .with_portals(map.transition_portals(connectivity))
+    .with_exit_masks(map.floor_exit_masks(connectivity));
+
+    path_finder.run(&map.weights)?;
+    path_finder.into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_floors() -> VoxelMap {
+        VoxelMap::new(5, 5, 2, vec![1.0; 50]).unwrap()
+    }
+
+    #[test]
+    fn test_new_rejects_wrong_weight_count() {
+        assert!(VoxelMap::new(5, 5, 2, vec![1.0; 49]).is_none());
+    }
+
+    #[test]
+    fn test_index_roundtrip() {
+        let point = Point3::new(3, 2, 1);
+        let index = point.to_1d_index(5, 5);
+
+        assert_eq!(38, index);
+
+        let actual = Point3::from_1d_index(5, 5, index);
+        assert_eq!((3, 2, 1), (actual.x, actual.y, actual.z));
+    }
+
+    #[test]
+    fn test_get_neighbours_without_transitions() {
+        let map = two_floors();
+
+        assert_eq!(
+            vec![7, 11, 13, 17],
+            map.get_neighbours(&Point3::new(2, 2, 0), Connectivity::Six)
+        );
+        assert_eq!(
+            8,
+            map.get_neighbours(&Point3::new(2, 2, 0), Connectivity::TwentySix)
+                .len()
+        );
+    }
+
+    #[test]
+    fn test_get_neighbours_with_transition() {
+        let mut map = two_floors();
+        map.add_vertical_transition(&Point3::new(2, 2, 0));
+
+        assert_eq!(
+            vec![7, 11, 13, 17, 37],
+            map.get_neighbours(&Point3::new(2, 2, 0), Connectivity::Six)
+        );
+        assert_eq!(
+            vec![12],
+            map.get_neighbours(&Point3::new(2, 2, 1), Connectivity::Six)
+                .into_iter()
+                .filter(|i| *i < 25)
+                .collect::<Vec<u32>>()
+        );
+    }
+
+    #[test]
+    fn test_find_path_3d_no_stairs() {
+        let map = two_floors();
+
+        let result = find_path_3d(
+            Point3::new(0, 0, 0),
+            Point3::new(4, 4, 1),
+            &map,
+            Connectivity::Six,
+            1,
+            1.0,
+        );
+
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_find_path_3d_stairs() {
+        let mut map = two_floors();
+        map.add_vertical_transition(&Point3::new(4, 0, 0));
+
+        let result = find_path_3d(
+            Point3::new(0, 0, 0),
+            Point3::new(4, 4, 1),
+            &map,
+            Connectivity::Six,
+            1,
+            1.0,
+        )
+        .unwrap();
+
+        assert_eq!(9.0, result.total_distance);
+        assert!(result.path_indexes.contains(&4));
+        assert!(result.path_indexes.contains(&29));
+    }
+
+    #[test]
+    fn test_find_path_3d_diagonal_stairs() {
+        let mut map = two_floors();
+        map.add_vertical_transition(&Point3::new(2, 2, 0));
+
+        let result = find_path_3d(
+            Point3::new(0, 0, 0),
+            Point3::new(4, 4, 1),
+            &map,
+            Connectivity::TwentySix,
+            1,
+            1.0,
+        )
+        .unwrap();
+
+        // two diagonal steps to the stairs, diagonally up them and one more diagonal step
+        let expected = 3.0 * 2.0_f32.sqrt() + 3.0_f32.sqrt();
+        assert!((expected - result.total_distance).abs() < 0.0001);
+        assert!(result.path_indexes.contains(&12));
+        assert!(result.path_indexes.contains(&43));
+    }
+
+    #[test]
+    fn test_find_path_3d_stays_on_floor() {
+        // walking off the bottom of the ground floor would lead straight to the target
+        let map = two_floors();
+
+        let result = find_path_3d(
+            Point3::new(2, 4, 0),
+            Point3::new(2, 0, 1),
+            &map,
+            Connectivity::TwentySix,
+            1,
+            1.0,
+        );
+
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_from_layers() {
+        let mut ground = Image::new(3, 2);
+        let mut first = Image::new(3, 2);
+        // black is a wall, white the cheapest terrain
+        ground.set_pixel(1, 0, bmp::Pixel::new(255, 255, 255));
+        first.set_pixel(2, 1, bmp::Pixel::new(255, 255, 255));
+
+        let map = VoxelMap::from_layers(&[ground.clone(), first.clone()], 1.0, 10.0).unwrap();
+
+        assert_eq!((3, 2, 2), (map.width, map.height, map.depth));
+        assert_eq!(12, map.weights.len());
+        assert_eq!(-1.0, map.weights[0]);
+        assert_eq!(1.0, map.weights[1]);
+        assert_eq!(
+            1.0,
+            map.weights[Point3::new(2, 1, 1).to_1d_index(3, 2) as usize]
+        );
+
+        assert!(VoxelMap::from_layers(&[], 1.0, 10.0).is_none());
+        assert!(VoxelMap::from_layers(&[ground, Image::new(2, 3)], 1.0, 10.0).is_none());
+    }
+}
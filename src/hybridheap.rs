@@ -11,6 +11,12 @@ pub struct HybridHeap<K: Eq + Hash + PartialEq + Copy, V: PartialOrd + Copy> {
     hashmap: HashMap<K, usize>,
 }
 
+impl<K: Eq + Hash + PartialEq + Copy, V: PartialOrd + Copy> Default for HybridHeap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<K: Eq + Hash + PartialEq + Copy, V: PartialOrd + Copy> HybridHeap<K, V> {
     /// Create new hybrid heap with no specified capacity. Stuff will be allocated when pushed
     pub fn new() -> Self {
@@ -39,20 +45,18 @@ impl<K: Eq + Hash + PartialEq + Copy, V: PartialOrd + Copy> HybridHeap<K, V> {
                 key: item.key,
                 value: new_value,
             };
-            Some(self.bubble_up(index))
+            self.bubble_up(index);
         } else if item.value < new_value {
             self.items[index] = HeapItem {
                 key: item.key,
                 value: new_value,
             };
-            Some(self.bubble_down(index))
-        } else {
-            None
-        };
+            self.bubble_down(index);
+        }
     }
 
     pub fn contains_key(&self, key: &K) -> bool {
-        self.hashmap.contains_key(&key)
+        self.hashmap.contains_key(key)
     }
 
     /// Get value of item
@@ -68,13 +72,13 @@ impl<K: Eq + Hash + PartialEq + Copy, V: PartialOrd + Copy> HybridHeap<K, V> {
 
     /// Pop item
     pub fn pop(&mut self) -> Option<K> {
-        match self.items.get(0) {
+        match self.items.first() {
             Some(item) => {
                 let key = item.key;
                 self.hashmap.remove(&item.key);
 
                 if let Some(last) = self.items.pop() {
-                    if self.items.len() > 0 {
+                    if !self.items.is_empty() {
                         self.items[0] = last;
                         self.bubble_down(0);
                     };
@@ -88,7 +92,21 @@ impl<K: Eq + Hash + PartialEq + Copy, V: PartialOrd + Copy> HybridHeap<K, V> {
 
     /// Peek item without removing it
     pub fn peek(&self) -> Option<&K> {
-        Some(&self.items.get(0)?.key)
+        Some(&self.items.first()?.key)
+    }
+
+    /// Peek the value of the top item without removing it
+    pub fn peek_value(&self) -> Option<V> {
+        Some(self.items.first()?.value)
+    }
+
+    /// Keys of all items in no particular order
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.items.iter().map(|item| &item.key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
     }
 
     pub fn is_empty(&self) -> bool {
@@ -104,14 +122,14 @@ impl<K: Eq + Hash + PartialEq + Copy, V: PartialOrd + Copy> HybridHeap<K, V> {
         let mut index = index;
         let item = self.items[index];
 
-        let mut parent_index = index.checked_sub(1).unwrap_or(0) / 2;
+        let mut parent_index = index.saturating_sub(1) / 2;
 
         while index > 0 && self.items[parent_index].value >= item.value {
             self.hashmap.insert(self.items[parent_index].key, index);
 
             self.items.swap(index, parent_index);
             index = parent_index;
-            parent_index = index.checked_sub(1).unwrap_or(0) / 2;
+            parent_index = index.saturating_sub(1) / 2;
         }
 
         self.items[index] = HeapItem {
@@ -173,7 +191,7 @@ mod tests {
 
         heap.push(&some_item, 1);
 
-        assert_eq!(false, heap.is_empty())
+        assert!(!heap.is_empty())
     }
 
     #[test]
@@ -186,7 +204,7 @@ mod tests {
         heap.push(&some_item, 2);
         heap.push(&some_other_item, 1);
 
-        assert_eq!(false, heap.is_empty())
+        assert!(!heap.is_empty())
     }
 
     #[test]
@@ -203,10 +221,10 @@ mod tests {
             Some(item) => {
                 assert_eq!(2, item.some_value)
             }
-            None => assert!(false),
+            None => panic!("expected an item"),
         };
 
-        assert_eq!(false, heap.is_empty());
+        assert!(!heap.is_empty());
     }
 
     #[test]
@@ -219,8 +237,8 @@ mod tests {
 
         heap.push(some_item, 2);
 
-        assert_eq!(true, heap.contains_key(&some_item));
-        assert_eq!(false, heap.contains_key(&some_other_item));
+        assert!(heap.contains_key(&some_item));
+        assert!(!heap.contains_key(&some_other_item));
     }
 
     #[test]
@@ -307,10 +325,10 @@ mod tests {
 
         assert_eq!(Some(&"second"), heap.peek());
 
-        heap.change_value(&"third", 2);
+        heap.change_value("third", 2);
         assert_eq!(Some(&"third"), heap.peek());
 
-        heap.change_value(&"first", 1);
+        heap.change_value("first", 1);
         assert_eq!(Some(&"first"), heap.peek());
     }
 
@@ -326,7 +344,7 @@ mod tests {
         println!("{:?}", heap.items);
         println!("{:?}", heap.hashmap);
 
-        heap.change_value(&"second", 100);
+        heap.change_value("second", 100);
         println!("{:?}", heap.items);
         println!("{:?}", heap.hashmap);
         assert_eq!(Some(&"first"), heap.peek());
@@ -340,7 +358,7 @@ mod tests {
         heap.push("second", 2);
 
         assert_eq!(Some(&"second"), heap.peek());
-        assert_eq!("second", heap.items.get(0).unwrap().key);
+        assert_eq!("second", heap.items.first().unwrap().key);
         assert_eq!("first", heap.items.get(1).unwrap().key);
 
         println!("{:?}", heap.items);
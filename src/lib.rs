@@ -2,16 +2,49 @@ pub mod astar;
 pub mod hybridheap;
 pub mod utils;
 
-use std::vec;
+use std::{collections::HashSet, sync::Arc, vec};
 
-use astar::{astar::FindPath, point::Point};
+use astar::{
+    alternatives::{k_shortest_paths_with, penalty_alternatives_with, AlternativePath},
+    anytime::AnytimeFindPath,
+    astar::FindPath,
+    batch::{run_query, QueryResult},
+    cache::PathCache,
+    clearance::{clearance_map, has_clearance, update_clearance, SINGLE_CELL_AGENT_RADIUS},
+    components::{Components, NO_COMPONENT},
+    cooperative::CooperativeAStar,
+    dijkstra::{distance_field, DistanceField},
+    elevation::Elevation,
+    flowfield::FlowField,
+    hybrid_astar::{find_vehicle_path, Pose, VehicleConfig, VehiclePath},
+    landmarks::{LandmarkStrategy, Landmarks},
+    mapf::find_multi_agent_paths,
+    oneway::{image_to_exit_masks, ALL_EXITS},
+    point::Point,
+    portals::Portals,
+    route::{find_route_with, RouteResult},
+    smoothing::{catmull_rom, chaikin, remove_collinear, shortcut, smooth_legs, SmoothedPath},
+    spacetime::{find_timed_path, MovingObstacle, ReservationTable, SpaceTimeResult},
+    tsp::find_tour_with,
+};
 use utils::image_to_vec;
 use wasm_bindgen::prelude::*;
 
-use crate::utils::{image_to_weight_map, set_panic_hook};
+use crate::utils::{heatmap_color, image_to_height_map, image_to_weight_map, set_panic_hook};
 
 const TERRAIN_MIN_WEIGHT: f32 = 1.0;
 const TERRAIN_MAX_WEIGHT: f32 = 10.0;
+const ISOCHRONE_INTERVAL: f32 = 10.0;
+const MAPF_MAX_NODES: u32 = 2000;
+const ANYTIME_EPSILON_STEP: f32 = 0.5;
+const PATH_CACHE_CAPACITY: usize = 1000;
+const ALTERNATIVE_COLORS: [(u8, u8, u8); 5] = [
+    (230, 25, 75),
+    (60, 180, 75),
+    (0, 130, 200),
+    (245, 130, 48),
+    (145, 30, 180),
+];
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
@@ -28,6 +61,36 @@ pub struct Board {
     start_pixel: Option<Point>,
     cell_weights: Vec<f32>,
     path_finder: Option<FindPath>,
+    portals: Portals,
+    distance_field: Option<DistanceField>,
+    flow_field: Option<FlowField>,
+    route: Option<RouteResult>,
+    route_stops: Vec<u32>,
+    alternatives: Vec<AlternativePath>,
+    clearance: Vec<f32>,
+    agent_radius: f32,
+    show_clearance: bool,
+    vehicle_path: Option<VehiclePath>,
+    elevation: Option<Elevation>,
+    exit_masks: Vec<u8>,
+    reservations: ReservationTable,
+    timed_path: Option<SpaceTimeResult>,
+    time: u32,
+    agent_paths: Vec<SpaceTimeResult>,
+    cooperative: Option<CooperativeAStar>,
+    anytime: Option<AnytimeFindPath>,
+    landmarks: Option<Arc<Landmarks>>,
+    components: Arc<Components>,
+    show_components: bool,
+    smoothed_path: Option<SmoothedPath>,
+    batch: Vec<QueryResult>,
+    path_cache: PathCache<(u32, u32)>, // keyed by multiplier and agent radius bits
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[wasm_bindgen]
@@ -38,6 +101,9 @@ impl Board {
         let image = bmp::from_reader(&mut bytes).unwrap();
         let image_data = image_to_vec(&image);
         let cell_weights = image_to_weight_map(&image, TERRAIN_MIN_WEIGHT, TERRAIN_MAX_WEIGHT);
+        let exit_masks = image_to_exit_masks(&image);
+        let clearance = clearance_map(&cell_weights, image.get_width(), image.get_height());
+        let components = Components::new(image.get_width(), image.get_height(), &cell_weights);
 
         Board {
             frame_data: vec![0; (image.get_width() * image.get_height() * 4) as usize],
@@ -47,6 +113,30 @@ impl Board {
             start_pixel: None,
             cell_weights,
             path_finder: None,
+            portals: Portals::new(image.get_width(), image.get_height()),
+            distance_field: None,
+            flow_field: None,
+            route: None,
+            route_stops: Vec::new(),
+            alternatives: Vec::new(),
+            clearance,
+            agent_radius: SINGLE_CELL_AGENT_RADIUS,
+            show_clearance: false,
+            vehicle_path: None,
+            elevation: None,
+            exit_masks,
+            reservations: ReservationTable::new(),
+            timed_path: None,
+            time: 0,
+            agent_paths: Vec::new(),
+            cooperative: None,
+            anytime: None,
+            landmarks: None,
+            components: Arc::new(components),
+            show_components: false,
+            smoothed_path: None,
+            batch: Vec::new(),
+            path_cache: PathCache::new(PATH_CACHE_CAPACITY, image.get_width(), TERRAIN_MIN_WEIGHT),
         }
     }
 
@@ -63,16 +153,76 @@ impl Board {
 
         self.frame_data.clone_from(&self.image_data);
 
+        if self.show_clearance {
+            let max_clearance = self.clearance.iter().copied().fold(1.0, f32::max);
+
+            for (index, clearance) in self.clearance.iter().enumerate() {
+                let (r, g, b) = heatmap_color(clearance / max_clearance);
+                let i = index * 4;
+
+                // cells the current agent cannot enter are dimmed
+                let shade = if has_clearance(*clearance, self.agent_radius) {
+                    1.0
+                } else {
+                    0.4
+                };
+
+                self.frame_data[i] = (r as f32 * shade) as u8;
+                self.frame_data[i + 1] = (g as f32 * shade) as u8;
+                self.frame_data[i + 2] = (b as f32 * shade) as u8;
+                self.frame_data[i + 3] = 255;
+            }
+        }
+
+        // every component in its own color, spread out with the golden ratio so neighbouring ids look different
+        if self.show_components {
+            for (index, label) in self.components.labels().iter().enumerate() {
+                if *label == NO_COMPONENT {
+                    continue;
+                }
+
+                let (r, g, b) = heatmap_color((*label as f32 * 0.618034).fract());
+                let i = index * 4;
+                self.frame_data[i] = self.frame_data[i] / 2 + r / 2;
+                self.frame_data[i + 1] = self.frame_data[i + 1] / 2 + g / 2;
+                self.frame_data[i + 2] = self.frame_data[i + 2] / 2 + b / 2;
+                self.frame_data[i + 3] = 255;
+            }
+        }
+
+        // isochrone heatmap, every other band is a bit darker
+        if let Some(field) = &self.distance_field {
+            let max_distance = field.max_distance().max(f32::EPSILON);
+
+            for (index, distance) in field.distances.iter().enumerate() {
+                if !distance.is_finite() {
+                    continue;
+                }
+
+                let (r, g, b) = heatmap_color(distance / max_distance);
+                let shade = if (distance / ISOCHRONE_INTERVAL) as u32 & 1 == 0 {
+                    1.0
+                } else {
+                    0.75
+                };
+
+                let i = index * 4;
+                self.frame_data[i] = ((self.frame_data[i] / 2 + r / 2) as f32 * shade) as u8;
+                self.frame_data[i + 1] =
+                    ((self.frame_data[i + 1] / 2 + g / 2) as f32 * shade) as u8;
+                self.frame_data[i + 2] =
+                    ((self.frame_data[i + 2] / 2 + b / 2) as f32 * shade) as u8;
+                self.frame_data[i + 3] = 255;
+            }
+        }
+
         if let Some(p) = &self.path_finder {
             for i in p.visited_points().keys().map(|key| key * 4) {
-                self.frame_data[i as usize] =
-                    self.frame_data[i as usize].checked_sub(40).unwrap_or(0);
-                self.frame_data[(i + 1) as usize] = self.frame_data[(i + 1) as usize]
-                    .checked_sub(40)
-                    .unwrap_or(0);
-                self.frame_data[(i + 2) as usize] = self.frame_data[(i + 2) as usize]
-                    .checked_sub(40)
-                    .unwrap_or(0);
+                self.frame_data[i as usize] = self.frame_data[i as usize].saturating_sub(40);
+                self.frame_data[(i + 1) as usize] =
+                    self.frame_data[(i + 1) as usize].saturating_sub(40);
+                self.frame_data[(i + 2) as usize] =
+                    self.frame_data[(i + 2) as usize].saturating_sub(40);
                 self.frame_data[(i + 3) as usize] = 255;
             }
 
@@ -85,15 +235,151 @@ impl Board {
                 }
             }
 
-            self.frame_data[(p.to_index * 4) as usize] = 255;
-            self.frame_data[((p.to_index * 4) + 1) as usize] = 0;
-            self.frame_data[((p.to_index * 4) + 2) as usize] = 0;
+            for i in p.goal_indexes().iter().map(|v| v * 4) {
+                self.frame_data[i as usize] = 255;
+                self.frame_data[(i + 1) as usize] = 0;
+                self.frame_data[(i + 2) as usize] = 0;
+            }
 
             self.frame_data[(p.from_index * 4) as usize] = 0;
             self.frame_data[((p.from_index * 4) + 1) as usize] = 255;
             self.frame_data[((p.from_index * 4) + 2) as usize] = 0;
         }
 
+        // cells expanded in the current epsilon round and the best path so far
+        if let Some(anytime) = &self.anytime {
+            for i in anytime.closed_points().map(|index| (index * 4) as usize) {
+                self.frame_data[i] = self.frame_data[i].saturating_sub(40);
+                self.frame_data[i + 1] = self.frame_data[i + 1].saturating_sub(40);
+                self.frame_data[i + 2] = self.frame_data[i + 2].saturating_sub(40);
+                self.frame_data[i + 3] = 255;
+            }
+
+            if let Some(path) = anytime.ordered_path() {
+                for i in path.iter().map(|v| (v * 4) as usize) {
+                    self.frame_data[i] = 100;
+                    self.frame_data[i + 1] = 100;
+                    self.frame_data[i + 2] = 100;
+                    self.frame_data[i + 3] = 255;
+                }
+            }
+        }
+
+        if let Some(route) = &self.route {
+            for i in route.path.iter().map(|v| v * 4) {
+                self.frame_data[i as usize] = 255;
+                self.frame_data[(i + 1) as usize] = 140;
+                self.frame_data[(i + 2) as usize] = 0;
+                self.frame_data[(i + 3) as usize] = 255;
+            }
+        }
+
+        // worst first so the best alternative ends up on top
+        for (n, alternative) in self.alternatives.iter().enumerate().rev() {
+            let (r, g, b) = ALTERNATIVE_COLORS[n % ALTERNATIVE_COLORS.len()];
+
+            for i in alternative.path.iter().map(|v| v * 4) {
+                self.frame_data[i as usize] = r;
+                self.frame_data[(i + 1) as usize] = g;
+                self.frame_data[(i + 2) as usize] = b;
+                self.frame_data[(i + 3) as usize] = 255;
+            }
+        }
+
+        for i in self.route_stops.iter().map(|v| v * 4) {
+            self.frame_data[i as usize] = 255;
+            self.frame_data[(i + 1) as usize] = 255;
+            self.frame_data[(i + 2) as usize] = 0;
+            self.frame_data[(i + 3) as usize] = 255;
+        }
+
+        for i in self.portals.endpoint_indexes().iter().map(|v| v * 4) {
+            self.frame_data[i as usize] = 180;
+            self.frame_data[(i + 1) as usize] = 0;
+            self.frame_data[(i + 2) as usize] = 255;
+            self.frame_data[(i + 3) as usize] = 255;
+        }
+
+        // remaining part of the timed path, the moving obstacles and the agent at the current time
+        if let Some(timed_path) = &self.timed_path {
+            let remaining: Vec<u32> = timed_path
+                .path
+                .iter()
+                .filter(|cell| cell.time >= self.time)
+                .map(|cell| cell.index)
+                .collect();
+
+            for index in remaining {
+                self.set_pixel(index, (255, 200, 120));
+            }
+        }
+
+        let obstacle_positions: Vec<u32> = self
+            .reservations
+            .obstacles()
+            .iter()
+            .filter_map(|obstacle| obstacle.position(self.time))
+            .collect();
+
+        for index in obstacle_positions {
+            self.set_pixel(index, (255, 0, 255));
+        }
+
+        if let Some(timed_path) = &self.timed_path {
+            self.set_pixel(timed_path.position(self.time), (0, 255, 255));
+        }
+
+        // every agent in its own color, the path dimmed and the position at the current time in full
+        for n in 0..self.agent_paths.len() {
+            let (r, g, b) = ALTERNATIVE_COLORS[n % ALTERNATIVE_COLORS.len()];
+            let path: Vec<u32> = self.agent_paths[n]
+                .path
+                .iter()
+                .filter(|cell| cell.time >= self.time)
+                .map(|cell| cell.index)
+                .collect();
+
+            for index in path {
+                self.set_pixel(index, (r / 2 + 127, g / 2 + 127, b / 2 + 127));
+            }
+        }
+
+        for n in 0..self.agent_paths.len() {
+            let position = self.agent_paths[n].position(self.time);
+            self.set_pixel(position, ALTERNATIVE_COLORS[n % ALTERNATIVE_COLORS.len()]);
+        }
+
+        if let Some(cooperative) = &self.cooperative {
+            let planned: Vec<(usize, Vec<u32>)> = (0..cooperative.agent_count())
+                .map(|n| (n, cooperative.planned_path(n)))
+                .collect();
+            let positions = cooperative.positions();
+
+            for (n, path) in planned {
+                let (r, g, b) = ALTERNATIVE_COLORS[n % ALTERNATIVE_COLORS.len()];
+
+                for index in path {
+                    self.set_pixel(index, (r / 2 + 127, g / 2 + 127, b / 2 + 127));
+                }
+            }
+
+            for (n, index) in positions.into_iter().enumerate() {
+                self.set_pixel(index, ALTERNATIVE_COLORS[n % ALTERNATIVE_COLORS.len()]);
+            }
+        }
+
+        for n in 0..self.batch.len() {
+            for index in self.batch[n].path.clone() {
+                self.set_pixel(index, ALTERNATIVE_COLORS[n % ALTERNATIVE_COLORS.len()]);
+            }
+        }
+
+        if let Some(landmarks) = self.landmarks.clone() {
+            for &index in landmarks.indexes() {
+                self.set_pixel(index, (255, 0, 255));
+            }
+        }
+
         if let Some(pixel) = &self.start_pixel {
             let pixel_index = (pixel.to_1d_index(width) * 4) as usize;
             self.frame_data[pixel_index] = 0;
@@ -113,21 +399,934 @@ impl Board {
 
     /// Get cell info... currently just the weight
     pub fn get_cell_info(&mut self, x: u32, y: u32) -> Option<f32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
         let index = Point::new(x, y).to_1d_index(self.width);
         self.cell_weights.get(index as usize).copied()
     }
 
+    /// Change the weight of a cell, negative for a wall
+    /// Other weights are clamped to the terrain range, so a cell can't be free to cross
+    /// The clearance and connected components follow the change, landmarks are dropped if the cell got cheaper since they would overestimate
+    /// The distance and flow fields are dropped, compute them again for the new terrain
+    pub fn set_cell_weight(&mut self, x: u32, y: u32, weight: f32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        if weight.is_nan() {
+            return;
+        }
+
+        let weight = if weight < 0.0 {
+            weight
+        } else {
+            weight.clamp(TERRAIN_MIN_WEIGHT, TERRAIN_MAX_WEIGHT)
+        };
+
+        let index = Point::new(x, y).to_1d_index(self.width);
+        let previous = self.cell_weights[index as usize];
+
+        if previous == weight {
+            return;
+        }
+
+        self.cell_weights[index as usize] = weight;
+
+        // the image is what is rendered, so it is painted to match
+        let brightness = if weight < 0.0 {
+            0
+        } else {
+            (255.0 * (TERRAIN_MAX_WEIGHT - weight) / (TERRAIN_MAX_WEIGHT - TERRAIN_MIN_WEIGHT))
+                .clamp(13.0, 255.0) as u8
+        };
+        let i = (index * 4) as usize;
+        self.image_data[i..i + 3].fill(brightness);
+
+        Arc::make_mut(&mut self.components).update_cell(index, &self.cell_weights);
+        if (previous < 0.0) != (weight < 0.0) {
+            update_clearance(
+                &mut self.clearance,
+                &self.cell_weights,
+                self.width,
+                self.height,
+                index,
+            );
+        }
+
+        if previous < 0.0 || (weight >= 0.0 && weight < previous) {
+            self.landmarks = None;
+        }
+
+        // distances to the sources and the target may change anywhere
+        self.distance_field = None;
+        self.flow_field = None;
+
+        // portals can lead anywhere, so the cheaper path region of the cache does not hold with them
+        if self.portals.is_empty() {
+            self.path_cache.invalidate_cell(index, previous, weight);
+        } else {
+            self.path_cache.clear();
+        }
+    }
+
+    pub fn set_show_components(&mut self, show_components: bool) {
+        self.show_components = show_components;
+    }
+
+    /// Number of connected areas of passable cells
+    pub fn component_count(&self) -> usize {
+        self.components.count()
+    }
+
+    /// Add portal between two cells, traversing it costs the specified amount
+    /// Returns false if the cost isnt positive or either end is off the board
+    pub fn add_portal(&mut self, from: Point, to: Point, cost: f32, bidirectional: bool) -> bool {
+        if !self.portals.add(&from, &to, cost, bidirectional) {
+            return false;
+        }
+
+        self.path_cache.clear();
+        true
+    }
+
+    pub fn clear_portals(&mut self) {
+        self.portals.clear();
+        self.path_cache.clear();
+    }
+
+    /// Radius of the agent in cells used when starting searches, 0.5 being a regular single cell agent
+    pub fn set_agent_radius(&mut self, agent_radius: f32) {
+        self.agent_radius = agent_radius.max(SINGLE_CELL_AGENT_RADIUS);
+    }
+
+    pub fn set_show_clearance(&mut self, show_clearance: bool) {
+        self.show_clearance = show_clearance;
+    }
+
+    /// Use the heights, one per cell, as an elevation layer for the following searches
+    /// Climbing and descending cost extra per unit of height, moves steeper than max_slope are impassable
+    /// Returns false and keeps the current elevation if there is not one height per cell
+    pub fn set_elevation(
+        &mut self,
+        heights: Vec<f32>,
+        uphill_cost: f32,
+        downhill_cost: f32,
+        max_slope: f32,
+    ) -> bool {
+        if heights.len() != (self.width * self.height) as usize {
+            return false;
+        }
+
+        self.elevation = Some(Elevation::new(
+            heights,
+            uphill_cost,
+            downhill_cost,
+            max_slope,
+        ));
+        self.path_cache.clear();
+        true
+    }
+
+    /// Same as set_elevation, with the heights read from a bmp image, black being 0 and white max_height
+    /// Returns false and keeps the current elevation if the bytes are not a bmp of the board size
+    pub fn set_elevation_from_image(
+        &mut self,
+        bmp_bytes: &[u8],
+        max_height: f32,
+        uphill_cost: f32,
+        downhill_cost: f32,
+        max_slope: f32,
+    ) -> bool {
+        let mut bytes = bmp_bytes;
+        let Ok(image) = bmp::from_reader(&mut bytes) else {
+            return false;
+        };
+
+        if image.get_width() != self.width || image.get_height() != self.height {
+            return false;
+        }
+
+        self.set_elevation(
+            image_to_height_map(&image, max_height),
+            uphill_cost,
+            downhill_cost,
+            max_slope,
+        )
+    }
+
+    pub fn clear_elevation(&mut self) {
+        self.elevation = None;
+        self.path_cache.clear();
+    }
+
+    /// Set which headings a cell can be left in, bit n being HEADINGS[n], see oneway
+    pub fn set_exit_mask(&mut self, x: u32, y: u32, mask: u8) {
+        if x < self.width && y < self.height {
+            self.exit_masks[Point::new(x, y).to_1d_index(self.width) as usize] = mask;
+            self.path_cache.clear();
+        }
+    }
+
+    /// Cells outside the board have no restrictions
+    pub fn get_exit_mask(&self, x: u32, y: u32) -> u8 {
+        if x >= self.width || y >= self.height {
+            return ALL_EXITS;
+        }
+
+        self.exit_masks
+            .get(Point::new(x, y).to_1d_index(self.width) as usize)
+            .copied()
+            .unwrap_or(ALL_EXITS)
+    }
+
+    /// Exit masks of all cells, for drawing the one-way arrows
+    pub fn exit_masks(&self) -> *const u8 {
+        self.exit_masks.as_ptr()
+    }
+
     pub fn start_path_find(&mut self, from: Point, to: Point, multiplier: u32) {
-        self.path_finder = Some(FindPath::new(
+        let path_finder = FindPath::new(
+            from,
+            to,
+            self.width,
+            self.height,
+            multiplier,
+            TERRAIN_MIN_WEIGHT,
+        )
+        .with_portals(self.portals.clone());
+
+        self.anytime = None;
+        self.smoothed_path = None;
+        self.path_finder = Some(self.with_board_settings(path_finder));
+    }
+
+    /// Start search towards the nearest of many goals, goals are given as flat x, y pairs
+    /// Returns false and leaves the current search alone if there are no goals
+    pub fn start_multi_target_path_find(
+        &mut self,
+        from: Point,
+        goals: &[u32],
+        multiplier: u32,
+    ) -> bool {
+        let goals: Vec<Point> = goals
+            .chunks_exact(2)
+            .map(|c| Point::new(c[0], c[1]))
+            .collect();
+
+        if goals.is_empty() {
+            return false;
+        }
+
+        let path_finder = FindPath::new_multi_target(
+            from,
+            goals,
+            self.width,
+            self.height,
+            multiplier,
+            TERRAIN_MIN_WEIGHT,
+        )
+        .with_portals(self.portals.clone());
+
+        self.anytime = None;
+        self.smoothed_path = None;
+        self.path_finder = Some(self.with_board_settings(path_finder));
+        true
+    }
+
+    /// The goal reached by the current search, None if not found (yet)
+    pub fn reached_goal(&self) -> Option<Point> {
+        self.path_finder.as_ref()?.reached_goal()
+    }
+
+    /// Compute distance to the nearest source for every cell, sources are given as flat x, y pairs
+    pub fn compute_distance_field(&mut self, sources: &[u32]) {
+        let sources: Vec<Point> = sources
+            .chunks_exact(2)
+            .map(|c| Point::new(c[0], c[1]))
+            .collect();
+
+        self.distance_field = Some(distance_field(
+            &sources,
+            self.width,
+            self.height,
+            &self.cell_weights,
+        ));
+    }
+
+    pub fn clear_distance_field(&mut self) {
+        self.distance_field = None;
+    }
+
+    /// Distance from the cell to the nearest source of the distance field, None if there is no field, the cell is off the board or unreachable
+    pub fn get_distance(&self, x: u32, y: u32) -> Option<f32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let distance = self.distance_field.as_ref()?.distance(&Point::new(x, y));
+        distance.is_finite().then_some(distance)
+    }
+
+    /// Compute flow field towards the target, eg a rally point for many units
+    pub fn compute_flow_field(&mut self, x: u32, y: u32) {
+        self.flow_field = Some(FlowField::new(
+            Point::new(x, y),
+            self.width,
+            self.height,
+            &self.cell_weights,
+        ));
+    }
+
+    pub fn clear_flow_field(&mut self) {
+        self.flow_field = None;
+    }
+
+    pub fn has_flow_field(&self) -> bool {
+        self.flow_field.is_some()
+    }
+
+    /// Flat dx, dy pairs for every cell, width * height * 2 values. Null if there is no flow field
+    pub fn flow_field_directions(&self) -> *const i8 {
+        match &self.flow_field {
+            Some(flow_field) => flow_field.directions.as_ptr(),
+            None => std::ptr::null(),
+        }
+    }
+
+    /// Next cell towards the flow field target from the specified position, None off the board
+    pub fn flow_field_next(&self, x: u32, y: u32) -> Option<Point> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        self.flow_field.as_ref()?.next_cell(&Point::new(x, y))
+    }
+
+    /// Find route through the stops in order, stops are given as flat x, y pairs with the first being the start and the last the end
+    /// Stops off the board are moved to the nearest cell on it
+    /// Returns the total distance, None if any of the legs cannot be completed
+    pub fn find_route(&mut self, stops: &[u32], multiplier: u32) -> Option<f32> {
+        let stops: Vec<Point> = stops
+            .chunks_exact(2)
+            .map(|c| self.clamp_to_board(c[0], c[1]))
+            .collect();
+
+        self.route = None;
+        self.route_stops = stops.iter().map(|p| p.to_1d_index(self.width)).collect();
+
+        if stops.len() < 2 {
+            return None;
+        }
+
+        let mut path_finder = self.with_board_settings(
+            FindPath::new(
+                stops[0].clone(),
+                stops[1].clone(),
+                self.width,
+                self.height,
+                multiplier,
+                TERRAIN_MIN_WEIGHT,
+            )
+            .with_portals(self.portals.clone()),
+        );
+
+        self.route = find_route_with(&mut path_finder, &stops, &self.cell_weights);
+        self.route.as_ref().map(|route| route.total_distance)
+    }
+
+    /// Find the cheapest order of visiting all targets from the start, targets are given as flat x, y pairs
+    /// Returns the visiting order as indexes into the targets, the route is rendered like a regular route
+    pub fn find_tour(
+        &mut self,
+        from: Point,
+        targets: &[u32],
+        return_to_start: bool,
+        multiplier: u32,
+    ) -> Option<Vec<u32>> {
+        let from = self.clamp_to_board(from.x, from.y);
+        let targets: Vec<Point> = targets
+            .chunks_exact(2)
+            .map(|c| self.clamp_to_board(c[0], c[1]))
+            .collect();
+
+        self.route = None;
+        self.route_stops = std::iter::once(&from)
+            .chain(targets.iter())
+            .map(|p| p.to_1d_index(self.width))
+            .collect();
+
+        let mut path_finder = self.with_board_settings(
+            FindPath::new(
+                from.clone(),
+                from.clone(),
+                self.width,
+                self.height,
+                multiplier,
+                TERRAIN_MIN_WEIGHT,
+            )
+            .with_portals(self.portals.clone()),
+        );
+
+        let tour = find_tour_with(
+            &mut path_finder,
+            from,
+            &targets,
+            return_to_start,
+            &self.cell_weights,
+        )?;
+
+        self.route = Some(tour.route);
+        Some(tour.order.into_iter().map(|i| i as u32).collect())
+    }
+
+    /// Find path where each 45 degree turn costs the penalty, the path is rendered like a route
+    pub fn find_path_with_turn_penalty(
+        &mut self,
+        from: Point,
+        to: Point,
+        turn_penalty: f32,
+        multiplier: u32,
+    ) -> Option<f32> {
+        let (from, to) = (
+            self.clamp_to_board(from.x, from.y),
+            self.clamp_to_board(to.x, to.y),
+        );
+
+        self.path_finder = None;
+        self.route_stops = vec![from.to_1d_index(self.width), to.to_1d_index(self.width)];
+
+        let mut path_finder = self.with_board_settings(
+            FindPath::new(
+                from,
+                to,
+                self.width,
+                self.height,
+                multiplier,
+                TERRAIN_MIN_WEIGHT,
+            )
+            .with_portals(self.portals.clone())
+            .with_turn_penalty(turn_penalty, None),
+        );
+
+        self.route = path_finder
+            .run(&self.cell_weights)
+            .map(|total_distance| RouteResult {
+                path: path_finder.ordered_path().unwrap(),
+                leg_costs: vec![total_distance],
+                total_distance,
+            });
+
+        self.route.as_ref().map(|route| route.total_distance)
+    }
+
+    /// Find a drivable path for a car like vehicle between the cell centers, headings are in radians
+    /// Returns the cost, the poses are read with vehicle_path_poses. None if there is no path or the turning radius is not positive
+    pub fn find_vehicle_path(
+        &mut self,
+        from: Point,
+        from_heading: f32,
+        to: Point,
+        to_heading: f32,
+        min_turning_radius: f32,
+        allow_reverse: bool,
+    ) -> Option<f32> {
+        let config = VehicleConfig {
+            min_turning_radius,
+            allow_reverse,
+            ..Default::default()
+        };
+
+        self.vehicle_path = find_vehicle_path(
+            Pose::new(from.x as f32 + 0.5, from.y as f32 + 0.5, from_heading),
+            Pose::new(to.x as f32 + 0.5, to.y as f32 + 0.5, to_heading),
+            &config,
+            self.width,
+            self.height,
+            TERRAIN_MIN_WEIGHT,
+            &self.cell_weights,
+        );
+
+        self.vehicle_path.as_ref().map(|path| path.total_cost)
+    }
+
+    /// Poses of the vehicle path as flat x, y, theta triplets in cell coordinates
+    pub fn vehicle_path_poses(&self) -> Vec<f32> {
+        match &self.vehicle_path {
+            Some(path) => path
+                .poses
+                .iter()
+                .flat_map(|pose| [pose.x, pose.y, pose.theta])
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn clear_vehicle_path(&mut self) {
+        self.vehicle_path = None;
+    }
+
+    /// Cost of each leg of the current route
+    pub fn route_leg_costs(&self) -> Vec<f32> {
+        match &self.route {
+            Some(route) => route.leg_costs.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn clear_route(&mut self) {
+        self.route = None;
+        self.route_stops.clear();
+    }
+
+    /// Find up to k best loopless paths between the points, returns the cost of each in ascending order
+    pub fn find_alternatives(
+        &mut self,
+        from: Point,
+        to: Point,
+        k: u32,
+        multiplier: u32,
+    ) -> Vec<f32> {
+        let mut path_finder = self.with_board_settings(
+            FindPath::new(
+                from,
+                to,
+                self.width,
+                self.height,
+                multiplier,
+                TERRAIN_MIN_WEIGHT,
+            )
+            .with_portals(self.portals.clone()),
+        );
+
+        self.alternatives = k_shortest_paths_with(&mut path_finder, k as usize, &self.cell_weights);
+        self.alternatives
+            .iter()
+            .map(|alternative| alternative.total_distance)
+            .collect()
+    }
+
+    /// Find diverse alternatives by penalizing the cells of each found path, returns the cost of each
+    /// The board weights are left untouched
+    pub fn find_diverse_alternatives(
+        &mut self,
+        from: Point,
+        to: Point,
+        max_routes: u32,
+        penalty_factor: f32,
+        max_overlap: f32,
+        multiplier: u32,
+    ) -> Vec<f32> {
+        let mut path_finder = self.with_board_settings(
+            FindPath::new(
+                from,
+                to,
+                self.width,
+                self.height,
+                multiplier,
+                TERRAIN_MIN_WEIGHT,
+            )
+            .with_portals(self.portals.clone()),
+        );
+
+        self.alternatives = penalty_alternatives_with(
+            &mut path_finder,
+            max_routes as usize,
+            penalty_factor,
+            max_overlap,
+            &self.cell_weights,
+        );
+        self.alternatives
+            .iter()
+            .map(|alternative| alternative.total_distance)
+            .collect()
+    }
+
+    pub fn clear_alternatives(&mut self) {
+        self.alternatives.clear();
+    }
+
+    /// Add a guard walking back and forth along the shortest path between the points, returns false if there is no path
+    pub fn add_patrol(&mut self, from: Point, to: Point) -> bool {
+        let mut path_finder = self.with_board_settings(FindPath::new(
+            from,
+            to,
+            self.width,
+            self.height,
+            1,
+            TERRAIN_MIN_WEIGHT,
+        ));
+
+        if path_finder.run(&self.cell_weights).is_none() {
+            return false;
+        }
+
+        self.reservations
+            .add_obstacle(MovingObstacle::patrol(path_finder.ordered_path().unwrap()));
+        true
+    }
+
+    pub fn clear_obstacles(&mut self) {
+        self.reservations.clear();
+        self.timed_path = None;
+        self.time = 0;
+    }
+
+    /// Find a path avoiding the moving obstacles, starting at time 0, returns the cost
+    /// Waiting in place costs wait_cost per time step
+    pub fn find_timed_path(
+        &mut self,
+        from: Point,
+        to: Point,
+        wait_cost: f32,
+        multiplier: u32,
+    ) -> Option<f32> {
+        self.time = 0;
+        self.timed_path = find_timed_path(
             from,
             to,
+            0,
+            wait_cost,
+            (self.width + self.height) * 4,
             self.width,
             self.height,
             multiplier,
             TERRAIN_MIN_WEIGHT,
+            &self.cell_weights,
+            &self.reservations,
+        );
+
+        self.timed_path.as_ref().map(|result| result.total_distance)
+    }
+
+    /// Time step at which the agent reaches the end of the timed path, None if there is no timed path
+    pub fn arrival_time(&self) -> Option<u32> {
+        self.timed_path.as_ref().map(|result| result.arrival_time())
+    }
+
+    pub fn clear_timed_path(&mut self) {
+        self.timed_path = None;
+    }
+
+    /// Collision free paths for many agents, queries are flat start x, start y, goal x, goal y quadruplets
+    /// Returns the sum of costs, or None if the agents cant all reach their goals
+    pub fn find_multi_agent_paths(
+        &mut self,
+        queries: &[u32],
+        wait_cost: f32,
+        multiplier: u32,
+    ) -> Option<f32> {
+        let agents: Vec<(Point, Point)> = queries
+            .chunks_exact(4)
+            .map(|q| (Point::new(q[0], q[1]), Point::new(q[2], q[3])))
+            .collect();
+
+        self.time = 0;
+        self.agent_paths = Vec::new();
+
+        let result = find_multi_agent_paths(
+            &agents,
+            wait_cost,
+            MAPF_MAX_NODES,
+            self.width,
+            self.height,
+            multiplier,
+            TERRAIN_MIN_WEIGHT,
+            &self.cell_weights,
+        )?;
+
+        self.agent_paths = result.paths;
+        Some(result.total_distance)
+    }
+
+    pub fn clear_agents(&mut self) {
+        self.agent_paths.clear();
+    }
+
+    /// Start moving many agents with windowed cooperative A*, queries are flat start x, start y, goal x, goal y quadruplets
+    /// Agents starting or ending off the board or in a wall, or in the same cell as an earlier agent, are left out
+    pub fn start_cooperative(&mut self, queries: &[u32], window: u32, wait_cost: f32) {
+        let mut starts: HashSet<u32> = HashSet::new();
+        let mut goals: HashSet<u32> = HashSet::new();
+
+        let agents: Vec<(Point, Point)> = queries
+            .chunks_exact(4)
+            .map(|q| (Point::new(q[0], q[1]), Point::new(q[2], q[3])))
+            .filter(|(start, goal)| {
+                if [start, goal]
+                    .iter()
+                    .any(|point| point.x >= self.width || point.y >= self.height)
+                {
+                    return false;
+                }
+
+                let start_index = start.to_1d_index(self.width);
+                let goal_index = goal.to_1d_index(self.width);
+
+                self.cell_weights[start_index as usize] >= 0.0
+                    && self.cell_weights[goal_index as usize] >= 0.0
+                    && !starts.contains(&start_index)
+                    && !goals.contains(&goal_index)
+                    && starts.insert(start_index)
+                    && goals.insert(goal_index)
+            })
+            .collect();
+
+        self.cooperative = Some(CooperativeAStar::new(
+            &agents,
+            window,
+            wait_cost,
+            self.width,
+            self.height,
+            TERRAIN_MIN_WEIGHT,
         ));
     }
 
+    /// Plan at most max_plans agents and move them once all have planned, returns true when all have arrived
+    pub fn cooperative_tick(&mut self, max_plans: u32) -> bool {
+        match self.cooperative.as_mut() {
+            Some(cooperative) => cooperative.tick(max_plans, &self.cell_weights),
+            None => true,
+        }
+    }
+
+    pub fn clear_cooperative(&mut self) {
+        self.cooperative = None;
+    }
+
+    /// Time step at which the obstacles and the agent are rendered
+    pub fn set_time(&mut self, time: u32) {
+        self.time = time;
+    }
+
+    pub fn time(&self) -> u32 {
+        self.time
+    }
+
+    /// Start an anytime search which first finds a path with the heuristic inflated by initial_epsilon and then keeps improving it
+    /// Portals, elevation, one-way cells and the agent radius apply like in start_path_find
+    pub fn start_anytime_path_find(&mut self, from: Point, to: Point, initial_epsilon: f32) {
+        let path_finder = FindPath::new(from, to, self.width, self.height, 1, TERRAIN_MIN_WEIGHT)
+            .with_portals(self.portals.clone());
+
+        self.path_finder = None;
+        self.anytime = Some(AnytimeFindPath::with_path_finder(
+            self.with_board_settings(path_finder),
+            initial_epsilon,
+            ANYTIME_EPSILON_STEP,
+        ));
+    }
+
+    /// Expand at most ticks cells of the anytime search, returns true once the path is optimal or the target is unreachable
+    pub fn anytime_tick(&mut self, ticks: u32) -> bool {
+        match self.anytime.as_mut() {
+            Some(anytime) => anytime.tick(ticks, &self.cell_weights),
+            None => true,
+        }
+    }
+
+    /// Cost of the best path of the anytime search so far
+    pub fn anytime_cost(&self) -> Option<f32> {
+        self.anytime.as_ref()?.best_cost()
+    }
+
+    /// The best path of the anytime search costs at most this many times the optimal cost
+    pub fn anytime_bound(&self) -> Option<f32> {
+        self.anytime.as_ref()?.suboptimality_bound()
+    }
+
+    pub fn clear_anytime(&mut self) {
+        self.anytime = None;
+    }
+
+    /// Precompute distance tables for count landmarks, path finding uses them for the ALT heuristic from then on
+    pub fn compute_landmarks(&mut self, count: usize, avoid: bool) {
+        let strategy = match avoid {
+            true => LandmarkStrategy::Avoid,
+            false => LandmarkStrategy::Farthest,
+        };
+
+        self.landmarks = Some(Arc::new(Landmarks::select(
+            count,
+            strategy,
+            self.width,
+            self.height,
+            &self.cell_weights,
+        )));
+        // the heuristic changed, which can pick another of equally cheap paths
+        self.path_cache.clear();
+    }
+
+    pub fn has_landmarks(&self) -> bool {
+        self.landmarks.is_some()
+    }
+
+    /// Precomputed landmarks for storing, so they dont have to be computed on every load
+    pub fn landmarks_to_bytes(&self) -> Option<Vec<u8>> {
+        Some(self.landmarks.as_ref()?.to_bytes())
+    }
+
+    /// Returns false if the bytes are not landmarks for a map of this size
+    pub fn load_landmarks(&mut self, bytes: &[u8]) -> bool {
+        match Landmarks::from_bytes(bytes) {
+            Some(landmarks) if landmarks.width == self.width && landmarks.height == self.height => {
+                self.landmarks = Some(Arc::new(landmarks));
+                self.path_cache.clear();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn clear_landmarks(&mut self) {
+        self.landmarks = None;
+        self.path_cache.clear();
+    }
+
+    /// Find paths for many queries at once, given as flat from x, from y, to x, to y, points outside the map are moved to the nearest edge
+    /// Results are cached until the map changes around them, so repeated queries are free
+    /// Returns the distance of each query, -1 if unreachable
+    pub fn find_paths(&mut self, queries: &[u32], multiplier: u32) -> Vec<f32> {
+        let queries: Vec<(Point, Point)> = queries
+            .chunks_exact(4)
+            .map(|q| {
+                (
+                    self.clamp_to_board(q[0], q[1]),
+                    self.clamp_to_board(q[2], q[3]),
+                )
+            })
+            .collect();
+
+        let options = (multiplier, self.agent_radius.to_bits());
+
+        // clearance changes up to the agent radius away from the changed cell
+        let margin = match self.agent_radius > SINGLE_CELL_AGENT_RADIUS {
+            true => (self.agent_radius + 0.5).ceil() as u32,
+            false => 0,
+        };
+
+        let mut path_finder: Option<FindPath> = None;
+        self.batch = Vec::with_capacity(queries.len());
+
+        for (from, to) in &queries {
+            let (from_index, to_index) = (from.to_1d_index(self.width), to.to_1d_index(self.width));
+
+            if let Some(cached) = self.path_cache.get(from_index, to_index, &options) {
+                self.batch.push(QueryResult {
+                    path: cached.path.clone(),
+                    total_distance: cached.total_distance,
+                    nodes_visited_count: 0,
+                });
+                continue;
+            }
+
+            // only set up when something is not in the cache
+            let path_finder = path_finder.get_or_insert_with(|| {
+                self.with_board_settings(
+                    FindPath::new(
+                        from.clone(),
+                        to.clone(),
+                        self.width,
+                        self.height,
+                        multiplier,
+                        TERRAIN_MIN_WEIGHT,
+                    )
+                    .with_portals(self.portals.clone()),
+                )
+            });
+
+            let result = run_query(path_finder, from, to, &self.cell_weights);
+            self.path_cache.insert(
+                from_index,
+                to_index,
+                options,
+                result.path.clone(),
+                result.total_distance,
+                margin,
+            );
+            self.batch.push(result);
+        }
+
+        self.batch
+            .iter()
+            .map(|result| result.total_distance.unwrap_or(-1.0))
+            .collect()
+    }
+
+    /// Number of cells visited by each query of the last batch
+    pub fn batch_visited_counts(&self) -> Vec<u32> {
+        self.batch
+            .iter()
+            .map(|result| result.nodes_visited_count)
+            .collect()
+    }
+
+    pub fn clear_batch(&mut self) {
+        self.batch = Vec::new();
+    }
+
+    /// Hits, misses, invalidations and evictions of the path cache
+    pub fn path_cache_stats(&self) -> Vec<u32> {
+        let stats = self.path_cache.stats();
+        vec![
+            stats.hits,
+            stats.misses,
+            stats.invalidations,
+            stats.evictions,
+        ]
+    }
+
+    pub fn path_cache_len(&self) -> usize {
+        self.path_cache.len()
+    }
+
+    /// Drop everything cached and start the statistics over
+    pub fn clear_path_cache(&mut self) {
+        self.path_cache.clear();
+        self.path_cache.reset_stats();
+    }
+
+    /// Drop the cells where the found path goes straight on, returns the cost of the polyline
+    /// None without a path, or with an agent radius, elevation or one-way cells which smoothing does not respect
+    pub fn smooth_path_collinear(&mut self) -> Option<f32> {
+        self.smooth_path(remove_collinear)
+    }
+
+    /// Pull the found path straight wherever that is no more expensive, returns the cost of the polyline
+    pub fn smooth_path_shortcut(&mut self) -> Option<f32> {
+        self.smooth_path(shortcut)
+    }
+
+    /// Shortcut the found path and round off the corners with Chaikin
+    pub fn smooth_path_chaikin(&mut self, iterations: u32) -> Option<f32> {
+        self.smooth_path(|path, weights, width, height| {
+            let pulled = shortcut(path, weights, width, height);
+            chaikin(&pulled.points, iterations, weights, width, height)
+        })
+    }
+
+    /// Shortcut the found path and run a Catmull-Rom spline through the remaining points
+    pub fn smooth_path_catmull_rom(&mut self, samples: u32) -> Option<f32> {
+        self.smooth_path(|path, weights, width, height| {
+            let pulled = shortcut(path, weights, width, height);
+            catmull_rom(&pulled.points, samples, weights, width, height)
+        })
+    }
+
+    /// Points of the smoothed path as flat x, y pairs in cells, empty if there is none
+    pub fn smoothed_path_points(&self) -> Vec<f32> {
+        match &self.smoothed_path {
+            Some(smoothed) => smoothed.points.iter().flat_map(|(x, y)| [*x, *y]).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn clear_smoothed_path(&mut self) {
+        self.smoothed_path = None;
+    }
+
     pub fn tick(&mut self, ticks: u32) -> Option<f32> {
         match self.path_finder.as_mut() {
             Some(p) => p.tick(ticks, &self.cell_weights),
@@ -135,3 +1334,73 @@ impl Board {
         }
     }
 }
+
+impl Board {
+    /// Nearest cell on the board, for coordinates coming from outside
+    fn clamp_to_board(&self, x: u32, y: u32) -> Point {
+        Point::new(x.min(self.width - 1), y.min(self.height - 1))
+    }
+
+    /// Paint the cell over whatever was rendered before
+    fn set_pixel(&mut self, index: u32, (r, g, b): (u8, u8, u8)) {
+        let i = (index * 4) as usize;
+        self.frame_data[i] = r;
+        self.frame_data[i + 1] = g;
+        self.frame_data[i + 2] = b;
+        self.frame_data[i + 3] = 255;
+    }
+
+    /// Smooth the legs of the found path between portal hops
+    /// Straight segments know nothing of clearance, one-way cells or slopes, so there is nothing to smooth with those
+    fn smooth_path(
+        &mut self,
+        mut smooth: impl FnMut(&[u32], &[f32], u32, u32) -> SmoothedPath,
+    ) -> Option<f32> {
+        self.smoothed_path = None;
+
+        if self.agent_radius > SINGLE_CELL_AGENT_RADIUS
+            || self.elevation.is_some()
+            || self.exit_masks.iter().any(|mask| *mask != ALL_EXITS)
+        {
+            return None;
+        }
+
+        let path_finder = self.path_finder.as_ref()?;
+        let path = path_finder.ordered_path()?;
+        let smoothed = smooth_legs(
+            &path,
+            path_finder.portals(),
+            &self.cell_weights,
+            self.width,
+            |leg| smooth(leg, &self.cell_weights, self.width, self.height),
+        );
+        let cost = smoothed.cost;
+
+        self.smoothed_path = Some(smoothed);
+        Some(cost)
+    }
+
+    /// Apply the agent radius, elevation, exit masks, landmarks and components of the board to a search
+    /// Clearance is only needed for agents larger than a single cell
+    fn with_board_settings(&self, mut path_finder: FindPath) -> FindPath {
+        if self.agent_radius > SINGLE_CELL_AGENT_RADIUS {
+            path_finder = path_finder.with_clearance(self.clearance.clone(), self.agent_radius);
+        }
+
+        if let Some(elevation) = &self.elevation {
+            path_finder = path_finder.with_elevation(elevation.clone());
+        }
+
+        if self.exit_masks.iter().any(|mask| *mask != ALL_EXITS) {
+            path_finder = path_finder.with_exit_masks(self.exit_masks.clone());
+        }
+
+        if let Some(landmarks) = &self.landmarks {
+            path_finder = path_finder.with_landmarks(landmarks.clone());
+        }
+
+        path_finder = path_finder.with_components(self.components.clone());
+
+        path_finder
+    }
+}
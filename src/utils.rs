@@ -32,6 +32,19 @@ pub fn rgb_to_hsv(r: u8, g: u8, b: u8) -> HSV {
     }
 }
 
+/// Map a value in 0..1 to a blue -> green -> red heatmap color
+pub fn heatmap_color(value: f32) -> (u8, u8, u8) {
+    let value = value.clamp(0.0, 1.0);
+
+    if value < 0.5 {
+        let t = value * 2.0;
+        (0, (t * 255.0) as u8, ((1.0 - t) * 255.0) as u8)
+    } else {
+        let t = (value - 0.5) * 2.0;
+        ((t * 255.0) as u8, ((1.0 - t) * 255.0) as u8, 0)
+    }
+}
+
 pub fn normalize(
     input_min: f32,
     input_max: f32,
@@ -78,6 +91,38 @@ pub fn image_to_weight_map(
     cell_weights
 }
 
+/// Stack multiple images into a single weight map, one image per floor, first image being the ground floor
+/// All images are expected to have the same dimensions
+pub fn image_layers_to_weight_map(
+    images: &[Image],
+    min_output_weight: f32,
+    max_output_weight: f32,
+) -> Vec<f32> {
+    images
+        .iter()
+        .flat_map(|image| image_to_weight_map(image, min_output_weight, max_output_weight))
+        .collect()
+}
+
+/// Brightness of each pixel as a height, black being 0 and white max_height
+pub fn image_to_height_map(image: &Image, max_height: f32) -> Vec<f32> {
+    let height = image.get_height();
+    let width = image.get_width();
+
+    let mut heights = vec![0.0; (width * height) as usize];
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = image.get_pixel(x, y);
+            let hsv = rgb_to_hsv(pixel.r, pixel.g, pixel.b);
+
+            heights[Point::new(x, y).to_1d_index(width) as usize] = hsv.brightness * max_height;
+        }
+    }
+
+    heights
+}
+
 pub fn image_to_vec(image: &Image) -> Vec<u8> {
     let height = image.get_height();
     let width = image.get_width();
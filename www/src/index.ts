@@ -26,6 +26,9 @@ canvas.style.height = height * (CELL_SIZE / devicePixelRatio) + "px";
 const pointInfoSpan = document.getElementById("point-info") as HTMLElement
 const pathInfoSpan = document.getElementById("path-info") as HTMLElement
 const multiplierInput = document.getElementById("heuristical-multiplier") as HTMLInputElement
+const agentRadiusInput = document.getElementById("agent-radius") as HTMLInputElement
+const turnPenaltyInput = document.getElementById("turn-penalty") as HTMLInputElement
+const turningRadiusInput = document.getElementById("turning-radius") as HTMLInputElement
 const ticksPerFrameRange = document.getElementById("ticks-per-frame") as HTMLInputElement
 
 const context = canvas.getContext('2d');
@@ -44,6 +47,11 @@ const coordinateToPointy = (x: number, y: number): Pointy => ({
 
 let from: Pointy | undefined = undefined;
 let to: Pointy | undefined = undefined;
+let portalFrom: Pointy | undefined = undefined;
+let goals: Pointy[] = [];
+let stops: Pointy[] = [];
+let pointer: Pointy | undefined = undefined;
+let robots: [Pointy, Pointy][] = [];
 
 
 const renderImage = (context: CanvasRenderingContext2D) => {
@@ -70,6 +78,126 @@ const renderImage = (context: CanvasRenderingContext2D) => {
             );
         }
     }
+
+    if (board.has_flow_field()) {
+        drawFlowField(context)
+    }
+
+    drawExitMasks(context)
+
+    drawVehiclePath(context)
+    drawSmoothedPath(context)
+}
+
+const drawVehiclePath = (context: CanvasRenderingContext2D) => {
+    const poses = board.vehicle_path_poses()
+
+    if (poses.length === 0) {
+        return
+    }
+
+    context.beginPath();
+    context.strokeStyle = `rgb(0 200 255)`
+    context.lineWidth = 2;
+    context.moveTo(poses[0] * CELL_SIZE, poses[1] * CELL_SIZE);
+
+    for (let i = 3; i < poses.length; i += 3) {
+        context.lineTo(poses[i] * CELL_SIZE, poses[i + 1] * CELL_SIZE);
+    }
+
+    context.stroke();
+}
+
+const drawSmoothedPath = (context: CanvasRenderingContext2D) => {
+    const points = board.smoothed_path_points()
+
+    if (points.length === 0) {
+        return
+    }
+
+    context.beginPath();
+    context.strokeStyle = `rgb(255 120 0)`
+    context.lineWidth = 2;
+    context.moveTo(points[0] * CELL_SIZE, points[1] * CELL_SIZE);
+
+    for (let i = 2; i < points.length; i += 2) {
+        context.lineTo(points[i] * CELL_SIZE, points[i + 1] * CELL_SIZE);
+    }
+
+    context.stroke();
+}
+
+// headings in the same order as HEADINGS on the rust side, clockwise from east
+const HEADINGS = [[1, 0], [1, 1], [0, 1], [-1, 1], [-1, 0], [-1, -1], [0, -1], [1, -1]]
+
+const oneWayMask = (heading: number) => (1 << heading) | (1 << ((heading + 7) % 8)) | (1 << ((heading + 1) % 8))
+
+// adds an arrow through the center of the cell to the current path
+const arrowPath = (context: CanvasRenderingContext2D, col: number, row: number, dx: number, dy: number) => {
+    const length = Math.hypot(dx, dy)
+    const centerX = (col + 0.5) * CELL_SIZE
+    const centerY = (row + 0.5) * CELL_SIZE
+    const tipX = centerX + (dx / length) * CELL_SIZE * 0.4
+    const tipY = centerY + (dy / length) * CELL_SIZE * 0.4
+
+    context.moveTo(centerX - (dx / length) * CELL_SIZE * 0.4, centerY - (dy / length) * CELL_SIZE * 0.4);
+    context.lineTo(tipX, tipY);
+    context.lineTo(tipX - (dx - dy * 0.5) / length * CELL_SIZE * 0.25, tipY - (dy + dx * 0.5) / length * CELL_SIZE * 0.25);
+    context.moveTo(tipX, tipY);
+    context.lineTo(tipX - (dx + dy * 0.5) / length * CELL_SIZE * 0.25, tipY - (dy - dx * 0.5) / length * CELL_SIZE * 0.25);
+}
+
+const drawFlowField = (context: CanvasRenderingContext2D) => {
+    const directions = new Int8Array(memory.buffer, board.flow_field_directions(), width * height * 2)
+
+    context.beginPath();
+    context.strokeStyle = `rgb(0 0 0 / 0.6)`
+    context.lineWidth = 1;
+
+    for (let row = 0; row < height; row++) {
+        for (let col = 0; col < width; col++) {
+            const i = (row * width + col) * 2
+            const dx = directions[i]
+            const dy = directions[i + 1]
+
+            if (dx === 0 && dy === 0) {
+                continue
+            }
+
+            arrowPath(context, col, row, dx, dy)
+        }
+    }
+
+    context.stroke();
+}
+
+// one-way cells get an arrow in their heading, other restricted cells one per allowed exit
+const drawExitMasks = (context: CanvasRenderingContext2D) => {
+    const masks = new Uint8Array(memory.buffer, board.exit_masks(), width * height)
+
+    context.beginPath();
+    context.strokeStyle = `rgb(0 0 255)`
+    context.lineWidth = 1;
+
+    for (let row = 0; row < height; row++) {
+        for (let col = 0; col < width; col++) {
+            const mask = masks[row * width + col]
+
+            if (mask === 0xFF) {
+                continue
+            }
+
+            const oneWayHeading = HEADINGS.findIndex((_, heading) => oneWayMask(heading) === mask)
+
+            HEADINGS.forEach(([dx, dy], heading) => {
+                if (oneWayHeading >= 0 ? heading === oneWayHeading : (mask & (1 << heading)) !== 0) {
+                    arrowPath(context, col, row, dx, dy)
+                }
+            })
+        }
+    }
+
+    context.stroke();
 }
 
 const drawGrid = (context: CanvasRenderingContext2D) => {
@@ -107,6 +235,46 @@ if (context) {
     canvas.onclick = e => {
         const point = coordinateToPointy(e.offsetX, e.offsetY)
 
+        // alt click twice to link two cells with a portal
+        if (e.altKey) {
+            if (!portalFrom) {
+                portalFrom = point
+            }
+            else {
+                board.add_portal(Point.new(portalFrom.x, portalFrom.y), Point.new(point.x, point.y), 1, true)
+                portalFrom = undefined
+                renderImage(context)
+            }
+            return
+        }
+
+        // ctrl click to add goals, the path goes to whichever is nearest
+        if (e.ctrlKey && from) {
+            goals = [...goals, point]
+            to = point
+            board.start_multi_target_path_find(Point.new(from.x, from.y), new Uint32Array(goals.flatMap(g => [g.x, g.y])), Number.parseInt(multiplierInput.value) ?? 1)
+            tick(ticksPerFrameRange.valueAsNumber > 100 ? 0 : ticksPerFrameRange.valueAsNumber, to)
+            return
+        }
+
+        // shift click to add stops to a route starting from the start point, the last stop is the end
+        if (e.shiftKey && from) {
+            stops = [...stops, point]
+            const route = [from, ...stops]
+            const distance = board.find_route(new Uint32Array(route.flatMap(p => [p.x, p.y])), Number.parseInt(multiplierInput.value) ?? 1)
+            const legs = Array.from(board.route_leg_costs()).map(c => c.toFixed(2)).join(' + ')
+            pathInfoSpan.innerText = distance !== undefined ? `distance: ${distance.toFixed(2)} (${legs})` : `distance: unreachable`
+            renderImage(context)
+            return
+        }
+
+        goals = []
+
+        if (stops.length > 0) {
+            stops = []
+            board.clear_route()
+        }
+
         if (!from) {
             from = point
             board.set_from(point.x, point.y)
@@ -114,6 +282,16 @@ if (context) {
         }
         else {
             to = point
+
+            // turn penalties use the heading aware search which isnt animated
+            if (turnPenaltyInput.valueAsNumber > 0) {
+                const distance = board.find_path_with_turn_penalty(Point.new(from.x, from.y), Point.new(to.x, to.y), turnPenaltyInput.valueAsNumber, Number.parseInt(multiplierInput.value) ?? 1)
+                pathInfoSpan.innerText = distance !== undefined ? `distance: ${distance.toFixed(2)}` : `distance: unreachable`
+                renderImage(context)
+                return
+            }
+
+            board.clear_route()
             board.start_path_find(Point.new(from.x, from.y), Point.new(to.x, to.y), Number.parseInt(multiplierInput.value) ?? 1)
 
             // 0 here works only because the u32 on rust side breaks down and happily wraps around when decreasing...
@@ -122,6 +300,11 @@ if (context) {
         }
     }
 
+    agentRadiusInput.onchange = () => {
+        board.set_agent_radius(agentRadiusInput.valueAsNumber)
+        renderImage(context)
+    }
+
     canvas.oncontextmenu = e => {
         e.preventDefault()
         pathInfoSpan.innerText = `distance: `
@@ -132,8 +315,283 @@ if (context) {
 
     canvas.onpointermove = e => {
         const point = coordinateToPointy(e.offsetX, e.offsetY)
+        pointer = point
         const cellInfo = board.get_cell_info(point.x, point.y)
-        pointInfoSpan.innerText = `x: ${point.x}, y: ${point.y}, weight: ${cellInfo?.toFixed(2)}`
+        const distance = board.get_distance(point.x, point.y)
+        pointInfoSpan.innerText = `x: ${point.x}, y: ${point.y}, weight: ${cellInfo?.toFixed(2)}` + (distance !== undefined ? `, distance: ${distance.toFixed(2)}` : '')
+    }
+
+    // advances time for the moving obstacles and the timed path
+    let clock: number | undefined = undefined
+    const startClock = () => {
+        if (clock === undefined) {
+            clock = setInterval(() => {
+                board.set_time(board.time() + 1)
+                renderImage(context)
+            }, 150)
+        }
+    }
+    const stopClock = () => {
+        clearInterval(clock)
+        clock = undefined
+    }
+
+    // d toggles a distance field seeded from the start point and any goals
+    let showDistanceField = false
+    let showClearance = false
+    let showComponents = false
+    let smoothing = 0
+    let showAlternatives = false
+    let showElevation = false
+    let batchQueries: number[] = []
+    document.onkeydown = e => {
+        if (e.key === 'd') {
+            showDistanceField = !showDistanceField
+
+            if (showDistanceField) {
+                const sources = [...(from ? [from] : []), ...goals]
+                board.compute_distance_field(new Uint32Array(sources.flatMap(s => [s.x, s.y])))
+            }
+            else {
+                board.clear_distance_field()
+            }
+
+            renderImage(context)
+        }
+
+        // t reorders the route stops into the cheapest visiting order
+        if (e.key === 't' && from && stops.length > 0) {
+            const order = board.find_tour(Point.new(from.x, from.y), new Uint32Array(stops.flatMap(p => [p.x, p.y])), false, Number.parseInt(multiplierInput.value) ?? 1)
+
+            if (order) {
+                stops = Array.from(order).map(i => stops[i])
+                const legs = Array.from(board.route_leg_costs())
+                pathInfoSpan.innerText = `distance: ${legs.reduce((a, b) => a + b, 0).toFixed(2)} (${legs.map(c => c.toFixed(2)).join(' + ')})`
+            }
+            else {
+                pathInfoSpan.innerText = `distance: unreachable`
+            }
+
+            renderImage(context)
+        }
+
+        // a toggles the three best alternative paths between the start and end points, p does the same with more diverse routes
+        if ((e.key === 'a' || e.key === 'p') && from && to) {
+            if (showAlternatives) {
+                board.clear_alternatives()
+            }
+            else {
+                const multiplier = Number.parseInt(multiplierInput.value) ?? 1
+                const costs = Array.from(e.key === 'a'
+                    ? board.find_alternatives(Point.new(from.x, from.y), Point.new(to.x, to.y), 3, multiplier)
+                    : board.find_diverse_alternatives(Point.new(from.x, from.y), Point.new(to.x, to.y), 3, 1.5, 0.5, multiplier))
+                pathInfoSpan.innerText = `alternatives: ${costs.map(c => c.toFixed(2)).join(', ')}`
+            }
+
+            showAlternatives = !showAlternatives
+            renderImage(context)
+        }
+
+        // v toggles a path for a car like vehicle between the start and end points, facing from start towards the end
+        // hold shift to allow reversing
+        if (e.key.toLowerCase() === 'v' && from && to) {
+            if (board.vehicle_path_poses().length > 0) {
+                board.clear_vehicle_path()
+            }
+            else {
+                const heading = Math.atan2(to.y - from.y, to.x - from.x)
+                const cost = board.find_vehicle_path(Point.new(from.x, from.y), heading, Point.new(to.x, to.y), heading, turningRadiusInput.valueAsNumber, e.shiftKey)
+                pathInfoSpan.innerText = cost !== undefined ? `vehicle: ${cost.toFixed(2)}` : `vehicle: unreachable`
+            }
+
+            renderImage(context)
+        }
+
+        // e toggles a generated rolling hills elevation layer for the following searches
+        if (e.key === 'e') {
+            showElevation = !showElevation
+
+            if (showElevation) {
+                const heights = new Float32Array(width * height)
+                for (let row = 0; row < height; row++) {
+                    for (let col = 0; col < width; col++) {
+                        heights[row * width + col] = 10 * (Math.sin(col / 8) + Math.cos(row / 6))
+                    }
+                }
+                board.set_elevation(heights, 1, 0.25, 2)
+            }
+            else {
+                board.clear_elevation()
+            }
+
+            pathInfoSpan.innerText = showElevation ? `elevation: on` : `elevation: off`
+        }
+
+        // o makes the cell under the pointer one-way, pressing again rotates it clockwise and finally makes it regular again
+        if (e.key === 'o' && pointer) {
+            const mask = board.get_exit_mask(pointer.x, pointer.y)
+            const heading = HEADINGS.findIndex((_, heading) => oneWayMask(heading) === mask)
+            board.set_exit_mask(pointer.x, pointer.y, heading === 7 ? 0xFF : oneWayMask(heading + 1))
+            renderImage(context)
+        }
+
+        // g adds a guard patrolling between the start and end points, shift+g removes all guards
+        if (e.key.toLowerCase() === 'g') {
+            if (e.shiftKey) {
+                board.clear_obstacles()
+                stopClock()
+            }
+            else if (from && to) {
+                board.add_patrol(Point.new(from.x, from.y), Point.new(to.x, to.y))
+                startClock()
+            }
+
+            renderImage(context)
+        }
+
+        // m finds a path between the start and end points dodging the guards, and plays it back
+        if (e.key === 'm' && from && to) {
+            const distance = board.find_timed_path(Point.new(from.x, from.y), Point.new(to.x, to.y), 0.5, Number.parseInt(multiplierInput.value) ?? 1)
+            pathInfoSpan.innerText = distance !== undefined ? `distance: ${distance.toFixed(2)}, arrival: ${board.arrival_time()}` : `distance: unreachable`
+            startClock()
+            renderImage(context)
+        }
+
+        // n adds a robot going from the start point to the end point and plans all robots together, shift+n removes them
+        if (e.key.toLowerCase() === 'n') {
+            if (e.shiftKey) {
+                robots = []
+                board.clear_agents()
+            }
+            else if (from && to) {
+                robots = [...robots, [from, to]]
+                const cost = board.find_multi_agent_paths(new Uint32Array(robots.flatMap(([s, g]) => [s.x, s.y, g.x, g.y])), 0.5, Number.parseInt(multiplierInput.value) ?? 1)
+                pathInfoSpan.innerText = cost !== undefined ? `robots: ${robots.length}, total: ${cost.toFixed(2)}` : `robots: no solution`
+                startClock()
+            }
+
+            renderImage(context)
+        }
+
+        // w moves the robots with windowed cooperative A* instead, shift+w spawns a couple of hundred robots at random
+        if (e.key.toLowerCase() === 'w') {
+            const queries = e.shiftKey
+                ? Array.from({ length: 200 * 4 }, (_, i) => Math.floor(Math.random() * (i % 2 === 0 ? width : height)))
+                : robots.flatMap(([s, g]) => [s.x, s.y, g.x, g.y])
+            board.start_cooperative(new Uint32Array(queries), 16, 0.5)
+
+            const step = () => {
+                const finished = board.cooperative_tick(ticksPerFrameRange.valueAsNumber)
+                renderImage(context)
+
+                if (!finished) {
+                    requestAnimationFrame(step)
+                }
+            }
+            step()
+        }
+
+        // i runs an anytime search between the start and end points, starting with H* as epsilon, and shows the path improving
+        if (e.key === 'i' && from && to) {
+            board.clear_route()
+            board.start_anytime_path_find(Point.new(from.x, from.y), Point.new(to.x, to.y), Math.max(Number.parseFloat(multiplierInput.value) || 1, 1))
+
+            const step = () => {
+                const finished = board.anytime_tick(ticksPerFrameRange.valueAsNumber)
+                const cost = board.anytime_cost()
+                const bound = board.anytime_bound()
+                pathInfoSpan.innerText = cost !== undefined && bound !== undefined
+                    ? `distance: ${cost.toFixed(2)}, at most ${bound.toFixed(2)}x optimal`
+                    : finished ? `distance: unreachable` : `distance: searching`
+                renderImage(context)
+
+                if (!finished) {
+                    requestAnimationFrame(step)
+                }
+            }
+            step()
+        }
+
+        // l toggles landmarks for the ALT heuristic, picked with the avoid strategy, or farthest point with shift
+        if (e.key.toLowerCase() === 'l') {
+            if (board.has_landmarks()) {
+                board.clear_landmarks()
+            }
+            else {
+                board.compute_landmarks(8, !e.shiftKey)
+            }
+            renderImage(context)
+        }
+
+        // x toggles a wall under the pointer, k shows the connected components the walls split the map into
+        if (e.key === 'x' && pointer) {
+            const weight = board.get_cell_info(pointer.x, pointer.y)
+            board.set_cell_weight(pointer.x, pointer.y, weight !== undefined && weight < 0 ? 1 : -1)
+            pathInfoSpan.innerText = `components: ${board.component_count()}`
+            renderImage(context)
+        }
+
+        if (e.key === 'k') {
+            showComponents = !showComponents
+            board.set_show_components(showComponents)
+            pathInfoSpan.innerText = `components: ${board.component_count()}`
+            renderImage(context)
+        }
+
+        // s cycles through the smoothings of the found path, collinear points removed, shortcut, chaikin, catmull-rom and none
+        if (e.key === 's') {
+            smoothing = (smoothing + 1) % 5
+            const smoothings = [
+                () => { board.clear_smoothed_path(); return undefined },
+                () => board.smooth_path_collinear(),
+                () => board.smooth_path_shortcut(),
+                () => board.smooth_path_chaikin(3),
+                () => board.smooth_path_catmull_rom(8),
+            ]
+            const cost = smoothings[smoothing]()
+            pathInfoSpan.innerText = cost !== undefined ? `smoothed distance: ${cost.toFixed(2)}` : `distance: `
+            renderImage(context)
+        }
+
+        // b finds paths between 50 random pairs of cells in one batch, pressing again clears them
+        // n runs the previous batch again, mostly from the path cache unless the map was edited
+        if (e.key === 'b' || (e.key === 'n' && batchQueries.length > 0)) {
+            if (e.key === 'b' && board.batch_visited_counts().length > 0) {
+                board.clear_batch()
+                pathInfoSpan.innerText = `distance: `
+            }
+            else {
+                if (e.key === 'b') {
+                    batchQueries = Array.from({ length: 50 * 4 }, (_, i) => Math.floor(Math.random() * (i % 2 === 0 ? width : height)))
+                }
+                const start = performance.now()
+                const distances = board.find_paths(new Uint32Array(batchQueries), Number.parseInt(multiplierInput.value) ?? 1)
+                const elapsed = performance.now() - start
+                const visited = board.batch_visited_counts().reduce((a, b) => a + b, 0)
+                const [hits, misses, invalidations] = board.path_cache_stats()
+                pathInfoSpan.innerText = `found ${distances.filter(d => d >= 0).length}/${distances.length} in ${elapsed.toFixed(1)} ms, ${visited} cells visited, cache hits ${hits} misses ${misses} invalidations ${invalidations}`
+            }
+            renderImage(context)
+        }
+
+        // c toggles the clearance map, cells too narrow for the current agent radius are dimmed
+        if (e.key === 'c') {
+            showClearance = !showClearance
+            board.set_show_clearance(showClearance)
+            renderImage(context)
+        }
+
+        // f toggles a flow field towards the start point
+        if (e.key === 'f') {
+            if (board.has_flow_field() || !from) {
+                board.clear_flow_field()
+            }
+            else {
+                board.compute_flow_field(from.x, from.y)
+            }
+
+            renderImage(context)
+        }
     }
 
     renderImage(context)